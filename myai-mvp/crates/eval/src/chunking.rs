@@ -0,0 +1,150 @@
+use anyhow::Result;
+use ingest::{Chunker, DirectoryWalker, IngestPipeline, SemanticChunker, WalkOptions};
+use models::{Embedder, EmbeddingModel};
+use retrieval::HybridIndex;
+use std::path::Path;
+use std::sync::Arc;
+use tracing::{info, warn};
+use types::{AppConfig, Chunk, Document};
+
+/// Texts embedded per model call
+const BATCH_SIZE: usize = 32;
+/// Ranks at which a chunk holding the answer counts as found
+const TOP_K: usize = 10;
+
+#[derive(serde::Deserialize)]
+pub struct ChunkingQuery {
+    pub query: String,
+    /// Text the chunk that answers the query contains
+    pub answer: String,
+}
+
+/// Compares fixed-size and semantic chunking of the files under `dir`.
+/// Chunks are ranked against each query by embedding similarity alone, so
+/// the scores differ only by where the chunks were cut.
+pub async fn compare(index: Arc<HybridIndex>, config: &AppConfig, dir: &Path, queries: &[ChunkingQuery]) -> Result<()> {
+    let pipeline = IngestPipeline::new(config.clone(), index.clone())?;
+    let embedder = &index.models().embedder;
+
+    let walker = DirectoryWalker::new(dir, &WalkOptions::from_config(&config.ingest))?;
+    let mut documents = Vec::new();
+    for path in walker.walk().into_iter().filter(|path| pipeline.supports(path)) {
+        match pipeline.extract(&path).await {
+            Ok(extracted) => {
+                for extracted in extracted {
+                    let doc = Document::new(
+                        path.to_string_lossy().to_string(),
+                        extracted.title.clone().unwrap_or_default(),
+                        "file".to_string(),
+                        ingest::mime_type(&path),
+                    );
+                    documents.push((doc, extracted));
+                }
+            }
+            Err(e) => warn!("Failed to extract {:?}: {}", path, e),
+        }
+    }
+    info!("Comparing chunking of {} documents on {} queries", documents.len(), queries.len());
+
+    let fixed = Chunker::new(
+        embedder.tokenizer(),
+        config.ingest.chunk_size,
+        config.ingest.overlap,
+        EmbeddingModel::MAX_TOKENS,
+    )?;
+    let semantic = SemanticChunker::new(embedder.tokenizer(), &config.ingest.semantic, EmbeddingModel::MAX_TOKENS)?;
+    let mut fixed_chunks = Vec::new();
+    let mut semantic_chunks = Vec::new();
+    for (doc, extracted) in &documents {
+        fixed_chunks.extend(fixed.chunk_document(extracted, doc)?);
+        semantic_chunks.extend(semantic.chunk_document(extracted, doc, embedder).await?);
+    }
+
+    let query_texts: Vec<String> = queries.iter().map(|query| query.query.clone()).collect();
+    let query_embeddings = embed(embedder, &query_texts).await?;
+    for (name, chunks) in [("fixed", fixed_chunks), ("semantic", semantic_chunks)] {
+        evaluate(name, &fixed, embedder, &chunks, queries, &query_embeddings).await?;
+    }
+
+    Ok(())
+}
+
+async fn evaluate(
+    name: &str,
+    counter: &Chunker,
+    embedder: &EmbeddingModel,
+    chunks: &[Chunk],
+    queries: &[ChunkingQuery],
+    query_embeddings: &[Vec<f32>],
+) -> Result<()> {
+    let texts: Vec<String> = chunks.iter().map(|chunk| chunk.text.clone()).collect();
+    let embeddings = embed(embedder, &texts).await?;
+    let tokens = texts
+        .iter()
+        .map(|text| counter.count_tokens(text))
+        .collect::<Result<Vec<_>>>()?;
+    let normalized: Vec<String> = texts.iter().map(|text| normalize(text)).collect();
+
+    let mut reciprocal_ranks = 0.0;
+    let mut hits_at_1 = 0;
+    let mut hits = 0;
+    let mut split_answers = 0;
+    for (query, query_embedding) in queries.iter().zip(query_embeddings) {
+        let answer = normalize(&query.answer);
+        let answers: Vec<bool> = normalized.iter().map(|text| text.contains(&answer)).collect();
+        if !answers.contains(&true) {
+            // No single chunk holds the whole answer
+            split_answers += 1;
+            continue;
+        }
+
+        let mut ranked: Vec<(usize, f32)> = embeddings
+            .iter()
+            .enumerate()
+            .map(|(i, embedding)| (i, cosine(query_embedding, embedding)))
+            .collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+        if let Some(rank) = ranked.iter().take(TOP_K).position(|(i, _)| answers[*i]) {
+            reciprocal_ranks += 1.0 / (rank + 1) as f32;
+            hits += 1;
+            if rank == 0 {
+                hits_at_1 += 1;
+            }
+        }
+    }
+
+    let count = queries.len().max(1) as f32;
+    let mean_tokens = tokens.iter().sum::<usize>() as f32 / tokens.len().max(1) as f32;
+    info!("=== {} CHUNKING ===", name.to_uppercase());
+    info!("Chunks: {} (mean {:.0} tokens, max {})", chunks.len(), mean_tokens, tokens.iter().max().unwrap_or(&0));
+    info!("Answers split across chunks: {}/{}", split_answers, queries.len());
+    info!("MRR@{}: {:.3}", TOP_K, reciprocal_ranks / count);
+    info!("Hit@1: {:.3}", hits_at_1 as f32 / count);
+    info!("Hit@{}: {:.3}", TOP_K, hits as f32 / count);
+
+    Ok(())
+}
+
+async fn embed(embedder: &EmbeddingModel, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+    let mut embeddings = Vec::with_capacity(texts.len());
+    for batch in texts.chunks(BATCH_SIZE) {
+        embeddings.extend(embedder.embed(batch).await?);
+    }
+    Ok(embeddings)
+}
+
+/// Lowercase with runs of whitespace collapsed, so answers match across
+/// line breaks.
+fn normalize(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase()
+}
+
+fn cosine(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm = a.iter().map(|x| x * x).sum::<f32>().sqrt() * b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm == 0.0 {
+        0.0
+    } else {
+        dot / norm
+    }
+}
@@ -1,6 +1,7 @@
 use anyhow::Result;
 use clap::Parser;
 use std::path::PathBuf;
+use std::sync::Arc;
 use std::time::Instant;
 use tracing::info;
 use types::AppConfig;
@@ -9,6 +10,8 @@ use models::ModelManager;
 use retrieval::HybridIndex;
 use storage::StorageManager;
 
+mod chunking;
+
 #[derive(Parser)]
 #[command(name = "eval")]
 #[command(about = "Evaluation harness for MyAI MVP - Personal AGI with Privacy")]
@@ -18,6 +21,11 @@ struct Cli {
     
     #[arg(short, long, default_value = "config/default.toml")]
     config: PathBuf,
+    
+    /// Compare fixed-size and semantic chunking of the files in this
+    /// directory; queries then name the `answer` a chunk should contain
+    #[arg(long)]
+    chunking: Option<PathBuf>,
 }
 
 #[derive(serde::Deserialize)]
@@ -41,10 +49,15 @@ async fn main() -> Result<()> {
     // Initialize components
     let models = ModelManager::new(&config).await?;
     let storage = StorageManager::new(&config.paths.data_dir).await?;
-    let index = HybridIndex::new(storage, models, config).await?;
+    let index = Arc::new(HybridIndex::new(storage, models, config.clone()).await?);
+    
+    if let Some(dir) = &cli.chunking {
+        let queries: Vec<chunking::ChunkingQuery> = load_queries(&cli.file).await?;
+        return chunking::compare(index, &config, dir, &queries).await;
+    }
     
     // Load evaluation queries
-    let queries = load_queries(&cli.file).await?;
+    let queries: Vec<EvalQuery> = load_queries(&cli.file).await?;
     
     info!("Running evaluation on {} queries", queries.len());
     
@@ -64,6 +77,9 @@ async fn main() -> Result<()> {
             date_to: None,
             filters: None,
             stream: false,
+            context: types::ContextMode::Chunk,
+            context_window: 1,
+            language: None,
         };
         
         let (hits, _reasoning) = index.search(&request).await?;
@@ -109,13 +125,13 @@ fn load_config(config_path: &PathBuf) -> Result<AppConfig> {
     }
 }
 
-async fn load_queries(path: &PathBuf) -> Result<Vec<EvalQuery>> {
+async fn load_queries<T: serde::de::DeserializeOwned>(path: &PathBuf) -> Result<Vec<T>> {
     let content = tokio::fs::read_to_string(path).await?;
     let mut queries = Vec::new();
     
     for line in content.lines() {
         if !line.trim().is_empty() {
-            let query: EvalQuery = serde_json::from_str(line)?;
+            let query: T = serde_json::from_str(line)?;
             queries.push(query);
         }
     }
@@ -1,70 +1,262 @@
 use anyhow::Result;
 use std::collections::HashMap;
-use types::{Chunk, Document};
+use tokenizers::Tokenizer;
+use tracing::warn;
+use types::{Chunk, Document, ParentChunk};
 
+use crate::handlers::{self, Extracted};
+
+/// Splits text into chunks measured in the embedder's tokens, so every chunk
+/// reaches the model whole. Chunks end on token (and so char) boundaries,
+/// preferably at the end of a sentence or line.
 pub struct Chunker {
+    tokenizer: Tokenizer,
+    /// Tokens per chunk, before the model's special tokens are added
     chunk_size: usize,
+    /// Tokens repeated from the end of one chunk at the start of the next
     overlap: usize,
 }
 
 impl Chunker {
-    pub fn new(chunk_size: usize, overlap: usize) -> Self {
-        Self {
-            chunk_size,
-            overlap,
+    /// `chunk_size` and `overlap` are in tokens of `tokenizer`. Chunks are
+    /// capped to fit `max_tokens` with the special tokens included.
+    pub fn new(tokenizer: &Tokenizer, chunk_size: usize, overlap: usize, max_tokens: usize) -> Result<Self> {
+        // Sections are measured whole; the chunker does the cutting
+        let mut tokenizer = tokenizer.clone();
+        tokenizer
+            .with_truncation(None)
+            .map_err(|e| anyhow::anyhow!("Failed to configure tokenizer: {}", e))?;
+        tokenizer.with_padding(None);
+        
+        let special_tokens = tokenizer
+            .encode("", true)
+            .map_err(|e| anyhow::anyhow!("Failed to tokenize text: {}", e))?
+            .len();
+        let limit = max_tokens.saturating_sub(special_tokens).max(1);
+        if chunk_size > limit {
+            warn!("chunkSize {} is more than the embedding model reads; using {} tokens", chunk_size, limit);
         }
+        let chunk_size = chunk_size.clamp(1, limit);
+        
+        Ok(Self {
+            tokenizer,
+            chunk_size,
+            // Each chunk has to move the window forward
+            overlap: overlap.min(chunk_size / 2),
+        })
     }
     
-    pub fn chunk(&self, text: &str, doc_id: &str) -> Result<Vec<Chunk>> {
-        let mut chunks = Vec::new();
-        let mut start = 0;
+    /// Chunks each section of an extracted file separately, so no chunk spans
+    /// two pages or sections.
+    pub fn chunk_document(&self, extracted: &Extracted, doc: &Document) -> Result<Vec<Chunk>> {
+        let pieces = extracted
+            .sections
+            .iter()
+            .map(|section| self.split(&section.text))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self::assemble(extracted, doc, pieces, None)?.0)
+    }
+    
+    /// Makes chunks of the pieces cut from each section. Chunks carry the
+    /// document title, the file's own metadata and their section's metadata,
+    /// with OCR line boxes narrowed to the lines each chunk holds; a
+    /// section's `created_at` (RFC 3339) dates its chunks.
+    ///
+    /// With a `parents` chunker, runs of consecutive chunks in a section are
+    /// also joined into parent sections of up to its chunk size, returned
+    /// alongside; every chunk links to the parent that holds it.
+    pub(crate) fn assemble(
+        extracted: &Extracted,
+        doc: &Document,
+        pieces: Vec<Vec<&str>>,
+        parents: Option<&Chunker>,
+    ) -> Result<(Vec<Chunk>, Vec<ParentChunk>)> {
+        let mut base = extracted.metadata.clone();
+        base.insert("title".to_string(), serde_json::Value::String(doc.title.clone()));
         
-        while start < text.len() {
-            let end = (start + self.chunk_size).min(text.len());
-            
-            // Try to break at sentence boundaries
-            let actual_end = if end < text.len() {
-                self.find_sentence_boundary(&text[start..end])
-            } else {
-                end - start
-            };
-            
-            let chunk_text = text[start..start + actual_end].trim();
+        let mut chunks = Vec::new();
+        let mut parent_chunks: Vec<ParentChunk> = Vec::new();
+        for (section, pieces) in extracted.sections.iter().zip(pieces) {
+            let mut metadata = base.clone();
+            metadata.extend(section.metadata.clone());
             
-            if !chunk_text.is_empty() {
-                let mut metadata = HashMap::new();
-                metadata.insert("title".to_string(), serde_json::Value::String("Document".to_string()));
-                metadata.insert("section".to_string(), serde_json::Value::String(format!("chunk_{}", chunks.len())));
-                
-                let chunk = Chunk {
-                    id: uuid::Uuid::new_v4().to_string(),
-                    doc_id: doc_id.to_string(),
-                    text: chunk_text.to_string(),
-                    embedding: None,
-                    metadata,
-                    created_at: chrono::Utc::now(),
-                };
-                
+            let first = chunks.len();
+            for piece in &pieces {
+                let mut chunk = Self::make_chunk(&doc.id, piece, &metadata, chunks.len());
+                let start = offset(&section.text, piece);
+                if let Some(lines) = handlers::image::ocr_lines_in(section, start..start + piece.len()) {
+                    chunk.metadata.insert("ocr_lines".to_string(), lines);
+                }
                 chunks.push(chunk);
             }
             
-            // Move to next chunk with overlap
-            start += actual_end.saturating_sub(self.overlap);
+            if let Some(parents) = parents {
+                for (range, text) in parents.group(&section.text, &pieces)? {
+                    let id = blake3::hash(format!("{}\0{}", doc.id, text).as_bytes()).to_string();
+                    for chunk in &mut chunks[first + range.start..first + range.end] {
+                        chunk.parent_id = Some(id.clone());
+                    }
+                    // A section repeated word for word shares its parent
+                    if !parent_chunks.iter().any(|existing| existing.id == id) {
+                        parent_chunks.push(ParentChunk {
+                            id,
+                            doc_id: doc.id.clone(),
+                            text: text.to_string(),
+                        });
+                    }
+                }
+            }
         }
         
-        Ok(chunks)
+        Ok((chunks, parent_chunks))
     }
     
-    fn find_sentence_boundary(&self, text: &str) -> usize {
-        // Simple sentence boundary detection
-        let sentence_endings = ['.', '!', '?', '\n'];
+    /// Groups consecutive `pieces` of `text` into spans of up to `chunk_size`
+    /// tokens, as the range of pieces in each span and the text it covers.
+    fn group<'a>(&self, text: &'a str, pieces: &[&str]) -> Result<Vec<(std::ops::Range<usize>, &'a str)>> {
+        let mut groups = Vec::new();
+        let mut first = 0;
+        while first < pieces.len() {
+            let start = offset(text, pieces[first]);
+            let mut last = first;
+            while last + 1 < pieces.len() {
+                let next = pieces[last + 1];
+                let end = offset(text, next) + next.len();
+                if self.count_tokens(&text[start..end])? > self.chunk_size {
+                    break;
+                }
+                last += 1;
+            }
+            let end = offset(text, pieces[last]) + pieces[last].len();
+            groups.push((first..last + 1, &text[start..end]));
+            first = last + 1;
+        }
+        Ok(groups)
+    }
+    
+    /// Tokens per chunk after capping to the model's input.
+    pub fn chunk_size(&self) -> usize {
+        self.chunk_size
+    }
+    
+    /// Length of `text` in tokens, without special tokens.
+    pub fn count_tokens(&self, text: &str) -> Result<usize> {
+        Ok(self.encode(text)?.len())
+    }
+    
+    fn make_chunk(doc_id: &str, text: &str, metadata: &HashMap<String, serde_json::Value>, index: usize) -> Chunk {
+        let mut metadata = metadata.clone();
+        metadata
+            .entry("section".to_string())
+            .or_insert_with(|| serde_json::Value::String(format!("chunk_{}", index)));
+        let created_at = metadata
+            .get("created_at")
+            .and_then(|value| value.as_str())
+            .and_then(|value| chrono::DateTime::parse_from_rfc3339(value).ok())
+            .map(|value| value.with_timezone(&chrono::Utc))
+            .unwrap_or_else(chrono::Utc::now);
         
-        for (i, char) in text.char_indices().rev() {
-            if sentence_endings.contains(&char) {
-                return i + 1;
+        Chunk {
+            id: uuid::Uuid::new_v4().to_string(),
+            doc_id: doc_id.to_string(),
+            text: text.to_string(),
+            embedding: None,
+            metadata,
+            created_at,
+            parent_id: None,
+            position: index as u32,
+        }
+    }
+    
+    pub(crate) fn split<'a>(&self, text: &'a str) -> Result<Vec<&'a str>> {
+        let encoding = self.encode(text)?;
+        let offsets = encoding.get_offsets();
+        let words = encoding.get_word_ids();
+        let mut pieces = Vec::new();
+        let mut start = 0;
+        
+        while start < offsets.len() {
+            let mut end = (start + self.chunk_size).min(offsets.len());
+            if end < offsets.len() {
+                end = find_boundary(text, offsets, words, start, end);
+            }
+            
+            // Re-encoding a piece can merge tokens differently at its edges
+            let mut piece = slice(text, offsets[start].0, offsets[end - 1].1);
+            while end - start > 1 && self.encode(piece)?.len() > self.chunk_size {
+                end -= 1;
+                piece = slice(text, offsets[start].0, offsets[end - 1].1);
+            }
+            
+            let piece = piece.trim();
+            if !piece.is_empty() {
+                pieces.push(piece);
+            }
+            if end == offsets.len() {
+                break;
             }
+            
+            // Move to next chunk with overlap, starting at a whole word
+            let mut next = end.saturating_sub(self.overlap).max(start + 1);
+            while next < end && same_word(words, next) {
+                next += 1;
+            }
+            start = next;
         }
         
-        text.len()
+        Ok(pieces)
+    }
+    
+    fn encode(&self, text: &str) -> Result<tokenizers::Encoding> {
+        self.tokenizer
+            .encode(text, false)
+            .map_err(|e| anyhow::anyhow!("Failed to tokenize text: {}", e))
+    }
+}
+
+/// Where to end a chunk that would run to token `end`: after the last
+/// sentence or line ending in its second half, else before the word `end`
+/// would cut in two.
+fn find_boundary(text: &str, offsets: &[(usize, usize)], words: &[Option<u32>], start: usize, end: usize) -> usize {
+    for i in ((start + (end - start) / 2).max(start + 1)..=end).rev() {
+        let last = slice(text, offsets[i - 1].0, offsets[i - 1].1);
+        let gap = slice(text, offsets[i - 1].1, offsets[i].0);
+        // A full stop only ends a sentence before a space: not in `3.14`
+        if last.ends_with(['。', '！', '？'])
+            || (last.ends_with(['.', '!', '?']) && !gap.is_empty())
+            || gap.contains('\n')
+        {
+            return i;
+        }
+    }
+    
+    let mut i = end;
+    while i > start + 1 && same_word(words, i) {
+        i -= 1;
+    }
+    i
+}
+
+/// Byte offset of `part`, a slice of `text`, within it.
+pub(crate) fn offset(text: &str, part: &str) -> usize {
+    part.as_ptr() as usize - text.as_ptr() as usize
+}
+
+/// Whether token `i` continues the word of the token before it.
+fn same_word(words: &[Option<u32>], i: usize) -> bool {
+    i > 0 && words[i].is_some() && words[i] == words[i - 1]
+}
+
+/// `text[start..end]` widened to char boundaries; tokenizers that work on
+/// bytes can report offsets inside a multi-byte character.
+fn slice(text: &str, start: usize, end: usize) -> &str {
+    let mut start = start.min(text.len());
+    let mut end = end.clamp(start, text.len());
+    while !text.is_char_boundary(start) {
+        start -= 1;
+    }
+    while !text.is_char_boundary(end) {
+        end += 1;
     }
+    &text[start..end]
 }
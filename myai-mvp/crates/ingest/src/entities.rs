@@ -0,0 +1,184 @@
+use anyhow::Result;
+use chrono::NaiveDate;
+use models::NerModel;
+use regex::Regex;
+use serde_json::Value;
+use std::sync::Arc;
+use types::{Chunk, EntityConfig, EntityKind};
+
+const MONTHS: &str = r"(jan(?:uary)?|feb(?:ruary)?|mar(?:ch)?|apr(?:il)?|may|june?|july?|aug(?:ust)?|sep(?:t(?:ember)?)?|oct(?:ober)?|nov(?:ember)?|dec(?:ember)?)\.?";
+
+/// Finds the people, organizations, places and dates each chunk mentions and
+/// lists them in its `people`, `organizations`, `places` and `dates`
+/// metadata. Names come from the NER model when it's installed; email
+/// addresses and `@handles` count as people and written-out dates are read
+/// by pattern either way.
+pub struct EntityExtractor {
+    ner: Option<Arc<NerModel>>,
+    min_score: f32,
+    email: Regex,
+    handle: Regex,
+    /// Date patterns, with the order of their year, month and day groups
+    dates: Vec<(Regex, DateOrder)>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum DateOrder {
+    /// `2024-03-15`
+    YearMonthDay,
+    /// `15 March 2024`
+    DayMonthYear,
+    /// `March 15, 2024`
+    MonthDayYear,
+}
+
+/// Entities of one chunk, normalized and without duplicates.
+#[derive(Debug, Default)]
+struct Found {
+    people: Vec<String>,
+    organizations: Vec<String>,
+    places: Vec<String>,
+    dates: Vec<String>,
+}
+
+impl EntityExtractor {
+    pub fn new(ner: Option<Arc<NerModel>>, config: &EntityConfig) -> Result<Self> {
+        Ok(Self {
+            ner,
+            min_score: config.min_score,
+            email: Regex::new(r"(?i)\b[a-z0-9._%+-]+@[a-z0-9-]+(?:\.[a-z0-9-]+)*\.[a-z]{2,}\b")?,
+            // Not the domain of an address, and not a decorator mid-word
+            handle: Regex::new(r"(?:^|[^\w@./])@([A-Za-z0-9_](?:[A-Za-z0-9_.-]{0,38}[A-Za-z0-9_])?)")?,
+            dates: vec![
+                (Regex::new(r"\b(\d{4})-(\d{2})-(\d{2})\b")?, DateOrder::YearMonthDay),
+                (
+                    Regex::new(&format!(r"(?i)\b(\d{{1,2}})(?:st|nd|rd|th)?(?: of)? {},? (\d{{4}})\b", MONTHS))?,
+                    DateOrder::DayMonthYear,
+                ),
+                (
+                    Regex::new(&format!(r"(?i)\b{} (\d{{1,2}})(?:st|nd|rd|th)?,? (\d{{4}})\b", MONTHS))?,
+                    DateOrder::MonthDayYear,
+                ),
+            ],
+        })
+    }
+
+    /// Adds the entities each chunk mentions to its metadata, keeping the
+    /// people its handler already listed (senders, attendees, ...).
+    pub async fn annotate(&self, chunks: &mut [Chunk]) -> Result<()> {
+        let names = match &self.ner {
+            Some(ner) => {
+                let ner = ner.clone();
+                let texts: Vec<String> = chunks.iter().map(|chunk| chunk.text.clone()).collect();
+                tokio::task::spawn_blocking(move || {
+                    texts
+                        .iter()
+                        .map(|text| ner.recognize(text))
+                        .collect::<Result<Vec<_>>>()
+                })
+                .await??
+            }
+            None => vec![Vec::new(); chunks.len()],
+        };
+
+        for (chunk, names) in chunks.iter_mut().zip(names) {
+            let mut found = Found {
+                people: string_list(chunk, EntityKind::Person.metadata_key()),
+                ..Default::default()
+            };
+            for name in names.iter().filter(|name| name.score >= self.min_score) {
+                let list = match name.label.as_str() {
+                    "PER" => &mut found.people,
+                    "ORG" => &mut found.organizations,
+                    "LOC" => &mut found.places,
+                    _ => continue,
+                };
+                if let Some(name) = chunk.text.get(name.start..name.end).and_then(normalize) {
+                    push(list, name);
+                }
+            }
+            for address in self.email.find_iter(&chunk.text) {
+                push(&mut found.people, address.as_str().to_lowercase());
+            }
+            for handle in self.handle.captures_iter(&chunk.text) {
+                push(&mut found.people, format!("@{}", handle[1].to_lowercase()));
+            }
+            for date in self.dates(&chunk.text) {
+                push(&mut found.dates, date);
+            }
+
+            for (kind, list) in [
+                (EntityKind::Person, found.people),
+                (EntityKind::Organization, found.organizations),
+                (EntityKind::Place, found.places),
+                (EntityKind::Date, found.dates),
+            ] {
+                if !list.is_empty() {
+                    chunk.metadata.insert(kind.metadata_key().to_string(), Value::from(list));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Dates written in `text`, as `YYYY-MM-DD`. Numeric dates other than ISO
+    /// ones (`03/04/2024`) are ambiguous and left out.
+    fn dates(&self, text: &str) -> Vec<String> {
+        let mut dates = Vec::new();
+        for (regex, order) in &self.dates {
+            for captures in regex.captures_iter(text) {
+                let (year, month, day) = match order {
+                    DateOrder::YearMonthDay => (&captures[1], captures[2].parse().ok(), &captures[3]),
+                    DateOrder::DayMonthYear => (&captures[3], month_number(&captures[2]), &captures[1]),
+                    DateOrder::MonthDayYear => (&captures[3], month_number(&captures[1]), &captures[2]),
+                };
+                let date = match (year.parse(), month, day.parse()) {
+                    (Ok(year), Some(month), Ok(day)) => NaiveDate::from_ymd_opt(year, month, day),
+                    _ => None,
+                };
+                if let Some(date) = date {
+                    dates.push(date.format("%Y-%m-%d").to_string());
+                }
+            }
+        }
+        dates
+    }
+}
+
+/// A name as it is stored: single spaces, without a possessive `'s` or
+/// punctuation around it. `None` for fragments too short to be a name.
+fn normalize(name: &str) -> Option<String> {
+    let name = name.split_whitespace().collect::<Vec<_>>().join(" ");
+    let name = name.trim_matches(|c: char| !c.is_alphanumeric());
+    let name = name
+        .strip_suffix("'s")
+        .or_else(|| name.strip_suffix("\u{2019}s"))
+        .unwrap_or(name);
+    (name.chars().filter(|c| c.is_alphabetic()).count() >= 2).then(|| name.to_string())
+}
+
+/// Adds `name` unless the list has it in any case.
+fn push(list: &mut Vec<String>, name: String) {
+    let key = name.to_lowercase();
+    if !list.iter().any(|known| known.to_lowercase() == key) {
+        list.push(name);
+    }
+}
+
+fn month_number(name: &str) -> Option<u32> {
+    let prefix = name.get(..3)?.to_lowercase();
+    ["jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec"]
+        .iter()
+        .position(|month| *month == prefix)
+        .map(|index| index as u32 + 1)
+}
+
+/// A list of strings from chunk metadata, as stored.
+fn string_list(chunk: &Chunk, key: &str) -> Vec<String> {
+    chunk
+        .metadata
+        .get(key)
+        .and_then(Value::as_array)
+        .map(|items| items.iter().filter_map(Value::as_str).map(str::to_string).collect())
+        .unwrap_or_default()
+}
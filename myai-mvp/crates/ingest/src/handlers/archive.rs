@@ -0,0 +1,268 @@
+use anyhow::Result;
+use flate2::read::GzDecoder;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::runtime::Handle;
+use tracing::{debug, warn};
+use types::{PrivacyConfig, PART_SEPARATOR};
+use zip::ZipArchive;
+
+use super::{Extracted, FileHandler};
+use crate::{mime_group, mime_type};
+
+/// Entries read from one archive before giving up on the rest
+const MAX_ENTRIES: usize = 10_000;
+
+/// Zip entries that expand more than this many times are treated as bombs
+pub(super) const MAX_COMPRESSION_RATIO: u64 = 100;
+
+/// Entries smaller than this are never treated as bombs, whatever their ratio
+pub(super) const MIN_BOMB_BYTES: u64 = 1024 * 1024;
+
+/// Handles zip, tar and tar.gz archives. Each entry is handed to the handler
+/// for its own type and stored as `archive.zip!/inner/file.md`. Nested
+/// archives are skipped.
+#[derive(Clone)]
+pub struct ArchiveHandler {
+    inner: Arc<Dispatcher>,
+}
+
+struct Dispatcher {
+    handlers: HashMap<String, Box<dyn FileHandler>>,
+    privacy: PrivacyConfig,
+}
+
+impl ArchiveHandler {
+    pub fn new(handlers: HashMap<String, Box<dyn FileHandler>>, privacy: PrivacyConfig) -> Self {
+        Self {
+            inner: Arc::new(Dispatcher { handlers, privacy }),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl FileHandler for ArchiveHandler {
+    async fn extract_text(&self, path: &Path) -> Result<String> {
+        let documents = self.extract_all(path).await?;
+        Ok(documents
+            .iter()
+            .map(Extracted::text)
+            .collect::<Vec<_>>()
+            .join("\n\n"))
+    }
+
+    async fn extract_all(&self, path: &Path) -> Result<Vec<Extracted>> {
+        let path = path.to_path_buf();
+        let inner = self.inner.clone();
+        let runtime = Handle::current();
+        tokio::task::spawn_blocking(move || inner.read_archive(&path, &runtime)).await?
+    }
+}
+
+/// Bytes an archive may still expand to.
+struct Budget {
+    entries: usize,
+    max_entry: u64,
+    remaining: u64,
+}
+
+impl Dispatcher {
+    /// Reads the archive one entry at a time, so only a single entry is ever
+    /// unpacked, into a scratch directory the entry's handler can read.
+    fn read_archive(&self, path: &Path, runtime: &Handle) -> Result<Vec<Extracted>> {
+        let max_bytes = self.privacy.max_file_mb * 1024 * 1024;
+        let mut budget = Budget {
+            entries: 0,
+            max_entry: max_bytes,
+            remaining: max_bytes,
+        };
+        let scratch = ScratchDir::new()?;
+        let mut documents = Vec::new();
+
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        if name.ends_with(".zip") {
+            let mut archive = ZipArchive::new(File::open(path)?)?;
+            for i in 0..archive.len() {
+                let mut entry = match archive.by_index(i) {
+                    Ok(entry) => entry,
+                    Err(e) => {
+                        warn!("Skipping entry {} of {:?}: {}", i, path, e);
+                        continue;
+                    }
+                };
+                if !entry.is_file() {
+                    continue;
+                }
+                let size = entry.size();
+                if size > MIN_BOMB_BYTES && size / entry.compressed_size().max(1) > MAX_COMPRESSION_RATIO {
+                    warn!("Skipping {} in {:?}: compression ratio too high", entry.name(), path);
+                    continue;
+                }
+                let entry_name = entry.name().to_string();
+                let flow = self.extract_entry(&entry_name, &mut entry, &scratch, runtime, &mut budget, &mut documents);
+                if flow.is_break() {
+                    break;
+                }
+            }
+        } else {
+            let reader: Box<dyn Read> = if name.ends_with(".tar") {
+                Box::new(BufReader::new(File::open(path)?))
+            } else {
+                Box::new(GzDecoder::new(BufReader::new(File::open(path)?)))
+            };
+            let mut archive = tar::Archive::new(reader);
+            for entry in archive.entries()? {
+                let mut entry = match entry {
+                    Ok(entry) => entry,
+                    Err(e) => {
+                        // A corrupt tar stream can't be resynchronized
+                        warn!("Stopped reading {:?}: {}", path, e);
+                        break;
+                    }
+                };
+                // Links could point outside the archive; only regular files are read
+                if !entry.header().entry_type().is_file() {
+                    continue;
+                }
+                let entry_name = String::from_utf8_lossy(&entry.path_bytes()).to_string();
+                let flow = self.extract_entry(&entry_name, &mut entry, &scratch, runtime, &mut budget, &mut documents);
+                if flow.is_break() {
+                    break;
+                }
+            }
+        }
+
+        Ok(documents)
+    }
+
+    fn extract_entry(
+        &self,
+        entry_name: &str,
+        reader: &mut dyn Read,
+        scratch: &ScratchDir,
+        runtime: &Handle,
+        budget: &mut Budget,
+        documents: &mut Vec<Extracted>,
+    ) -> ControlFlow<()> {
+        budget.entries += 1;
+        if budget.entries > MAX_ENTRIES {
+            warn!("Archive has more than {} entries, ignoring the rest", MAX_ENTRIES);
+            return ControlFlow::Break(());
+        }
+
+        let Some(name) = safe_name(entry_name) else {
+            warn!("Skipping archive entry with unsafe path: {}", entry_name);
+            return ControlFlow::Continue(());
+        };
+        let entry_path = Path::new(&name);
+        let mime = mime_type(entry_path);
+        let group = mime_group(&mime);
+        if group == "archive" {
+            debug!("Skipping nested archive {}", name);
+            return ControlFlow::Continue(());
+        }
+        let Some(handler) = self
+            .handlers
+            .get(&mime)
+            .filter(|_| self.privacy.allowed_mime_groups.iter().any(|allowed| allowed == group))
+        else {
+            debug!("Skipping unsupported archive entry {} ({})", name, mime);
+            return ControlFlow::Continue(());
+        };
+
+        let file_name = entry_path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| "entry".to_string());
+        // Entries keep their folders, which some formats read meaning from
+        // (a Slack export's channel directories); `safe_name` keeps them inside
+        let unpacked = scratch.0.join(&name);
+        // Sizes in archive headers can lie, so the limits apply to the bytes
+        // actually unpacked
+        let limit = budget.max_entry.min(budget.remaining);
+        let written = unpacked
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|_| File::create(&unpacked))
+            .and_then(|mut file| std::io::copy(&mut reader.take(limit + 1), &mut file));
+        let written = match written {
+            Ok(written) => written,
+            Err(e) => {
+                warn!("Could not unpack {}: {}", name, e);
+                let _ = std::fs::remove_file(&unpacked);
+                return ControlFlow::Continue(());
+            }
+        };
+        if written > limit {
+            let _ = std::fs::remove_file(&unpacked);
+            if limit == budget.max_entry {
+                warn!("Skipping {}: larger than {}MB", name, self.privacy.max_file_mb);
+                budget.remaining -= limit;
+                return ControlFlow::Continue(());
+            }
+            warn!("Archive expands to more than {}MB, ignoring the rest", self.privacy.max_file_mb);
+            return ControlFlow::Break(());
+        }
+        budget.remaining -= written;
+
+        let extracted = runtime.block_on(handler.extract_all(&unpacked));
+        let _ = std::fs::remove_file(&unpacked);
+        match extracted {
+            Ok(extracted) => {
+                for mut document in extracted {
+                    document.part = Some(match document.part.take() {
+                        Some(part) => format!("{}{}{}", name, PART_SEPARATOR, part),
+                        None => name.clone(),
+                    });
+                    document.title.get_or_insert_with(|| file_name.clone());
+                    documents.push(document);
+                }
+            }
+            Err(e) => warn!("Skipping archive entry {}: {}", name, e),
+        }
+        ControlFlow::Continue(())
+    }
+}
+
+/// The entry's path with `.` components removed, or `None` if it is absolute
+/// or climbs out of the archive with `..`.
+fn safe_name(name: &str) -> Option<String> {
+    if name.starts_with('/') || name.starts_with('\\') {
+        return None;
+    }
+    let mut components = Vec::new();
+    for component in name.split(['/', '\\']) {
+        match component {
+            "" | "." => {}
+            ".." => return None,
+            // Windows drive letters, as in C:\
+            component if components.is_empty() && component.contains(':') => return None,
+            component => components.push(component),
+        }
+    }
+    (!components.is_empty()).then(|| components.join("/"))
+}
+
+/// Temporary directory removed with everything in it when dropped.
+struct ScratchDir(PathBuf);
+
+impl ScratchDir {
+    fn new() -> Result<Self> {
+        let path = std::env::temp_dir().join(format!("myai-archive-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&path)?;
+        Ok(Self(path))
+    }
+}
+
+impl Drop for ScratchDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
@@ -0,0 +1,122 @@
+use anyhow::Result;
+use models::{Segment, SpeechModel};
+use serde_json::{json, Value};
+use std::path::Path;
+use std::sync::Arc;
+use tracing::info;
+
+use super::{Extracted, FileHandler, Section};
+
+/// A section spans at most this many seconds of the recording...
+const SECTION_SECONDS: f64 = 60.0;
+/// ...or this many characters of transcript, whichever comes first
+const SECTION_CHARS: usize = 800;
+
+/// Handles voice memos and recordings (WAV, MP3, M4A/AAC, Ogg Vorbis,
+/// FLAC) by transcribing them with the Whisper model. The transcript is cut
+/// into sections of up to a minute, each with the time it starts at, so a
+/// hit can point at `12:34` in the recording.
+#[derive(Clone)]
+pub struct AudioHandler {
+    speech: Arc<SpeechModel>,
+}
+
+impl AudioHandler {
+    pub fn new(speech: Arc<SpeechModel>) -> Self {
+        Self { speech }
+    }
+}
+
+#[async_trait::async_trait]
+impl FileHandler for AudioHandler {
+    async fn extract_text(&self, path: &Path) -> Result<String> {
+        Ok(self.extract(path).await?.text())
+    }
+
+    async fn extract(&self, path: &Path) -> Result<Extracted> {
+        let bytes = tokio::fs::read(path).await?;
+        let speech = self.speech.clone();
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(str::to_lowercase);
+        let started = std::time::Instant::now();
+        let transcript =
+            tokio::task::spawn_blocking(move || speech.transcribe(bytes, extension.as_deref())).await??;
+        info!(
+            "Transcribed {:?} ({} of audio) in {:.1}s",
+            path,
+            timestamp(transcript.duration),
+            started.elapsed().as_secs_f64()
+        );
+
+        let mut extracted = Extracted::default();
+        extracted
+            .metadata
+            .insert("duration".to_string(), Value::String(timestamp(transcript.duration)));
+        extracted
+            .metadata
+            .insert("duration_seconds".to_string(), json!(seconds(transcript.duration)));
+        if let Some(language) = transcript.language {
+            extracted.metadata.insert("language".to_string(), Value::String(language));
+        }
+        extracted.sections = sections(&transcript.segments);
+        Ok(extracted)
+    }
+}
+
+/// Groups consecutive segments into sections of about a minute.
+fn sections(segments: &[Segment]) -> Vec<Section> {
+    let mut sections = Vec::new();
+    let mut group: Vec<&Segment> = Vec::new();
+    let mut length = 0;
+
+    for segment in segments {
+        if let Some(first) = group.first() {
+            if segment.end - first.start > SECTION_SECONDS || length + segment.text.len() > SECTION_CHARS {
+                sections.push(section(&group));
+                group.clear();
+                length = 0;
+            }
+        }
+        length += segment.text.len() + 1;
+        group.push(segment);
+    }
+    if !group.is_empty() {
+        sections.push(section(&group));
+    }
+    sections
+}
+
+fn section(group: &[&Segment]) -> Section {
+    let start = group.first().map_or(0.0, |segment| segment.start);
+    let end = group.last().map_or(0.0, |segment| segment.end);
+    let mut section = Section {
+        text: group
+            .iter()
+            .map(|segment| segment.text.as_str())
+            .collect::<Vec<_>>()
+            .join(" "),
+        ..Default::default()
+    };
+    section.metadata.insert("start".to_string(), Value::String(timestamp(start)));
+    section.metadata.insert("start_seconds".to_string(), json!(seconds(start)));
+    section.metadata.insert("end_seconds".to_string(), json!(seconds(end)));
+    section
+}
+
+/// `12:34`, or `1:02:03` past the hour.
+fn timestamp(seconds: f64) -> String {
+    let total = seconds.max(0.0) as u64;
+    let (hours, minutes, seconds) = (total / 3600, total / 60 % 60, total % 60);
+    if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, minutes, seconds)
+    } else {
+        format!("{}:{:02}", minutes, seconds)
+    }
+}
+
+/// Seconds to the nearest tenth, as stored in metadata.
+fn seconds(value: f64) -> f64 {
+    (value * 10.0).round() / 10.0
+}
@@ -0,0 +1,437 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use rusqlite::{Connection, OpenFlags};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use tracing::debug;
+use url::Url;
+
+use super::{Extracted, FileHandler, HtmlHandler, Section};
+use crate::{mime_group, mime_type};
+
+/// Most recently visited pages kept from a history; bookmarks are always kept
+const MAX_PAGES: usize = 20_000;
+
+/// Microseconds between 1601-01-01, where Chromium counts from, and the Unix epoch
+const WEBKIT_EPOCH_OFFSET: i64 = 11_644_473_600_000_000;
+
+/// Firefox bookmark roots and the names the browser shows for them
+const FIREFOX_ROOTS: &[(&str, &str)] = &[
+    ("menu________", "Bookmarks Menu"),
+    ("toolbar_____", "Bookmarks Toolbar"),
+    ("unfiled_____", "Other Bookmarks"),
+    ("mobile______", "Mobile Bookmarks"),
+];
+
+/// Bookmarks under this Firefox root are tags, named by their folder
+const FIREFOX_TAGS_ROOT: &str = "tags________";
+
+/// Imports a copy of a Firefox `places.sqlite` or a Chromium `History` or
+/// `Bookmarks` file, one document per page keyed by a hash of its URL. A
+/// page saved to disk from the browser has the saved copy's text added.
+pub struct BrowserHandler;
+
+#[async_trait::async_trait]
+impl FileHandler for BrowserHandler {
+    async fn extract_text(&self, path: &Path) -> Result<String> {
+        let pages = self.extract_all(path).await?;
+        Ok(pages
+            .iter()
+            .map(Extracted::text)
+            .collect::<Vec<_>>()
+            .join("\n\n"))
+    }
+
+    async fn extract_all(&self, path: &Path) -> Result<Vec<Extracted>> {
+        let path = path.to_path_buf();
+        let (browser, pages) = tokio::task::spawn_blocking(move || read_browser_data(&path)).await??;
+
+        let mut documents = Vec::with_capacity(pages.len());
+        for page in pages {
+            let mut document = page_document(&page, browser);
+            if let Some(saved) = &page.saved {
+                match HtmlHandler.extract(saved).await {
+                    Ok(extracted) => {
+                        document.metadata.insert(
+                            "saved_page".to_string(),
+                            Value::String(saved.to_string_lossy().to_string()),
+                        );
+                        document.sections.extend(extracted.sections);
+                    }
+                    Err(e) => debug!("Could not read saved page {:?}: {}", saved, e),
+                }
+            }
+            documents.push(document);
+        }
+        Ok(documents)
+    }
+}
+
+#[derive(Default)]
+struct Page {
+    url: String,
+    title: Option<String>,
+    visits: i64,
+    last_visit: Option<DateTime<Utc>>,
+    bookmark: Option<Bookmark>,
+    /// Copy of the page saved from the browser, if it is still on disk
+    saved: Option<PathBuf>,
+}
+
+#[derive(Default)]
+struct Bookmark {
+    title: Option<String>,
+    /// `Bookmarks Toolbar > Rust`
+    folder: Option<String>,
+    tags: Vec<String>,
+    added: Option<DateTime<Utc>>,
+}
+
+/// Tells the three formats apart by content, since `History` and
+/// `Bookmarks` have no extension to go by.
+fn read_browser_data(path: &Path) -> Result<(&'static str, Vec<Page>)> {
+    let mut head = [0u8; 16];
+    let read = std::fs::File::open(path)?.read(&mut head)?;
+    if read == 16 && &head == b"SQLite format 3\0" {
+        let connection = open_read_only(path)?;
+        if has_table(&connection, "moz_places")? {
+            return Ok(("firefox", read_firefox(&connection)?));
+        }
+        if has_table(&connection, "urls")? {
+            return Ok(("chromium", read_chromium_history(&connection)?));
+        }
+        return Err(anyhow::anyhow!("Not a browser history database: {:?}", path));
+    }
+
+    let value: Value = serde_json::from_slice(&std::fs::read(path)?)
+        .map_err(|_| anyhow::anyhow!("Not a browser bookmarks file: {:?}", path))?;
+    let roots = value
+        .get("roots")
+        .and_then(Value::as_object)
+        .ok_or_else(|| anyhow::anyhow!("Not a browser bookmarks file: {:?}", path))?;
+    let mut pages = Vec::new();
+    for root in roots.values() {
+        collect_chromium_bookmarks(root, &mut Vec::new(), &mut pages);
+    }
+    Ok(("chromium", pages))
+}
+
+/// Opens the database without taking a lock, so a profile the browser still
+/// has open can be read, and without ever writing to it.
+fn open_read_only(path: &Path) -> Result<Connection> {
+    let path = std::fs::canonicalize(path)?;
+    let uri = Url::from_file_path(&path)
+        .map_err(|_| anyhow::anyhow!("Cannot open {:?}", path))?;
+    let connection = Connection::open_with_flags(
+        format!("{}?immutable=1", uri),
+        OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_URI | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+    )?;
+    Ok(connection)
+}
+
+fn has_table(connection: &Connection, name: &str) -> Result<bool> {
+    let count: i64 = connection.query_row(
+        "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = ?1",
+        [name],
+        |row| row.get(0),
+    )?;
+    Ok(count > 0)
+}
+
+fn read_firefox(connection: &Connection) -> Result<Vec<Page>> {
+    let mut pages: HashMap<String, Page> = HashMap::new();
+    let mut order = Vec::new();
+
+    let mut statement = connection.prepare(
+        "SELECT url, title, visit_count, last_visit_date FROM moz_places
+         WHERE visit_count > 0 AND hidden = 0
+         ORDER BY last_visit_date DESC",
+    )?;
+    let rows = statement.query_map([], |row| {
+        Ok(Page {
+            url: row.get(0)?,
+            title: row.get(1)?,
+            visits: row.get::<_, Option<i64>>(2)?.unwrap_or(0),
+            last_visit: row.get::<_, Option<i64>>(3)?.and_then(DateTime::from_timestamp_micros),
+            ..Default::default()
+        })
+    })?;
+    for page in rows.filter_map(|row| row.ok()).filter(|page| is_web_page(&page.url)).take(MAX_PAGES) {
+        order.push(page.url.clone());
+        pages.insert(page.url.clone(), page);
+    }
+
+    // Folders, bookmarks and tags all live in one tree
+    struct Node {
+        parent: i64,
+        title: Option<String>,
+        guid: String,
+    }
+    let mut nodes: HashMap<i64, Node> = HashMap::new();
+    let mut bookmarks = Vec::new();
+    let mut statement = connection.prepare(
+        "SELECT b.id, b.parent, b.type, b.title, b.guid, b.dateAdded, p.url, p.title, p.visit_count, p.last_visit_date
+         FROM moz_bookmarks b LEFT JOIN moz_places p ON p.id = b.fk",
+    )?;
+    let mut rows = statement.query([])?;
+    while let Some(row) = rows.next()? {
+        let id: i64 = row.get(0)?;
+        let node = Node {
+            parent: row.get::<_, Option<i64>>(1)?.unwrap_or(0),
+            title: row.get(3)?,
+            guid: row.get::<_, Option<String>>(4)?.unwrap_or_default(),
+        };
+        // Type 1 is a bookmark; folders and separators only shape the tree
+        if row.get::<_, i64>(2)? == 1 {
+            if let Some(url) = row.get::<_, Option<String>>(6)? {
+                let page = Page {
+                    url,
+                    title: row.get(7)?,
+                    visits: row.get::<_, Option<i64>>(8)?.unwrap_or(0),
+                    last_visit: row.get::<_, Option<i64>>(9)?.and_then(DateTime::from_timestamp_micros),
+                    ..Default::default()
+                };
+                let added = row.get::<_, Option<i64>>(5)?.and_then(DateTime::from_timestamp_micros);
+                bookmarks.push((node.parent, node.title.clone(), added, page));
+            }
+        }
+        nodes.insert(id, node);
+    }
+
+    for (parent, title, added, page) in bookmarks {
+        if !is_web_page(&page.url) {
+            continue;
+        }
+        let mut folders = Vec::new();
+        let mut tag = None;
+        let mut current = parent;
+        // Depth guard against a corrupt tree that loops
+        for _ in 0..64 {
+            let Some(node) = nodes.get(&current) else {
+                break;
+            };
+            if node.guid == FIREFOX_TAGS_ROOT {
+                tag = folders.first().cloned();
+                break;
+            }
+            if let Some((_, name)) = FIREFOX_ROOTS.iter().find(|(guid, _)| *guid == node.guid) {
+                folders.push(name.to_string());
+                break;
+            }
+            if node.parent == 0 {
+                break;
+            }
+            folders.push(node.title.clone().unwrap_or_default());
+            current = node.parent;
+        }
+
+        let url = page.url.clone();
+        let page = pages.entry(url.clone()).or_insert_with(|| {
+            order.push(url);
+            page
+        });
+        let bookmark = page.bookmark.get_or_insert_with(Bookmark::default);
+        match tag {
+            Some(tag) => {
+                if !bookmark.tags.contains(&tag) {
+                    bookmark.tags.push(tag);
+                }
+            }
+            None => {
+                folders.reverse();
+                bookmark.title = title.filter(|title| !title.is_empty());
+                bookmark.folder = Some(folders.join(" > ")).filter(|folder| !folder.is_empty());
+                bookmark.added = added;
+            }
+        }
+    }
+
+    let mut pages: Vec<Page> = order.into_iter().filter_map(|url| pages.remove(&url)).collect();
+    let saved = firefox_saved_pages(connection);
+    for page in &mut pages {
+        page.saved = saved.get(&page.url).cloned();
+    }
+    Ok(pages)
+}
+
+/// Pages saved with "Save Page As", which Firefox records as downloads of
+/// the page's own URL.
+fn firefox_saved_pages(connection: &Connection) -> HashMap<String, PathBuf> {
+    let query = "SELECT p.url, a.content FROM moz_annos a
+         JOIN moz_anno_attributes n ON n.id = a.anno_attribute_id
+         JOIN moz_places p ON p.id = a.place_id
+         WHERE n.name = 'downloads/destinationFileURI'";
+    saved_pages(connection, query, |target| Url::parse(target).ok()?.to_file_path().ok())
+}
+
+fn read_chromium_history(connection: &Connection) -> Result<Vec<Page>> {
+    let mut statement = connection.prepare(
+        "SELECT url, title, visit_count, last_visit_time FROM urls
+         WHERE hidden = 0 AND visit_count > 0
+         ORDER BY last_visit_time DESC",
+    )?;
+    let rows = statement.query_map([], |row| {
+        Ok(Page {
+            url: row.get(0)?,
+            title: row.get(1)?,
+            visits: row.get::<_, Option<i64>>(2)?.unwrap_or(0),
+            last_visit: row.get::<_, Option<i64>>(3)?.and_then(from_webkit),
+            ..Default::default()
+        })
+    })?;
+    let mut pages: Vec<Page> = rows
+        .filter_map(|row| row.ok())
+        .filter(|page| is_web_page(&page.url))
+        .take(MAX_PAGES)
+        .collect();
+
+    let query = "SELECT tab_url, target_path FROM downloads WHERE state = 1";
+    let saved = saved_pages(connection, query, |target| Some(PathBuf::from(target)));
+    for page in &mut pages {
+        page.saved = saved.get(&page.url).cloned();
+    }
+    Ok(pages)
+}
+
+/// Runs `query` for (page URL, download target) pairs and keeps the
+/// targets that are web pages still on disk. Browsers that don't have the
+/// tables queried just have no saved pages.
+fn saved_pages(
+    connection: &Connection,
+    query: &str,
+    to_path: impl Fn(&str) -> Option<PathBuf>,
+) -> HashMap<String, PathBuf> {
+    let mut saved = HashMap::new();
+    let Ok(mut statement) = connection.prepare(query) else {
+        return saved;
+    };
+    let Ok(rows) = statement.query_map([], |row| {
+        Ok((row.get::<_, Option<String>>(0)?, row.get::<_, Option<String>>(1)?))
+    }) else {
+        return saved;
+    };
+    for (url, target) in rows.filter_map(|row| row.ok()) {
+        let (Some(url), Some(path)) = (url, target.as_deref().and_then(&to_path)) else {
+            continue;
+        };
+        if mime_group(&mime_type(&path)) == "html" && path.is_file() {
+            saved.insert(url, path);
+        }
+    }
+    saved
+}
+
+/// Walks a Chromium `Bookmarks` tree, keeping the folder path.
+fn collect_chromium_bookmarks(node: &Value, folders: &mut Vec<String>, pages: &mut Vec<Page>) {
+    let name = node.get("name").and_then(Value::as_str).unwrap_or("");
+    match node.get("type").and_then(Value::as_str) {
+        Some("url") => {
+            let Some(url) = node.get("url").and_then(Value::as_str).filter(|url| is_web_page(url)) else {
+                return;
+            };
+            // Chromium writes its timestamps as strings
+            let time = |key: &str| {
+                node.get(key)
+                    .and_then(Value::as_str)
+                    .and_then(|time| time.parse().ok())
+                    .filter(|&time: &i64| time > 0)
+                    .and_then(from_webkit)
+            };
+            pages.push(Page {
+                url: url.to_string(),
+                last_visit: time("date_last_used"),
+                bookmark: Some(Bookmark {
+                    title: Some(name.to_string()).filter(|name| !name.is_empty()),
+                    folder: Some(folders.join(" > ")).filter(|folder| !folder.is_empty()),
+                    tags: Vec::new(),
+                    added: time("date_added"),
+                }),
+                ..Default::default()
+            });
+        }
+        Some("folder") => {
+            folders.push(name.to_string());
+            for child in node.get("children").and_then(Value::as_array).into_iter().flatten() {
+                collect_chromium_bookmarks(child, folders, pages);
+            }
+            folders.pop();
+        }
+        _ => {}
+    }
+}
+
+fn from_webkit(micros: i64) -> Option<DateTime<Utc>> {
+    DateTime::from_timestamp_micros(micros.checked_sub(WEBKIT_EPOCH_OFFSET)?)
+}
+
+/// Web and local pages; browser-internal, extension and script URLs are
+/// left out.
+fn is_web_page(url: &str) -> bool {
+    let scheme = url.split(':').next().unwrap_or("").to_lowercase();
+    matches!(scheme.as_str(), "http" | "https" | "file")
+}
+
+/// A header block naming the page, where it lives and how often it was
+/// visited. The last visit, or for a bookmark never opened the day it was
+/// saved, becomes `created_at`.
+fn page_document(page: &Page, browser: &str) -> Extracted {
+    let title = page
+        .bookmark
+        .as_ref()
+        .and_then(|bookmark| bookmark.title.clone())
+        .or_else(|| page.title.clone().filter(|title| !title.trim().is_empty()))
+        .unwrap_or_else(|| page.url.clone());
+    let mut extracted = Extracted {
+        title: Some(title.clone()),
+        part: Some(blake3::hash(page.url.as_bytes()).to_hex()[..16].to_string()),
+        ..Default::default()
+    };
+    let metadata = &mut extracted.metadata;
+    metadata.insert("browser".to_string(), Value::String(browser.to_string()));
+    metadata.insert("url".to_string(), Value::String(page.url.clone()));
+    if let Some(domain) = Url::parse(&page.url).ok().and_then(|url| url.host_str().map(str::to_string)) {
+        metadata.insert("domain".to_string(), Value::String(domain));
+    }
+
+    let label = if page.bookmark.is_some() { "Bookmark" } else { "Page" };
+    let mut lines = vec![format!("{}: {}", label, title), format!("URL: {}", page.url)];
+    if let Some(bookmark) = &page.bookmark {
+        metadata.insert("bookmarked".to_string(), Value::Bool(true));
+        if let Some(folder) = &bookmark.folder {
+            lines.push(format!("Folder: {}", folder));
+            metadata.insert("folder".to_string(), Value::String(folder.clone()));
+        }
+        if !bookmark.tags.is_empty() {
+            lines.push(format!("Tags: {}", bookmark.tags.join(", ")));
+            metadata.insert("tags".to_string(), Value::from(bookmark.tags.clone()));
+        }
+        if let Some(added) = bookmark.added {
+            lines.push(format!("Bookmarked: {}", added.format("%Y-%m-%d")));
+            metadata.insert("bookmarked_at".to_string(), Value::String(added.to_rfc3339()));
+        }
+    }
+    if page.visits > 0 {
+        metadata.insert("visit_count".to_string(), Value::from(page.visits));
+        match page.last_visit {
+            Some(last) => lines.push(format!("Visits: {}, last on {}", page.visits, last.format("%Y-%m-%d"))),
+            None => lines.push(format!("Visits: {}", page.visits)),
+        }
+    }
+    if let Some(last) = page.last_visit {
+        metadata.insert("last_visit".to_string(), Value::String(last.to_rfc3339()));
+    }
+
+    let dated = page.last_visit.or_else(|| page.bookmark.as_ref().and_then(|bookmark| bookmark.added));
+    if let Some(dated) = dated {
+        metadata.insert("date".to_string(), Value::String(dated.format("%Y-%m-%d").to_string()));
+        metadata.insert("created_at".to_string(), Value::String(dated.to_rfc3339()));
+    }
+
+    extracted.sections.push(Section {
+        text: lines.join("\n"),
+        ..Default::default()
+    });
+    extracted
+}
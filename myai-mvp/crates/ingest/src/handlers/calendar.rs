@@ -0,0 +1,878 @@
+use anyhow::Result;
+use chrono::{DateTime, Datelike, Duration, Months, NaiveDate, NaiveDateTime, TimeZone, Utc, Weekday};
+use chrono_tz::Tz;
+use ical::parser::ical::component::IcalEvent;
+use ical::property::Property;
+use ical::IcalParser;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use tracing::{debug, warn};
+
+use super::{Extracted, FileHandler, Section};
+
+/// Recurring events are expanded from this far back...
+const PAST_DAYS: i64 = 2 * 365;
+
+/// ...to this far ahead of now
+const FUTURE_DAYS: i64 = 365;
+
+/// Occurrences kept per recurring event
+const MAX_OCCURRENCES: usize = 500;
+
+/// Days, weeks, months or years stepped through per recurring event
+const MAX_PERIODS: usize = 20_000;
+
+/// Handles iCalendar files (`.ics`), one document per event. Recurring
+/// events become one document per occurrence within a window around now,
+/// keyed `UID/20240304T090000Z`; an occurrence moved or changed on its own
+/// (`RECURRENCE-ID`) replaces the one it overrides.
+pub struct CalendarHandler;
+
+#[async_trait::async_trait]
+impl FileHandler for CalendarHandler {
+    async fn extract_text(&self, path: &Path) -> Result<String> {
+        let events = self.extract_all(path).await?;
+        Ok(events
+            .iter()
+            .map(Extracted::text)
+            .collect::<Vec<_>>()
+            .join("\n\n"))
+    }
+
+    async fn extract_all(&self, path: &Path) -> Result<Vec<Extracted>> {
+        let path = path.to_path_buf();
+        tokio::task::spawn_blocking(move || read_calendar(&path)).await?
+    }
+}
+
+fn read_calendar(path: &Path) -> Result<Vec<Extracted>> {
+    let now = Utc::now();
+    let window = (now - Duration::days(PAST_DAYS), now + Duration::days(FUTURE_DAYS));
+    let mut documents = Vec::new();
+
+    for calendar in IcalParser::new(BufReader::new(File::open(path)?)) {
+        let calendar = match calendar {
+            Ok(calendar) => calendar,
+            Err(e) => {
+                warn!("Stopped reading {:?}: {}", path, e);
+                break;
+            }
+        };
+        let name = text(&calendar.properties, "X-WR-CALNAME");
+
+        let (events, moved): (Vec<Event>, Vec<Event>) = calendar
+            .events
+            .iter()
+            .filter_map(parse_event)
+            .partition(|event| event.recurrence_id.is_none());
+        // Overrides of single occurrences, by the UID of their series
+        let mut overrides: HashMap<String, Vec<Event>> = HashMap::new();
+        for event in moved {
+            overrides.entry(event.uid.clone()).or_default().push(event);
+        }
+
+        for event in &events {
+            let moved = overrides.remove(&event.uid).unwrap_or_default();
+            documents.extend(expand(event, &moved, window, name.as_deref()));
+            for event in moved.iter().filter(|event| !event.is_cancelled()) {
+                let at = event.start.utc();
+                if at >= window.0 && at <= window.1 {
+                    documents.extend(occurrence_document(event, event.start, name.as_deref()));
+                }
+            }
+        }
+        // Overrides whose series isn't in this file
+        for event in overrides.values().flatten().filter(|event| !event.is_cancelled()) {
+            documents.extend(occurrence_document(event, event.start, name.as_deref()));
+        }
+    }
+
+    Ok(documents)
+}
+
+/// One document for a single event, or one per occurrence of a recurring
+/// one. A series with no occurrence in the window is kept as a single
+/// document at its first start so it can still be found.
+fn expand(event: &Event, moved: &[Event], window: (DateTime<Utc>, DateTime<Utc>), calendar: Option<&str>) -> Vec<Extracted> {
+    let Some(rule) = &event.rule else {
+        return single_document(event, calendar).into_iter().collect();
+    };
+
+    let replaced: HashSet<DateTime<Utc>> = moved
+        .iter()
+        .filter_map(|event| event.recurrence_id)
+        .chain(event.exdates.iter().copied())
+        .collect();
+    let starts = occurrences(rule, event.start, window);
+    if starts.is_empty() {
+        return single_document(event, calendar).into_iter().collect();
+    }
+
+    starts
+        .into_iter()
+        .filter(|start| !replaced.contains(&start.utc()))
+        .filter_map(|start| occurrence_document(event, start, calendar))
+        .collect()
+}
+
+fn single_document(event: &Event, calendar: Option<&str>) -> Option<Extracted> {
+    let mut document = event_document(event, event.start, calendar)?;
+    document.part = Some(event.uid.clone());
+    Some(document)
+}
+
+fn occurrence_document(event: &Event, start: Moment, calendar: Option<&str>) -> Option<Extracted> {
+    let mut document = event_document(event, start, calendar)?;
+    // An override is keyed by the occurrence it replaces, not where it moved
+    let key = event.recurrence_id.unwrap_or_else(|| start.utc());
+    document.part = Some(format!("{}/{}", event.uid, key.format("%Y%m%dT%H%M%SZ")));
+    Some(document)
+}
+
+/// A header block (when, where, who, how often) followed by the
+/// description. The start becomes `created_at` so date filters find the
+/// event by when it happens. `None` if the event ends past the last date
+/// there is.
+fn event_document(event: &Event, start: Moment, calendar: Option<&str>) -> Option<Extracted> {
+    let Some(end) = start.local.checked_add_signed(event.duration) else {
+        debug!("Skipping event with an out-of-range end: {}", event.summary);
+        return None;
+    };
+    let end = start.at(end);
+    let mut extracted = Extracted {
+        title: Some(event.summary.clone()),
+        ..Default::default()
+    };
+    let metadata = &mut extracted.metadata;
+
+    let mut lines = vec![format!("Event: {}", event.summary), format!("When: {}", describe_span(start, end))];
+    if let Some(location) = &event.location {
+        lines.push(format!("Where: {}", location));
+        metadata.insert("location".to_string(), Value::String(location.clone()));
+    }
+
+    let mut people = Vec::new();
+    if let Some(organizer) = &event.organizer {
+        lines.push(format!("Organizer: {}", organizer.formatted()));
+        metadata.insert("organizer".to_string(), Value::String(organizer.formatted()));
+        organizer.collect(&mut people);
+    }
+    if !event.attendees.is_empty() {
+        let attendees: Vec<String> = event.attendees.iter().map(Person::formatted).collect();
+        lines.push(format!("Attendees: {}", attendees.join(", ")));
+        metadata.insert("attendees".to_string(), Value::from(attendees));
+        for attendee in &event.attendees {
+            attendee.collect(&mut people);
+        }
+    }
+    if !people.is_empty() {
+        metadata.insert("people".to_string(), Value::from(people));
+    }
+
+    if let Some(repeats) = &event.repeats {
+        lines.push(format!("Repeats: {}", repeats));
+    }
+    if let Some(status) = &event.status {
+        if !status.eq_ignore_ascii_case("CONFIRMED") {
+            lines.push(format!("Status: {}", status.to_lowercase()));
+        }
+        metadata.insert("status".to_string(), Value::String(status.to_lowercase()));
+    }
+
+    metadata.insert("uid".to_string(), Value::String(event.uid.clone()));
+    metadata.insert("recurring".to_string(), Value::Bool(event.repeats.is_some()));
+    metadata.insert("all_day".to_string(), Value::Bool(start.all_day));
+    metadata.insert("start".to_string(), Value::String(start.stamp()));
+    metadata.insert("end".to_string(), Value::String(end.stamp()));
+    metadata.insert(
+        "date".to_string(),
+        Value::String(start.local.date().format("%Y-%m-%d").to_string()),
+    );
+    metadata.insert("created_at".to_string(), Value::String(start.utc().to_rfc3339()));
+    if let Some(calendar) = calendar {
+        metadata.insert("calendar".to_string(), Value::String(calendar.to_string()));
+    }
+
+    let mut text = lines.join("\n");
+    if let Some(description) = &event.description {
+        text.push_str("\n\n");
+        text.push_str(description);
+    }
+    extracted.sections.push(Section {
+        text,
+        ..Default::default()
+    });
+    Some(extracted)
+}
+
+struct Event {
+    uid: String,
+    summary: String,
+    description: Option<String>,
+    location: Option<String>,
+    status: Option<String>,
+    organizer: Option<Person>,
+    attendees: Vec<Person>,
+    start: Moment,
+    /// Length of every occurrence
+    duration: Duration,
+    rule: Option<Rule>,
+    /// The RRULE in words, also set for rules that aren't expanded
+    repeats: Option<String>,
+    exdates: Vec<DateTime<Utc>>,
+    /// Start of the occurrence this event overrides
+    recurrence_id: Option<DateTime<Utc>>,
+}
+
+impl Event {
+    fn is_cancelled(&self) -> bool {
+        self.status.as_deref().is_some_and(|status| status.eq_ignore_ascii_case("CANCELLED"))
+    }
+}
+
+fn parse_event(event: &IcalEvent) -> Option<Event> {
+    let properties = &event.properties;
+    let Some(start) = property(properties, "DTSTART").and_then(parse_moment) else {
+        debug!("Skipping event without a start: {:?}", text(properties, "SUMMARY"));
+        return None;
+    };
+    let summary = text(properties, "SUMMARY").unwrap_or_else(|| "(untitled event)".to_string());
+
+    let duration = match property(properties, "DTEND").and_then(parse_moment) {
+        Some(end) => end.utc() - start.utc(),
+        None => match property(properties, "DURATION").and_then(|property| property.value.as_deref()) {
+            Some(value) => {
+                let Some(duration) = parse_duration(value) else {
+                    debug!("Skipping event with an unreadable duration {:?}: {}", value, summary);
+                    return None;
+                };
+                duration
+            }
+            None if start.all_day => Duration::days(1),
+            None => Duration::zero(),
+        },
+    };
+
+    let raw_rule = property(properties, "RRULE").and_then(|property| property.value.clone());
+    let rule = raw_rule.as_deref().and_then(|rule| parse_rule(rule, start));
+    if raw_rule.is_some() && rule.is_none() {
+        debug!("Not expanding unsupported recurrence {:?} of {}", raw_rule, summary);
+    }
+    let repeats = match (&rule, raw_rule) {
+        (Some(rule), _) => Some(rule.describe()),
+        (None, raw) => raw.map(|raw| raw.to_lowercase()),
+    };
+
+    let exdates = properties
+        .iter()
+        .filter(|property| property.name.eq_ignore_ascii_case("EXDATE"))
+        .flat_map(|property| {
+            let zone = zone_of(property);
+            let date_only = is_date(property);
+            property
+                .value
+                .as_deref()
+                .unwrap_or("")
+                .split(',')
+                .filter_map(move |value| parse_time(value.trim(), zone, date_only))
+                .map(|moment| moment.utc())
+                .collect::<Vec<_>>()
+        })
+        .collect();
+
+    let uid = text(properties, "UID").unwrap_or_else(|| {
+        let seed = format!("{}{}", summary, start.stamp());
+        blake3::hash(seed.as_bytes()).to_hex()[..16].to_string()
+    });
+
+    Some(Event {
+        uid,
+        summary,
+        description: text(properties, "DESCRIPTION"),
+        location: text(properties, "LOCATION"),
+        status: text(properties, "STATUS"),
+        organizer: property(properties, "ORGANIZER").and_then(parse_person),
+        attendees: properties
+            .iter()
+            .filter(|property| property.name.eq_ignore_ascii_case("ATTENDEE"))
+            // Rooms and equipment aren't people
+            .filter(|property| {
+                !param(property, "CUTYPE").is_some_and(|kind| kind.eq_ignore_ascii_case("ROOM") || kind.eq_ignore_ascii_case("RESOURCE"))
+            })
+            .filter_map(parse_person)
+            .collect(),
+        start,
+        duration: duration.max(Duration::zero()),
+        rule,
+        repeats,
+        exdates,
+        recurrence_id: property(properties, "RECURRENCE-ID")
+            .and_then(parse_moment)
+            .map(|moment| moment.utc()),
+    })
+}
+
+struct Person {
+    name: Option<String>,
+    email: Option<String>,
+}
+
+impl Person {
+    fn formatted(&self) -> String {
+        match (&self.name, &self.email) {
+            (Some(name), Some(email)) => format!("{} <{}>", name, email),
+            (Some(name), None) => name.clone(),
+            (None, Some(email)) => email.clone(),
+            (None, None) => String::new(),
+        }
+    }
+
+    /// Adds the name and lowercased address to `people`, as mail does.
+    fn collect(&self, people: &mut Vec<String>) {
+        for person in self.name.iter().chain(&self.email) {
+            if !people.contains(person) {
+                people.push(person.clone());
+            }
+        }
+    }
+}
+
+/// `ORGANIZER;CN="Alice Smith":mailto:alice@example.com`
+fn parse_person(property: &Property) -> Option<Person> {
+    let name = param(property, "CN")
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty());
+    let email = property.value.as_deref().and_then(|value| {
+        let value = value.trim();
+        value
+            .get(..7)
+            .filter(|scheme| scheme.eq_ignore_ascii_case("mailto:"))
+            .map(|_| value[7..].trim().to_lowercase())
+            .filter(|email| !email.is_empty())
+    });
+    (name.is_some() || email.is_some()).then_some(Person { name, email })
+}
+
+#[derive(Clone, Copy)]
+enum Zone {
+    /// UTC, and floating times that carry no zone at all
+    Utc,
+    Named(Tz),
+}
+
+/// A start or end as written: wall-clock time in its zone. Recurrences are
+/// stepped in wall-clock time so a 9:00 meeting stays at 9:00 across DST.
+#[derive(Clone, Copy)]
+struct Moment {
+    local: NaiveDateTime,
+    zone: Zone,
+    all_day: bool,
+}
+
+impl Moment {
+    fn at(&self, local: NaiveDateTime) -> Moment {
+        Moment { local, ..*self }
+    }
+
+    fn utc(&self) -> DateTime<Utc> {
+        match self.zone {
+            Zone::Utc => self.local.and_utc(),
+            Zone::Named(tz) => tz
+                .from_local_datetime(&self.local)
+                .earliest()
+                // A wall time skipped when clocks go forward
+                .or_else(|| tz.from_local_datetime(&(self.local + Duration::hours(1))).earliest())
+                .map(|at| at.with_timezone(&Utc))
+                .unwrap_or_else(|| self.local.and_utc()),
+        }
+    }
+
+    /// `2024-03-04` for all-day events, RFC 3339 with the event's offset otherwise.
+    fn stamp(&self) -> String {
+        if self.all_day {
+            return self.local.date().format("%Y-%m-%d").to_string();
+        }
+        match self.zone {
+            Zone::Utc => self.utc().to_rfc3339(),
+            Zone::Named(tz) => self.utc().with_timezone(&tz).to_rfc3339(),
+        }
+    }
+}
+
+/// "Mon 4 Mar 2024 10:00–10:30 (Europe/Berlin)", or just the day(s) for
+/// all-day events.
+fn describe_span(start: Moment, end: Moment) -> String {
+    const DAY: &str = "%a %-d %b %Y";
+    const DAY_TIME: &str = "%a %-d %b %Y %H:%M";
+    if start.all_day {
+        // All-day ends are exclusive: a one-day event ends the next midnight
+        let last = (end.local - Duration::days(1)).date().max(start.local.date());
+        return if last == start.local.date() {
+            start.local.format(DAY).to_string()
+        } else {
+            format!("{} – {}", start.local.format(DAY), last.format(DAY))
+        };
+    }
+
+    let zone = match start.zone {
+        Zone::Utc => "UTC",
+        Zone::Named(tz) => tz.name(),
+    };
+    let from = start.local.format(DAY_TIME);
+    if end.local <= start.local {
+        format!("{} ({})", from, zone)
+    } else if end.local.date() == start.local.date() {
+        format!("{}–{} ({})", from, end.local.format("%H:%M"), zone)
+    } else {
+        format!("{} – {} ({})", from, end.local.format(DAY_TIME), zone)
+    }
+}
+
+fn parse_moment(property: &Property) -> Option<Moment> {
+    parse_time(property.value.as_deref()?.trim(), zone_of(property), is_date(property))
+}
+
+/// Reads `20240304T100000Z` (UTC), `20240304T100000` (in `zone`) and
+/// `20240304` (all day).
+fn parse_time(value: &str, zone: Zone, date_only: bool) -> Option<Moment> {
+    if date_only || value.len() == 8 {
+        let date = NaiveDate::parse_from_str(value.get(..8)?, "%Y%m%d").ok()?;
+        return Some(Moment {
+            local: date.and_hms_opt(0, 0, 0)?,
+            zone: Zone::Utc,
+            all_day: true,
+        });
+    }
+    let (value, zone) = match value.strip_suffix('Z') {
+        Some(value) => (value, Zone::Utc),
+        None => (value, zone),
+    };
+    let local = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").ok()?;
+    Some(Moment {
+        local,
+        zone,
+        all_day: false,
+    })
+}
+
+fn is_date(property: &Property) -> bool {
+    param(property, "VALUE").is_some_and(|value| value.eq_ignore_ascii_case("DATE"))
+}
+
+fn zone_of(property: &Property) -> Zone {
+    let Some(name) = param(property, "TZID") else {
+        return Zone::Utc;
+    };
+    // Some exporters prefix the zone name, as in /mozilla.org/20050126_1/Europe/Berlin
+    let suffixes = name.match_indices('/').map(|(i, _)| &name[i + 1..]);
+    for candidate in std::iter::once(name).chain(suffixes) {
+        if let Ok(tz) = candidate.parse::<Tz>() {
+            return Zone::Named(tz);
+        }
+    }
+    debug!("Unknown time zone {}, reading its times as UTC", name);
+    Zone::Utc
+}
+
+/// `PT1H30M`, `P1D`, `P2W`, `-PT15M`
+fn parse_duration(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    let (negative, value) = match value.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, value.strip_prefix('+').unwrap_or(value)),
+    };
+    let mut rest = value.strip_prefix('P')?;
+    let mut total = Duration::zero();
+    let mut in_time = false;
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix('T') {
+            in_time = true;
+            rest = after;
+            continue;
+        }
+        let digits = rest.find(|c: char| !c.is_ascii_digit())?;
+        let amount: i64 = rest[..digits].parse().ok()?;
+        let part = match (rest[digits..].chars().next()?, in_time) {
+            ('W', false) => Duration::try_weeks(amount),
+            ('D', false) => Duration::try_days(amount),
+            ('H', true) => Duration::try_hours(amount),
+            ('M', true) => Duration::try_minutes(amount),
+            ('S', true) => Duration::try_seconds(amount),
+            _ => return None,
+        };
+        // Out of range amounts make the duration unreadable
+        total = total.checked_add(&part?)?;
+        rest = &rest[digits + 1..];
+    }
+    Some(if negative { -total } else { total })
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+/// The subset of RRULE that covers what calendar apps write: FREQ from
+/// daily to yearly with INTERVAL, COUNT, UNTIL, BYDAY (`MO`, `2TU`,
+/// `-1FR`), BYMONTHDAY and BYMONTH.
+struct Rule {
+    frequency: Frequency,
+    interval: u32,
+    count: Option<usize>,
+    until: Option<DateTime<Utc>>,
+    by_day: Vec<(Option<i32>, Weekday)>,
+    by_month_day: Vec<i32>,
+    by_month: Vec<u32>,
+}
+
+fn parse_rule(value: &str, start: Moment) -> Option<Rule> {
+    let mut rule = Rule {
+        frequency: Frequency::Daily,
+        interval: 1,
+        count: None,
+        until: None,
+        by_day: Vec::new(),
+        by_month_day: Vec::new(),
+        by_month: Vec::new(),
+    };
+    let mut frequency = None;
+
+    for part in value.split(';').filter(|part| !part.is_empty()) {
+        let (key, value) = part.split_once('=')?;
+        let values = value.split(',').map(str::trim);
+        match key.trim().to_uppercase().as_str() {
+            "FREQ" => {
+                frequency = Some(match value.trim().to_uppercase().as_str() {
+                    "DAILY" => Frequency::Daily,
+                    "WEEKLY" => Frequency::Weekly,
+                    "MONTHLY" => Frequency::Monthly,
+                    "YEARLY" => Frequency::Yearly,
+                    _ => return None,
+                })
+            }
+            "INTERVAL" => rule.interval = value.trim().parse().ok().filter(|&interval| interval > 0)?,
+            "COUNT" => rule.count = Some(value.trim().parse().ok()?),
+            "UNTIL" => {
+                let until = parse_time(value.trim(), start.zone, false)?;
+                // A date-only UNTIL includes that whole day
+                rule.until = Some(if until.all_day && !start.all_day {
+                    until.utc() + Duration::days(1) - Duration::seconds(1)
+                } else {
+                    until.utc()
+                });
+            }
+            "BYDAY" => {
+                for day in values {
+                    let split = day.len().checked_sub(2)?;
+                    let weekday = parse_weekday(day.get(split..)?)?;
+                    let nth = match day.get(..split)? {
+                        "" => None,
+                        nth => Some(nth.trim_start_matches('+').parse().ok()?),
+                    };
+                    rule.by_day.push((nth, weekday));
+                }
+            }
+            "BYMONTHDAY" => {
+                for day in values {
+                    rule.by_month_day.push(day.parse().ok().filter(|day: &i32| *day != 0 && day.abs() <= 31)?);
+                }
+            }
+            "BYMONTH" => {
+                for month in values {
+                    rule.by_month.push(month.parse().ok().filter(|month| (1..=12).contains(month))?);
+                }
+            }
+            "WKST" => {}
+            // BYSETPOS, BYWEEKNO, BYYEARDAY, BYHOUR, ...
+            _ => return None,
+        }
+    }
+
+    rule.frequency = frequency?;
+    Some(rule)
+}
+
+fn parse_weekday(code: &str) -> Option<Weekday> {
+    Some(match code.to_uppercase().as_str() {
+        "MO" => Weekday::Mon,
+        "TU" => Weekday::Tue,
+        "WE" => Weekday::Wed,
+        "TH" => Weekday::Thu,
+        "FR" => Weekday::Fri,
+        "SA" => Weekday::Sat,
+        "SU" => Weekday::Sun,
+        _ => return None,
+    })
+}
+
+impl Rule {
+    /// "every 2 weeks on Mon, Thu until 2024-06-30"
+    fn describe(&self) -> String {
+        let unit = match self.frequency {
+            Frequency::Daily => "day",
+            Frequency::Weekly => "week",
+            Frequency::Monthly => "month",
+            Frequency::Yearly => "year",
+        };
+        let mut text = if self.interval == 1 {
+            format!("every {}", unit)
+        } else {
+            format!("every {} {}s", self.interval, unit)
+        };
+        if !self.by_day.is_empty() {
+            let days: Vec<String> = self
+                .by_day
+                .iter()
+                .map(|(nth, weekday)| match nth {
+                    Some(-1) => format!("last {}", weekday),
+                    Some(nth) => format!("{} {}", ordinal(*nth), weekday),
+                    None => weekday.to_string(),
+                })
+                .collect();
+            text.push_str(&format!(" on {}", days.join(", ")));
+        }
+        if !self.by_month_day.is_empty() {
+            let days: Vec<String> = self
+                .by_month_day
+                .iter()
+                .map(|&day| if day == -1 { "last day".to_string() } else { ordinal(day) })
+                .collect();
+            text.push_str(&format!(" on the {}", days.join(", ")));
+        }
+        if let Some(until) = self.until {
+            text.push_str(&format!(" until {}", until.format("%Y-%m-%d")));
+        } else if let Some(count) = self.count {
+            text.push_str(&format!(", {} times", count));
+        }
+        text
+    }
+
+    /// Days of the period `step` periods after the one holding `first`.
+    /// `None` once dates run out of range.
+    fn days(&self, first: NaiveDate, step: u32) -> Option<Vec<NaiveDate>> {
+        let mut days = match self.frequency {
+            Frequency::Daily => {
+                let day = first.checked_add_signed(Duration::days(step as i64))?;
+                let weekday_matches = self.by_day.is_empty() || self.by_day.iter().any(|(_, weekday)| *weekday == day.weekday());
+                let month_day_matches = self.by_month_day.is_empty() || month_days(day, &self.by_month_day).contains(&day);
+                if weekday_matches && month_day_matches { vec![day] } else { Vec::new() }
+            }
+            Frequency::Weekly => {
+                let monday = first.checked_sub_signed(Duration::days(first.weekday().num_days_from_monday() as i64))?;
+                let week = monday.checked_add_signed(Duration::weeks(step as i64))?;
+                if self.by_day.is_empty() {
+                    vec![week + Duration::days(first.weekday().num_days_from_monday() as i64)]
+                } else {
+                    self.by_day
+                        .iter()
+                        .map(|(_, weekday)| week + Duration::days(weekday.num_days_from_monday() as i64))
+                        .collect()
+                }
+            }
+            Frequency::Monthly => {
+                let month = first.with_day(1)?.checked_add_months(Months::new(step))?;
+                self.month_days(month, first.day())
+            }
+            Frequency::Yearly => {
+                let year = first.year().checked_add(step as i32)?;
+                if !self.by_day.is_empty() && self.by_month.is_empty() && self.by_month_day.is_empty() {
+                    // Weekdays counted through the whole year: 20MO is the 20th Monday
+                    let from = NaiveDate::from_ymd_opt(year, 1, 1)?;
+                    weekdays(from, NaiveDate::from_ymd_opt(year, 12, 31)?, &self.by_day)
+                } else {
+                    let months = if !self.by_month.is_empty() {
+                        self.by_month.clone()
+                    } else if !self.by_month_day.is_empty() {
+                        (1..=12).collect()
+                    } else {
+                        vec![first.month()]
+                    };
+                    months
+                        .into_iter()
+                        .filter_map(|month| NaiveDate::from_ymd_opt(year, month, 1))
+                        .flat_map(|month| self.month_days(month, first.day()))
+                        .collect()
+                }
+            }
+        };
+        if !self.by_month.is_empty() {
+            days.retain(|day| self.by_month.contains(&day.month()));
+        }
+        days.sort();
+        days.dedup();
+        Some(days)
+    }
+
+    /// Days of the month starting `month` that the rule picks; the start's
+    /// own day of the month when it names none. Months too short for that
+    /// day are skipped, as RFC 5545 says.
+    fn month_days(&self, month: NaiveDate, default_day: u32) -> Vec<NaiveDate> {
+        let last = month
+            .checked_add_months(Months::new(1))
+            .and_then(|next| next.pred_opt())
+            .unwrap_or(month);
+        if !self.by_month_day.is_empty() {
+            let mut days = month_days(month, &self.by_month_day);
+            if !self.by_day.is_empty() {
+                days.retain(|day| self.by_day.iter().any(|(_, weekday)| *weekday == day.weekday()));
+            }
+            days
+        } else if !self.by_day.is_empty() {
+            weekdays(month, last, &self.by_day)
+        } else {
+            month.with_day(default_day).into_iter().collect()
+        }
+    }
+
+    /// Period to start stepping from so that, without a COUNT to honour, a
+    /// long-running series doesn't walk every period since it began.
+    fn first_step(&self, first: NaiveDate, window_start: NaiveDate) -> u32 {
+        if self.count.is_some() || window_start <= first {
+            return 0;
+        }
+        let periods = match self.frequency {
+            Frequency::Daily => (window_start - first).num_days(),
+            Frequency::Weekly => (window_start - first).num_weeks(),
+            Frequency::Monthly => (window_start.year() - first.year()) as i64 * 12 + window_start.month() as i64 - first.month() as i64,
+            Frequency::Yearly => (window_start.year() - first.year()) as i64,
+        };
+        // One period early, so nothing at the edge of the window is missed
+        (periods / self.interval as i64 - 1).max(0) as u32
+    }
+}
+
+/// Days of `month`'s month named by BYMONTHDAY values; negative ones count
+/// from the end.
+fn month_days(month: NaiveDate, by_month_day: &[i32]) -> Vec<NaiveDate> {
+    let first = month.with_day(1).unwrap_or(month);
+    let length = first
+        .checked_add_months(Months::new(1))
+        .map(|next| (next - first).num_days() as i32)
+        .unwrap_or(31);
+    by_month_day
+        .iter()
+        .map(|&day| if day > 0 { day } else { length + day + 1 })
+        .filter(|&day| day >= 1 && day <= length)
+        .filter_map(|day| first.with_day(day as u32))
+        .collect()
+}
+
+/// Days from `from` to `to` matching BYDAY values; `2TU` is the second
+/// Tuesday in the range and `-1FR` the last Friday.
+fn weekdays(from: NaiveDate, to: NaiveDate, by_day: &[(Option<i32>, Weekday)]) -> Vec<NaiveDate> {
+    let mut days = Vec::new();
+    for &(nth, weekday) in by_day {
+        let matching: Vec<NaiveDate> = from
+            .iter_days()
+            .take_while(|day| *day <= to)
+            .filter(|day| day.weekday() == weekday)
+            .collect();
+        match nth {
+            None => days.extend(matching),
+            Some(nth) if nth > 0 => days.extend(matching.get(nth as usize - 1)),
+            Some(nth) => days.extend(matching.len().checked_sub(nth.unsigned_abs() as usize).and_then(|i| matching.get(i))),
+        }
+    }
+    days
+}
+
+fn ordinal(n: i32) -> String {
+    let suffix = match (n.abs() % 10, n.abs() % 100) {
+        (1, 11) | (2, 12) | (3, 13) => "th",
+        (1, _) => "st",
+        (2, _) => "nd",
+        (3, _) => "rd",
+        _ => "th",
+    };
+    format!("{}{}", n, suffix)
+}
+
+/// Occurrence starts of a recurring event inside `window`. COUNT is
+/// counted from DTSTART, which is always the first occurrence, so
+/// occurrences before the window still use up the count.
+fn occurrences(rule: &Rule, start: Moment, window: (DateTime<Utc>, DateTime<Utc>)) -> Vec<Moment> {
+    let mut found = Vec::new();
+    let mut counted = 0;
+    let mut keep = |occurrence: Moment, found: &mut Vec<Moment>| -> bool {
+        let at = occurrence.utc();
+        counted += 1;
+        if rule.until.is_some_and(|until| at > until) || rule.count.is_some_and(|count| counted > count) || at > window.1 {
+            return false;
+        }
+        if at >= window.0 {
+            found.push(occurrence);
+        }
+        found.len() < MAX_OCCURRENCES
+    };
+
+    if !keep(start, &mut found) {
+        return found;
+    }
+    let first = start.local.date();
+    let time = start.local.time();
+    let from = rule.first_step(first, window.0.date_naive());
+    for period in 0..MAX_PERIODS as u32 {
+        let Some(step) = (from + period).checked_mul(rule.interval) else {
+            break;
+        };
+        let Some(days) = rule.days(first, step) else {
+            break;
+        };
+        for day in days {
+            let occurrence = start.at(day.and_time(time));
+            if occurrence.local <= start.local {
+                continue;
+            }
+            if !keep(occurrence, &mut found) {
+                return found;
+            }
+        }
+    }
+    found
+}
+
+fn property<'a>(properties: &'a [Property], name: &str) -> Option<&'a Property> {
+    properties.iter().find(|property| property.name.eq_ignore_ascii_case(name))
+}
+
+fn param<'a>(property: &'a Property, name: &str) -> Option<&'a str> {
+    property
+        .params
+        .as_ref()?
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))?
+        .1
+        .first()
+        .map(String::as_str)
+}
+
+/// The property's value with iCalendar escapes undone, if not blank.
+fn text(properties: &[Property], name: &str) -> Option<String> {
+    let value = unescape(property(properties, name)?.value.as_deref()?);
+    let value = value.trim();
+    (!value.is_empty()).then(|| value.to_string())
+}
+
+/// Undoes `\n`, `\,`, `\;` and `\\`, shared by iCalendar and vCard.
+pub(crate) fn unescape(value: &str) -> String {
+    let mut text = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            text.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n' | 'N') => text.push('\n'),
+            Some(other) => text.push(other),
+            None => text.push('\\'),
+        }
+    }
+    text
+}
@@ -0,0 +1,409 @@
+use anyhow::Result;
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+use super::{Extracted, Section};
+
+/// A pause longer than this starts a new thread
+const THREAD_GAP_MINUTES: i64 = 30;
+
+/// Messages per thread before a long conversation is cut into the next one
+const MAX_THREAD_MESSAGES: usize = 40;
+
+/// Slack events that aren't part of the conversation
+const SLACK_NOISE: &[&str] = &["channel_join", "channel_leave", "channel_purpose", "channel_topic", "channel_name"];
+
+struct Message {
+    at: DateTime<Utc>,
+    author: String,
+    text: String,
+    /// Slack `ts`, which replies point to with `thread_ts`
+    id: Option<String>,
+    parent: Option<String>,
+}
+
+/// Reads a Slack channel-day file or a DiscordChatExporter JSON export.
+/// Returns `None` for any other JSON so it can be read as records.
+pub fn read_chat_json(path: &Path) -> Result<Option<Extracted>> {
+    let mut head = Vec::new();
+    File::open(path)?.take(4096).read_to_end(&mut head)?;
+    let head: String = String::from_utf8_lossy(&head)
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect();
+
+    let slack = head.starts_with('[') && head.contains("\"ts\":\"") && head.contains("\"type\":\"message\"");
+    let discord = head.starts_with("{\"guild\":");
+    if !slack && !discord {
+        return Ok(None);
+    }
+
+    let value: Value = serde_json::from_reader(BufReader::new(File::open(path)?))?;
+    Ok(if slack { parse_slack(&value, path) } else { parse_discord(&value) })
+}
+
+/// A Slack export stores each channel as a folder of `YYYY-MM-DD.json`
+/// files; the folder names the channel.
+fn parse_slack(value: &Value, path: &Path) -> Option<Extracted> {
+    let items = value.as_array()?;
+    let channel = path
+        .parent()
+        .and_then(Path::file_name)
+        .map(|name| format!("#{}", name.to_string_lossy()))
+        .unwrap_or_else(|| "slack".to_string());
+
+    // Mentions carry only user ids; messages carry their author's profile
+    let mut users: HashMap<String, String> = HashMap::new();
+    for item in items {
+        if let (Some(id), Some(name)) = (item["user"].as_str(), slack_author(item)) {
+            users.insert(id.to_string(), name);
+        }
+    }
+
+    let messages = items
+        .iter()
+        .filter(|item| item["type"] == "message")
+        .filter(|item| !item["subtype"].as_str().is_some_and(|subtype| SLACK_NOISE.contains(&subtype)))
+        .filter_map(|item| {
+            let id = item["ts"].as_str()?;
+            let seconds: f64 = id.parse().ok()?;
+            let at = DateTime::from_timestamp_millis((seconds * 1000.0) as i64)?;
+            let author = slack_author(item)
+                .or_else(|| item["user"].as_str().map(|user| users.get(user).cloned().unwrap_or_else(|| user.to_string())))
+                .unwrap_or_else(|| "unknown".to_string());
+            let text = slack_markup(item["text"].as_str().unwrap_or_default(), &users);
+            Some(Message {
+                at,
+                author,
+                text,
+                id: Some(id.to_string()),
+                parent: item["thread_ts"].as_str().filter(|parent| *parent != id).map(str::to_string),
+            })
+        })
+        .collect();
+
+    let day = path.file_stem().map(|stem| stem.to_string_lossy().to_string());
+    let title = match day {
+        Some(day) => format!("{} {}", channel, day),
+        None => channel.clone(),
+    };
+    Some(conversation("slack", &channel, title, messages))
+}
+
+fn slack_author(item: &Value) -> Option<String> {
+    let profile = &item["user_profile"];
+    [&profile["real_name"], &profile["display_name"], &item["user_name"], &item["username"]]
+        .into_iter()
+        .filter_map(Value::as_str)
+        .map(str::trim)
+        .find(|name| !name.is_empty())
+        .map(str::to_string)
+}
+
+/// Resolves `<@U123>` mentions, `<#C123|general>` channel links and
+/// `<https://...|label>` links to plain text.
+fn slack_markup(text: &str, users: &HashMap<String, String>) -> String {
+    let mut out = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find('<') {
+        let Some(len) = rest[start..].find('>') else {
+            break;
+        };
+        out.push_str(&rest[..start]);
+        let inner = &rest[start + 1..start + len];
+        let (target, label) = match inner.split_once('|') {
+            Some((target, label)) => (target, Some(label)),
+            None => (inner, None),
+        };
+        if let Some(user) = target.strip_prefix('@') {
+            out.push('@');
+            out.push_str(label.or_else(|| users.get(user).map(String::as_str)).unwrap_or(user));
+        } else if let Some(channel) = target.strip_prefix('#') {
+            out.push('#');
+            out.push_str(label.unwrap_or(channel));
+        } else if let Some(special) = target.strip_prefix('!') {
+            out.push('@');
+            out.push_str(special);
+        } else {
+            out.push_str(label.unwrap_or(target));
+        }
+        rest = &rest[start + len + 1..];
+    }
+    out.push_str(rest);
+    out.replace("&lt;", "<").replace("&gt;", ">").replace("&amp;", "&")
+}
+
+/// DiscordChatExporter's JSON: `guild`, `channel` and a `messages` array.
+fn parse_discord(value: &Value) -> Option<Extracted> {
+    let channel_name = value["channel"]["name"].as_str().unwrap_or("discord");
+    let channel = format!("#{}", channel_name);
+    let title = match value["guild"]["name"].as_str() {
+        Some(guild) => format!("{} / {}", guild, channel),
+        None => channel.clone(),
+    };
+
+    let messages = value["messages"]
+        .as_array()?
+        .iter()
+        .filter_map(|item| {
+            let at = DateTime::parse_from_rfc3339(item["timestamp"].as_str()?).ok()?.with_timezone(&Utc);
+            let author = &item["author"];
+            let author = [&author["nickname"], &author["name"]]
+                .into_iter()
+                .filter_map(Value::as_str)
+                .find(|name| !name.trim().is_empty())
+                .unwrap_or("unknown")
+                .to_string();
+            let mut text = item["content"].as_str().unwrap_or_default().to_string();
+            for attachment in item["attachments"].as_array().into_iter().flatten() {
+                if let Some(name) = attachment["fileName"].as_str() {
+                    text.push_str(&format!(" [attachment: {}]", name));
+                }
+            }
+            Some(Message {
+                at,
+                author,
+                text: text.trim().to_string(),
+                id: None,
+                parent: None,
+            })
+        })
+        .collect();
+
+    let mut extracted = conversation("discord", &channel, title, messages);
+    if let Some(guild) = value["guild"]["name"].as_str() {
+        extracted.metadata.insert("guild".to_string(), Value::String(guild.to_string()));
+    }
+    Some(extracted)
+}
+
+/// Whether a text file is named like a chat export: "WhatsApp Chat with
+/// Alice.txt", `_chat.txt` in a "WhatsApp Chat - Alice" folder, or a
+/// Signal export with "signal" in its name. Other text is never read as a
+/// chat, however much it looks like one; log lines start with timestamps
+/// too.
+pub fn is_chat_export(path: &Path) -> bool {
+    let Some(file_name) = path.file_stem().map(|stem| stem.to_string_lossy()) else {
+        return false;
+    };
+    if file_name == "_chat" {
+        return path
+            .parent()
+            .and_then(Path::file_name)
+            .is_some_and(|folder| folder.to_string_lossy().starts_with("WhatsApp Chat"));
+    }
+    file_name.starts_with("WhatsApp Chat") || file_name.to_lowercase().contains("signal")
+}
+
+/// Reads a WhatsApp or Signal text export:
+/// `[05/01/2024, 14:03:22] Alice: Hi` (WhatsApp on iOS),
+/// `05/01/2024, 14:03 - Alice: Hi` (WhatsApp on Android) or
+/// `[2024-01-05 14:03] Alice: Hi` (Signal exports). Returns `None` for
+/// text that doesn't look like one.
+pub fn parse_chat_text(text: &str, path: &Path) -> Option<Extracted> {
+    let lines: Vec<String> = text
+        .lines()
+        .map(|line| {
+            line.trim_start_matches(['\u{200e}', '\u{200f}', '\u{feff}'])
+                .replace('\u{202f}', " ")
+        })
+        .collect();
+
+    // Day-first and month-first dates look alike line by line, so the
+    // format is chosen once for the whole file, day-first on a tie
+    let candidates: Vec<&str> = lines.iter().take(200).filter_map(|line| split_header(line)).map(|(stamp, _)| stamp).collect();
+    let format = TIMESTAMP_FORMATS
+        .iter()
+        .rev()
+        .max_by_key(|format| candidates.iter().filter(|stamp| NaiveDateTime::parse_from_str(stamp, format).is_ok()).count())?;
+    let first = lines.iter().find(|line| !line.trim().is_empty())?;
+    let is_header = |line: &str| {
+        split_header(line).is_some_and(|(stamp, _)| NaiveDateTime::parse_from_str(stamp, format).is_ok())
+    };
+    let headers = lines.iter().take(50).filter(|line| is_header(line)).count();
+    if !is_header(first) || headers < 3 {
+        return None;
+    }
+
+    let mut messages: Vec<Message> = Vec::new();
+    let mut in_message = false;
+    for line in &lines {
+        match split_header(line) {
+            Some((stamp, rest)) if is_header(line) => {
+                let at = NaiveDateTime::parse_from_str(stamp, format).ok()?.and_utc();
+                // Lines without an author are notices such as "Alice joined";
+                // iOS marks notices and omitted media with a leading LRM
+                in_message = match rest.split_once(": ").filter(|(_, body)| !body.starts_with('\u{200e}')) {
+                    Some((author, body)) => {
+                        messages.push(Message {
+                            at,
+                            author: author.trim().to_string(),
+                            text: body.replace('\u{200e}', "").trim().to_string(),
+                            id: None,
+                            parent: None,
+                        });
+                        true
+                    }
+                    None => false,
+                };
+            }
+            _ if in_message => {
+                if let Some(message) = messages.last_mut() {
+                    message.text.push('\n');
+                    message.text.push_str(line.trim_end());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let file_name = path.file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default();
+    let platform = if file_name.to_lowercase().contains("signal") { "signal" } else { "whatsapp" };
+    // "WhatsApp Chat with Alice.txt", or `_chat.txt` in a "WhatsApp Chat - Alice" folder
+    let named = if file_name.starts_with('_') {
+        path.parent().and_then(Path::file_name).map(|name| name.to_string_lossy().to_string())
+    } else {
+        Some(file_name.clone())
+    };
+    let channel = named
+        .as_deref()
+        .map(|name| {
+            name.trim_start_matches("WhatsApp Chat with ")
+                .trim_start_matches("WhatsApp Chat - ")
+                .trim()
+                .to_string()
+        })
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| platform.to_string());
+    let title = named.unwrap_or_else(|| channel.clone());
+
+    Some(conversation(platform, &channel, title, messages))
+}
+
+const TIMESTAMP_FORMATS: &[&str] = &[
+    "%d/%m/%Y, %H:%M:%S",
+    "%d/%m/%Y, %H:%M",
+    "%m/%d/%Y, %H:%M:%S",
+    "%m/%d/%Y, %H:%M",
+    "%d/%m/%y, %H:%M:%S",
+    "%d/%m/%y, %H:%M",
+    "%m/%d/%y, %H:%M",
+    "%m/%d/%y, %I:%M %p",
+    "%m/%d/%y, %I:%M:%S %p",
+    "%d/%m/%Y, %I:%M %p",
+    "%d.%m.%y, %H:%M:%S",
+    "%d.%m.%y, %H:%M",
+    "%d.%m.%Y, %H:%M",
+    "%Y-%m-%d, %H:%M:%S",
+    "%Y-%m-%d %H:%M:%S",
+    "%Y-%m-%d %H:%M",
+];
+
+/// Splits `[stamp] rest` or `stamp - rest` into its two parts.
+fn split_header(line: &str) -> Option<(&str, &str)> {
+    if let Some(inner) = line.strip_prefix('[') {
+        let (stamp, rest) = inner.split_once("] ")?;
+        return Some((stamp.trim(), rest));
+    }
+    let (stamp, rest) = line.split_once(" - ")?;
+    stamp.starts_with(|c: char| c.is_ascii_digit()).then_some((stamp.trim(), rest))
+}
+
+/// Groups messages into threads and renders each as one section. A thread
+/// ends after a pause of `THREAD_GAP_MINUTES` or `MAX_THREAD_MESSAGES`
+/// messages; Slack replies stay in their parent's thread.
+fn conversation(platform: &str, channel: &str, title: String, mut messages: Vec<Message>) -> Extracted {
+    messages.sort_by_key(|message| message.at);
+    messages.retain(|message| !message.text.trim().is_empty());
+
+    // Replies whose parent is in another file are threads of their own
+    let top_level: Vec<String> = messages
+        .iter()
+        .filter(|message| message.parent.is_none())
+        .filter_map(|message| message.id.clone())
+        .collect();
+    let mut replies: HashMap<String, Vec<Message>> = HashMap::new();
+    let mut roots = Vec::new();
+    for message in messages {
+        match message.parent.clone().filter(|parent| top_level.contains(parent)) {
+            Some(parent) => replies.entry(parent).or_default().push(message),
+            None => roots.push(message),
+        }
+    }
+
+    let mut threads: Vec<Vec<(Message, bool)>> = Vec::new();
+    let mut last_at: Option<DateTime<Utc>> = None;
+    for message in roots {
+        let gap = last_at.is_some_and(|last| message.at - last > Duration::minutes(THREAD_GAP_MINUTES));
+        let full = threads.last().is_some_and(|thread| thread.len() >= MAX_THREAD_MESSAGES);
+        if threads.is_empty() || gap || full {
+            threads.push(Vec::new());
+        }
+        last_at = Some(message.at);
+        let thread_replies = message.id.as_ref().and_then(|id| replies.remove(id)).unwrap_or_default();
+        let thread = threads.last_mut().expect("a thread was just started");
+        thread.push((message, false));
+        thread.extend(thread_replies.into_iter().map(|reply| (reply, true)));
+    }
+
+    let mut extracted = Extracted {
+        title: Some(title),
+        ..Default::default()
+    };
+    let mut everyone: Vec<String> = Vec::new();
+    let mut first_at = None;
+    let mut last_at = None;
+    let mut count = 0;
+
+    for thread in threads {
+        let mut participants: Vec<String> = Vec::new();
+        let mut lines = Vec::new();
+        for (message, is_reply) in &thread {
+            if !participants.contains(&message.author) {
+                participants.push(message.author.clone());
+            }
+            let indent = if *is_reply { "  ↳ " } else { "" };
+            lines.push(format!("{}[{}] {}: {}", indent, message.at.format("%Y-%m-%d %H:%M"), message.author, message.text));
+        }
+        let started_at = thread.iter().map(|(message, _)| message.at).min().unwrap_or_default();
+        let ended_at = thread.iter().map(|(message, _)| message.at).max().unwrap_or_default();
+        first_at.get_or_insert(started_at);
+        last_at = last_at.max(Some(ended_at));
+        count += thread.len();
+        for participant in &participants {
+            if !everyone.contains(participant) {
+                everyone.push(participant.clone());
+            }
+        }
+
+        let mut metadata = HashMap::new();
+        metadata.insert("channel".to_string(), Value::String(channel.to_string()));
+        metadata.insert("participants".to_string(), Value::from(participants.clone()));
+        metadata.insert("people".to_string(), Value::from(participants));
+        metadata.insert("started_at".to_string(), Value::String(started_at.to_rfc3339()));
+        metadata.insert("ended_at".to_string(), Value::String(ended_at.to_rfc3339()));
+        metadata.insert("created_at".to_string(), Value::String(started_at.to_rfc3339()));
+        metadata.insert(
+            "section".to_string(),
+            Value::String(format!("{} {}", channel, started_at.format("%Y-%m-%d %H:%M"))),
+        );
+        extracted.sections.push(Section {
+            text: lines.join("\n"),
+            metadata,
+        });
+    }
+
+    extracted.metadata.insert("platform".to_string(), Value::String(platform.to_string()));
+    extracted.metadata.insert("channel".to_string(), Value::String(channel.to_string()));
+    extracted.metadata.insert("participants".to_string(), Value::from(everyone));
+    extracted.metadata.insert("messages".to_string(), Value::from(count));
+    if let (Some(first), Some(last)) = (first_at, last_at) {
+        extracted.metadata.insert("started_at".to_string(), Value::String(first.to_rfc3339()));
+        extracted.metadata.insert("ended_at".to_string(), Value::String(last.to_rfc3339()));
+    }
+    extracted
+}
@@ -0,0 +1,467 @@
+use anyhow::Result;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use tracing::warn;
+use tree_sitter::{Language, Node, Parser};
+
+use super::{Extracted, FileHandler, Section};
+
+/// Containers (classes, impl blocks) longer than this are split into their
+/// members instead of being kept as one unit
+const MAX_CONTAINER_LINES: usize = 60;
+
+/// Nodes that only hold a container's members
+const BODY_KINDS: &[&str] = &[
+    "declaration_list",
+    "class_body",
+    "block",
+    "body_statement",
+    "field_declaration_list",
+    "enum_body",
+    "interface_body",
+];
+
+/// Nodes wrapping a definition: decorators, `export`, C++ templates
+const WRAPPER_KINDS: &[&str] = &["decorated_definition", "export_statement", "template_declaration"];
+
+pub struct CodeLanguage {
+    pub name: &'static str,
+    /// Synthetic MIME type the pipeline routes to the code handler
+    pub mime: &'static str,
+    extensions: &'static [&'static str],
+    grammar: fn() -> Language,
+    /// Definitions that become their own section
+    units: &'static [&'static str],
+    /// Units whose members are split out when the unit is too long
+    containers: &'static [&'static str],
+    /// Joins a container's name to a member's, e.g. `Server::start`
+    separator: &'static str,
+}
+
+pub static LANGUAGES: &[CodeLanguage] = &[
+    CodeLanguage {
+        name: "rust",
+        mime: "text/x-rust",
+        extensions: &["rs"],
+        grammar: || tree_sitter_rust::LANGUAGE.into(),
+        units: &[
+            "function_item", "struct_item", "enum_item", "union_item", "trait_item", "impl_item",
+            "mod_item", "macro_definition", "const_item", "static_item", "type_item",
+        ],
+        containers: &["impl_item", "trait_item", "mod_item"],
+        separator: "::",
+    },
+    CodeLanguage {
+        name: "python",
+        mime: "text/x-python",
+        extensions: &["py", "pyi"],
+        grammar: || tree_sitter_python::LANGUAGE.into(),
+        units: &["function_definition", "class_definition"],
+        containers: &["class_definition"],
+        separator: ".",
+    },
+    CodeLanguage {
+        name: "javascript",
+        mime: "text/x-javascript",
+        extensions: &["js", "mjs", "cjs", "jsx"],
+        grammar: || tree_sitter_javascript::LANGUAGE.into(),
+        units: &[
+            "function_declaration", "generator_function_declaration", "class_declaration",
+            "method_definition", "lexical_declaration",
+        ],
+        containers: &["class_declaration"],
+        separator: ".",
+    },
+    CodeLanguage {
+        name: "typescript",
+        mime: "text/x-typescript",
+        extensions: &["ts", "mts", "cts"],
+        grammar: || tree_sitter_typescript::LANGUAGE_TYPESCRIPT.into(),
+        units: &[
+            "function_declaration", "generator_function_declaration", "class_declaration",
+            "abstract_class_declaration", "method_definition", "lexical_declaration",
+            "interface_declaration", "type_alias_declaration", "enum_declaration", "internal_module",
+        ],
+        containers: &["class_declaration", "abstract_class_declaration", "internal_module"],
+        separator: ".",
+    },
+    CodeLanguage {
+        name: "tsx",
+        mime: "text/x-tsx",
+        extensions: &["tsx"],
+        grammar: || tree_sitter_typescript::LANGUAGE_TSX.into(),
+        units: &[
+            "function_declaration", "generator_function_declaration", "class_declaration",
+            "abstract_class_declaration", "method_definition", "lexical_declaration",
+            "interface_declaration", "type_alias_declaration", "enum_declaration",
+        ],
+        containers: &["class_declaration", "abstract_class_declaration"],
+        separator: ".",
+    },
+    CodeLanguage {
+        name: "go",
+        mime: "text/x-go",
+        extensions: &["go"],
+        grammar: || tree_sitter_go::LANGUAGE.into(),
+        units: &["function_declaration", "method_declaration", "type_declaration"],
+        containers: &[],
+        separator: ".",
+    },
+    CodeLanguage {
+        name: "java",
+        mime: "text/x-java",
+        extensions: &["java"],
+        grammar: || tree_sitter_java::LANGUAGE.into(),
+        units: &[
+            "class_declaration", "interface_declaration", "enum_declaration", "record_declaration",
+            "method_declaration", "constructor_declaration",
+        ],
+        containers: &["class_declaration", "interface_declaration", "enum_declaration", "record_declaration"],
+        separator: ".",
+    },
+    CodeLanguage {
+        name: "c",
+        mime: "text/x-c",
+        extensions: &["c", "h"],
+        grammar: || tree_sitter_c::LANGUAGE.into(),
+        units: &["function_definition", "struct_specifier", "enum_specifier", "type_definition"],
+        containers: &[],
+        separator: ".",
+    },
+    CodeLanguage {
+        name: "cpp",
+        mime: "text/x-c++",
+        extensions: &["cc", "cpp", "cxx", "hh", "hpp", "hxx"],
+        grammar: || tree_sitter_cpp::LANGUAGE.into(),
+        units: &[
+            "function_definition", "class_specifier", "struct_specifier", "enum_specifier",
+            "namespace_definition", "type_definition",
+        ],
+        containers: &["class_specifier", "struct_specifier", "namespace_definition"],
+        separator: "::",
+    },
+    CodeLanguage {
+        name: "csharp",
+        mime: "text/x-csharp",
+        extensions: &["cs"],
+        grammar: || tree_sitter_c_sharp::LANGUAGE.into(),
+        units: &[
+            "class_declaration", "struct_declaration", "interface_declaration", "enum_declaration",
+            "record_declaration", "method_declaration", "constructor_declaration",
+            "namespace_declaration",
+        ],
+        containers: &[
+            "class_declaration", "struct_declaration", "interface_declaration", "record_declaration",
+            "namespace_declaration",
+        ],
+        separator: ".",
+    },
+    CodeLanguage {
+        name: "ruby",
+        mime: "text/x-ruby",
+        extensions: &["rb"],
+        grammar: || tree_sitter_ruby::LANGUAGE.into(),
+        units: &["method", "singleton_method", "class", "module"],
+        containers: &["class", "module"],
+        separator: "::",
+    },
+    CodeLanguage {
+        name: "bash",
+        mime: "text/x-shellscript",
+        extensions: &["sh", "bash", "zsh"],
+        grammar: || tree_sitter_bash::LANGUAGE.into(),
+        units: &["function_definition"],
+        containers: &[],
+        separator: ".",
+    },
+];
+
+/// The language of a source file, from its extension.
+pub fn detect_language(path: &Path) -> Option<&'static CodeLanguage> {
+    let extension = path.extension()?.to_str()?.to_lowercase();
+    LANGUAGES
+        .iter()
+        .find(|language| language.extensions.contains(&extension.as_str()))
+}
+
+/// Handles source files: one section per function, class or impl block,
+/// with `language`, `symbol`, `start_line` and `end_line` in the metadata.
+pub struct CodeHandler;
+
+#[async_trait::async_trait]
+impl FileHandler for CodeHandler {
+    async fn extract_text(&self, path: &Path) -> Result<String> {
+        Ok(tokio::fs::read_to_string(path).await?)
+    }
+
+    async fn extract(&self, path: &Path) -> Result<Extracted> {
+        let language = detect_language(path)
+            .ok_or_else(|| anyhow::anyhow!("Unknown source language: {:?}", path))?;
+        let source = tokio::fs::read_to_string(path).await?;
+        tokio::task::spawn_blocking(move || parse_code(&source, language)).await?
+    }
+}
+
+/// Splits source code into sections along its syntactic units. Code between
+/// units (imports, top-level statements, struct fields of a split-up class)
+/// is kept in sections of its own so nothing is lost.
+pub fn parse_code(source: &str, language: &CodeLanguage) -> Result<Extracted> {
+    let mut extracted = Extracted::default();
+    extracted
+        .metadata
+        .insert("language".to_string(), Value::String(language.name.to_string()));
+
+    let lines: Vec<&str> = source.lines().collect();
+    if lines.is_empty() {
+        return Ok(extracted);
+    }
+
+    let mut parser = Parser::new();
+    parser.set_language(&(language.grammar)())?;
+    let Some(tree) = parser.parse(source, None) else {
+        warn!("Could not parse {} source, keeping it whole", language.name);
+        extracted.sections.push(Section {
+            text: source.to_string(),
+            ..Default::default()
+        });
+        return Ok(extracted);
+    };
+
+    let mut collector = UnitCollector {
+        language,
+        source,
+        units: Vec::new(),
+        containers: Vec::new(),
+    };
+    collector.collect(tree.root_node(), None);
+
+    let mut units = collector.units;
+    units.sort_by_key(|unit| unit.start);
+
+    // Lines not covered by any unit, grouped into runs that don't cross the
+    // edge of a split-up container
+    let mut covered = vec![false; lines.len()];
+    for unit in &units {
+        for line in covered.iter_mut().take(unit.end + 1).skip(unit.start) {
+            *line = true;
+        }
+    }
+    let boundaries: HashSet<usize> = collector
+        .containers
+        .iter()
+        .flat_map(|(from, to, _)| [*from, to + 1])
+        .collect();
+    let mut gaps = Vec::new();
+    let mut start: Option<usize> = None;
+    for (i, is_covered) in covered.iter().enumerate() {
+        if let Some(gap_start) = start {
+            if *is_covered || boundaries.contains(&i) {
+                gaps.push((gap_start, i - 1));
+                start = None;
+            }
+        }
+        if !is_covered && start.is_none() {
+            start = Some(i);
+        }
+    }
+    if let Some(gap_start) = start {
+        gaps.push((gap_start, lines.len() - 1));
+    }
+
+    let mut spans: Vec<(usize, usize, Option<String>)> = units
+        .into_iter()
+        .map(|unit| (unit.start, unit.end, Some(unit.symbol)))
+        .collect();
+    for (start, end) in gaps {
+        // Skip blank runs and lone closing braces
+        let Some(start) = (start..=end).find(|&i| lines[i].chars().any(char::is_alphanumeric)) else {
+            continue;
+        };
+        let scope = collector
+            .containers
+            .iter()
+            .filter(|(from, to, _)| *from <= start && end <= *to)
+            .min_by_key(|(from, to, _)| to - from)
+            .map(|(_, _, symbol)| symbol.clone());
+        spans.push((start, end, scope));
+    }
+    spans.sort_by_key(|(start, _, _)| *start);
+
+    for (start, end, symbol) in spans {
+        let text = lines[start..=end].join("\n");
+        let mut metadata = HashMap::new();
+        metadata.insert("start_line".to_string(), Value::from(start + 1));
+        metadata.insert("end_line".to_string(), Value::from(end + 1));
+        if let Some(symbol) = symbol {
+            metadata.insert("section".to_string(), Value::String(symbol.clone()));
+            metadata.insert("symbol".to_string(), Value::String(symbol));
+        }
+        extracted.sections.push(Section {
+            text: text.trim_end().to_string(),
+            metadata,
+        });
+    }
+
+    Ok(extracted)
+}
+
+struct Unit {
+    start: usize,
+    end: usize,
+    symbol: String,
+}
+
+struct UnitCollector<'a> {
+    language: &'a CodeLanguage,
+    source: &'a str,
+    units: Vec<Unit>,
+    /// Line ranges and names of containers that were split into members
+    containers: Vec<(usize, usize, String)>,
+}
+
+impl UnitCollector<'_> {
+    fn collect(&mut self, node: Node, scope: Option<&str>) {
+        let mut cursor = node.walk();
+        // Comments and attributes directly above a definition belong to it
+        let mut leading: Option<usize> = None;
+
+        for child in node.named_children(&mut cursor) {
+            let kind = child.kind();
+            if kind.contains("comment") || kind == "attribute_item" {
+                leading.get_or_insert(child.start_position().row);
+                continue;
+            }
+
+            let definition = self.definition(child);
+            let Some(definition) = definition.filter(|definition| self.is_unit(*definition)) else {
+                leading = None;
+                if BODY_KINDS.contains(&kind) {
+                    self.collect(child, scope);
+                }
+                continue;
+            };
+
+            let name = self.name(definition).unwrap_or_else(|| definition.kind().to_string());
+            let symbol = match scope {
+                Some(scope) => format!("{}{}{}", scope, self.language.separator, name),
+                None => name,
+            };
+            let start = leading.take().unwrap_or(child.start_position().row);
+            let end = child.end_position().row;
+
+            let too_long = end - start + 1 > MAX_CONTAINER_LINES;
+            if too_long && self.language.containers.contains(&definition.kind()) {
+                let scope_name = match scope {
+                    Some(scope) => format!("{}{}{}", scope, self.language.separator, self.scope_name(definition)),
+                    None => self.scope_name(definition),
+                };
+                self.containers.push((start, end, scope_name.clone()));
+                self.collect(definition, Some(&scope_name));
+            } else {
+                self.units.push(Unit { start, end, symbol });
+            }
+        }
+    }
+
+    /// The definition inside decorator, export and template wrappers.
+    fn definition<'t>(&self, node: Node<'t>) -> Option<Node<'t>> {
+        if !WRAPPER_KINDS.contains(&node.kind()) {
+            return Some(node);
+        }
+        node.child_by_field_name("definition")
+            .or_else(|| node.child_by_field_name("declaration"))
+            .or_else(|| {
+                let count = node.named_child_count();
+                node.named_child(count.checked_sub(1)?)
+            })
+    }
+
+    fn is_unit(&self, node: Node) -> bool {
+        if !self.language.units.contains(&node.kind()) {
+            return false;
+        }
+        // `const handler = () => ...` is a function; other bindings are not
+        if node.kind() == "lexical_declaration" {
+            return self.declarator(node).is_some_and(|declarator| {
+                declarator.child_by_field_name("value").is_some_and(|value| {
+                    matches!(value.kind(), "arrow_function" | "function_expression" | "function" | "class")
+                })
+            });
+        }
+        true
+    }
+
+    fn name(&self, node: Node) -> Option<String> {
+        match node.kind() {
+            "impl_item" => {
+                let target = self.text(node.child_by_field_name("type")?);
+                Some(match node.child_by_field_name("trait") {
+                    Some(trait_name) => format!("impl {} for {}", self.text(trait_name), target),
+                    None => format!("impl {}", target),
+                })
+            }
+            "method_declaration" if self.language.name == "go" => {
+                let name = self.text(node.child_by_field_name("name")?);
+                let receiver = node
+                    .child_by_field_name("receiver")
+                    .and_then(|receiver| find_descendant(receiver, "type_identifier"))
+                    .map(|receiver| self.text(receiver));
+                Some(match receiver {
+                    Some(receiver) => format!("{}.{}", receiver, name),
+                    None => name,
+                })
+            }
+            "type_declaration" => {
+                let spec = find_kind(node, "type_spec")?;
+                Some(self.text(spec.child_by_field_name("name")?))
+            }
+            "lexical_declaration" => {
+                Some(self.text(self.declarator(node)?.child_by_field_name("name")?))
+            }
+            "function_definition" if node.child_by_field_name("name").is_none() => {
+                // C/C++: the name is nested inside pointer and function declarators
+                let mut declarator = node.child_by_field_name("declarator")?;
+                while let Some(inner) = declarator.child_by_field_name("declarator") {
+                    declarator = inner;
+                }
+                Some(self.text(declarator))
+            }
+            "type_definition" => Some(self.text(node.child_by_field_name("declarator")?)),
+            _ => node.child_by_field_name("name").map(|name| self.text(name)),
+        }
+    }
+
+    /// Name members are qualified with: the type for an impl block,
+    /// otherwise the container's own name.
+    fn scope_name(&self, node: Node) -> String {
+        let name = match node.kind() {
+            "impl_item" => node.child_by_field_name("type").map(|target| self.text(target)),
+            _ => self.name(node),
+        };
+        name.unwrap_or_else(|| node.kind().to_string())
+    }
+
+    fn declarator<'t>(&self, node: Node<'t>) -> Option<Node<'t>> {
+        find_kind(node, "variable_declarator")
+    }
+
+    fn text(&self, node: Node) -> String {
+        self.source[node.byte_range()].split_whitespace().collect::<Vec<_>>().join(" ")
+    }
+}
+
+fn find_kind<'t>(node: Node<'t>, kind: &str) -> Option<Node<'t>> {
+    let mut cursor = node.walk();
+    let found = node.named_children(&mut cursor).find(|child| child.kind() == kind);
+    found
+}
+
+fn find_descendant<'t>(node: Node<'t>, kind: &str) -> Option<Node<'t>> {
+    if node.kind() == kind {
+        return Some(node);
+    }
+    let mut cursor = node.walk();
+    let children: Vec<Node<'t>> = node.named_children(&mut cursor).collect();
+    children.into_iter().find_map(|child| find_descendant(child, kind))
+}
@@ -0,0 +1,341 @@
+use anyhow::Result;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use ical::property::Property;
+use ical::VcardParser;
+use serde_json::Value;
+use std::collections::HashSet;
+use std::path::Path;
+use tracing::warn;
+
+use super::calendar::unescape;
+use super::{Extracted, FileHandler, Section};
+
+/// Handles vCard files (`.vcf`), one document per contact keyed by its UID.
+/// Names and addresses go into `people` so the people filter finds a
+/// contact the same way it finds their mail.
+pub struct VcardHandler;
+
+#[async_trait::async_trait]
+impl FileHandler for VcardHandler {
+    async fn extract_text(&self, path: &Path) -> Result<String> {
+        let contacts = self.extract_all(path).await?;
+        Ok(contacts
+            .iter()
+            .map(Extracted::text)
+            .collect::<Vec<_>>()
+            .join("\n\n"))
+    }
+
+    async fn extract_all(&self, path: &Path) -> Result<Vec<Extracted>> {
+        let path = path.to_path_buf();
+        tokio::task::spawn_blocking(move || read_contacts(&path)).await?
+    }
+}
+
+fn read_contacts(path: &Path) -> Result<Vec<Extracted>> {
+    let mut seen = HashSet::new();
+    let mut contacts = Vec::new();
+
+    let text = normalize(&std::fs::read_to_string(path)?);
+    for (i, contact) in VcardParser::new(text.as_bytes()).enumerate() {
+        let contact = match contact {
+            Ok(contact) => contact,
+            Err(e) => {
+                warn!("Stopped reading {:?} at contact {}: {}", path, i + 1, e);
+                break;
+            }
+        };
+        let Some(mut extracted) = parse_contact(&contact.properties) else {
+            continue;
+        };
+        let key = extracted.part.take().unwrap_or_default();
+        if seen.insert(key.clone()) {
+            extracted.part = Some(key);
+            contacts.push(extracted);
+        }
+    }
+
+    Ok(contacts)
+}
+
+/// vCard 2.1 allows bare parameters (`TEL;CELL;VOICE:`), which the parser
+/// rejects; they are rewritten as `TYPE=CELL`.
+fn normalize(text: &str) -> String {
+    let mut normalized = String::with_capacity(text.len());
+    for line in text.lines() {
+        let head_end = line.find(':').unwrap_or(0);
+        let (head, rest) = line.split_at(head_end);
+        if head.contains(';') && !head.contains('"') && !line.starts_with([' ', '\t']) {
+            let mut params = head.split(';');
+            normalized.push_str(params.next().unwrap_or(""));
+            for param in params {
+                normalized.push(';');
+                if !param.contains('=') {
+                    normalized.push_str("TYPE=");
+                }
+                normalized.push_str(param);
+            }
+            normalized.push_str(rest);
+        } else {
+            normalized.push_str(line);
+        }
+        normalized.push('\n');
+    }
+    normalized
+}
+
+/// A field per line (name, email, phone, organization, birthday, address,
+/// note). Without a UID the key is a hash of the name and addresses, which
+/// stay put when the rest of the card is edited.
+fn parse_contact(properties: &[Property]) -> Option<Extracted> {
+    let mut card = Card::default();
+    for property in properties {
+        // Apple groups labelled fields as item1.EMAIL, item1.X-ABLABEL
+        let name = property.name.rsplit('.').next().unwrap_or("").to_uppercase();
+        let Some(value) = property.value.as_deref() else {
+            continue;
+        };
+        let value = decode(property, value);
+        let fields = || split_fields(&value);
+        match name.as_str() {
+            "FN" => card.name = non_empty(unescape(&value)),
+            // Family;Given;Middle;Prefix;Suffix
+            "N" => {
+                let parts = fields();
+                let order = [3, 1, 2, 0, 4];
+                let name = order
+                    .iter()
+                    .filter_map(|&i| parts.get(i))
+                    .filter(|part| !part.is_empty())
+                    .cloned()
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                card.structured_name = non_empty(name);
+            }
+            "NICKNAME" => card.nicknames.extend(split_list(&value)),
+            "EMAIL" => {
+                if let Some(email) = non_empty(unescape(&value).to_lowercase()) {
+                    card.emails.push(email);
+                }
+            }
+            "TEL" => {
+                if let Some(phone) = non_empty(unescape(&value)) {
+                    let phone = phone.trim_start_matches("tel:").to_string();
+                    card.phones.push(match kind(property) {
+                        Some(kind) => format!("{} ({})", phone, kind),
+                        None => phone,
+                    });
+                }
+            }
+            "ORG" => card.organization = non_empty(fields().into_iter().filter(|part| !part.is_empty()).collect::<Vec<_>>().join(", ")),
+            "TITLE" => card.title = non_empty(unescape(&value)),
+            "BDAY" => card.birthday = parse_birthday(&value),
+            // PO box;Extended;Street;City;Region;Postal code;Country
+            "ADR" => {
+                let address = fields().into_iter().filter(|part| !part.is_empty()).collect::<Vec<_>>().join(", ");
+                if let Some(address) = non_empty(address) {
+                    card.addresses.push(address);
+                }
+            }
+            "URL" => card.urls.push(unescape(&value)),
+            "NOTE" => card.note = non_empty(unescape(&value)),
+            "UID" => card.uid = non_empty(unescape(&value)),
+            "REV" => card.revised = parse_revision(&value),
+            _ => {}
+        }
+    }
+
+    let name = card
+        .name
+        .clone()
+        .or_else(|| card.structured_name.clone())
+        .or_else(|| card.organization.clone())
+        .or_else(|| card.emails.first().cloned())?;
+    let mut extracted = Extracted {
+        title: Some(name.clone()),
+        ..Default::default()
+    };
+    let metadata = &mut extracted.metadata;
+
+    let mut lines = vec![format!("Contact: {}", name)];
+    if !card.nicknames.is_empty() {
+        lines.push(format!("Nickname: {}", card.nicknames.join(", ")));
+    }
+    if !card.emails.is_empty() {
+        lines.push(format!("Email: {}", card.emails.join(", ")));
+        metadata.insert("emails".to_string(), Value::from(card.emails.clone()));
+    }
+    if !card.phones.is_empty() {
+        lines.push(format!("Phone: {}", card.phones.join(", ")));
+        metadata.insert("phones".to_string(), Value::from(card.phones.clone()));
+    }
+    match (&card.organization, &card.title) {
+        (Some(organization), Some(title)) => lines.push(format!("Organization: {} ({})", organization, title)),
+        (Some(organization), None) => lines.push(format!("Organization: {}", organization)),
+        (None, Some(title)) => lines.push(format!("Title: {}", title)),
+        (None, None) => {}
+    }
+    if let Some(organization) = &card.organization {
+        metadata.insert("organization".to_string(), Value::String(organization.clone()));
+    }
+    if let Some(birthday) = &card.birthday {
+        lines.push(format!("Birthday: {}", birthday));
+        metadata.insert("birthday".to_string(), Value::String(birthday.clone()));
+    }
+    for address in &card.addresses {
+        lines.push(format!("Address: {}", address));
+    }
+    for url in card.urls.iter().filter(|url| !url.is_empty()) {
+        lines.push(format!("Web: {}", url));
+    }
+
+    let mut people: Vec<String> = Vec::new();
+    for person in std::iter::once(&name).chain(&card.nicknames).chain(&card.emails) {
+        if !people.contains(person) {
+            people.push(person.clone());
+        }
+    }
+    metadata.insert("people".to_string(), Value::from(people));
+    if let Some(revised) = card.revised {
+        metadata.insert("created_at".to_string(), Value::String(revised.to_rfc3339()));
+    }
+
+    let mut text = lines.join("\n");
+    if let Some(note) = &card.note {
+        text.push_str("\n\n");
+        text.push_str(note);
+    }
+    extracted.sections.push(Section {
+        text,
+        ..Default::default()
+    });
+
+    extracted.part = Some(card.uid.unwrap_or_else(|| {
+        let seed = format!("{}{}", name, card.emails.join(","));
+        blake3::hash(seed.as_bytes()).to_hex()[..16].to_string()
+    }));
+    Some(extracted)
+}
+
+#[derive(Default)]
+struct Card {
+    name: Option<String>,
+    /// Put together from `N` for cards without `FN`
+    structured_name: Option<String>,
+    nicknames: Vec<String>,
+    emails: Vec<String>,
+    phones: Vec<String>,
+    organization: Option<String>,
+    title: Option<String>,
+    birthday: Option<String>,
+    addresses: Vec<String>,
+    urls: Vec<String>,
+    note: Option<String>,
+    uid: Option<String>,
+    revised: Option<DateTime<Utc>>,
+}
+
+fn non_empty(value: String) -> Option<String> {
+    let value = value.trim();
+    (!value.is_empty()).then(|| value.to_string())
+}
+
+/// The first TYPE that says what a number is for: `cell`, `work`, ...
+fn kind(property: &Property) -> Option<String> {
+    property
+        .params
+        .as_ref()?
+        .iter()
+        .filter(|(key, _)| key.eq_ignore_ascii_case("TYPE"))
+        .flat_map(|(_, values)| values.iter().flat_map(|value| value.split(',')))
+        .map(str::to_lowercase)
+        .find(|kind| !matches!(kind.as_str(), "voice" | "pref" | "internet"))
+}
+
+/// vCard 2.1, still written by Android, quoted-printable encodes
+/// non-ASCII values.
+fn decode(property: &Property, value: &str) -> String {
+    let quoted = property.params.as_ref().is_some_and(|params| {
+        params.iter().any(|(key, values)| {
+            key.eq_ignore_ascii_case("ENCODING") && values.iter().any(|value| value.eq_ignore_ascii_case("QUOTED-PRINTABLE"))
+        })
+    });
+    if !quoted {
+        return value.to_string();
+    }
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = (bytes[i] == b'=')
+            .then(|| value.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match hex {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+/// Components of a structured value like `N` or `ADR`, split on unescaped
+/// `;` and unescaped one by one.
+fn split_fields(value: &str) -> Vec<String> {
+    split_unescaped(value, ';').iter().map(|field| unescape(field).trim().to_string()).collect()
+}
+
+/// Items of a comma-separated list like `NICKNAME`.
+fn split_list(value: &str) -> Vec<String> {
+    split_unescaped(value, ',').iter().filter_map(|item| non_empty(unescape(item))).collect()
+}
+
+fn split_unescaped(value: &str, separator: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut escaped = false;
+    for (i, c) in value.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            c if c == separator => {
+                parts.push(&value[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&value[start..]);
+    parts
+}
+
+/// `1990-04-01`, `19900401`, or `--0401` when the year is unknown.
+fn parse_birthday(value: &str) -> Option<String> {
+    let value = value.trim();
+    let date = value.get(..10).unwrap_or(value);
+    if let Some(day) = date.strip_prefix("--") {
+        let day = day.replace('-', "");
+        return (day.len() == 4).then(|| format!("--{}-{}", &day[..2], &day[2..]));
+    }
+    NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .ok()
+        .or_else(|| NaiveDate::parse_from_str(value.get(..8)?, "%Y%m%d").ok())
+        .map(|date| date.format("%Y-%m-%d").to_string())
+}
+
+/// `REV:20240304T101500Z` or an RFC 3339 timestamp.
+fn parse_revision(value: &str) -> Option<DateTime<Utc>> {
+    let value = value.trim();
+    if let Ok(revised) = DateTime::parse_from_rfc3339(value) {
+        return Some(revised.with_timezone(&Utc));
+    }
+    NaiveDateTime::parse_from_str(value.trim_end_matches('Z'), "%Y%m%dT%H%M%S")
+        .ok()
+        .map(|revised| revised.and_utc())
+}
@@ -0,0 +1,254 @@
+use anyhow::Result;
+use mail_parser::mailbox::mbox::MessageIterator;
+use mail_parser::{Address, MessageParser, MimeHeaders};
+use serde_json::Value;
+use std::collections::HashSet;
+use std::path::Path;
+use tracing::warn;
+
+use super::{Extracted, FileHandler, Section};
+
+/// Handles a single RFC 5322 message (`.eml`).
+pub struct EmailHandler;
+
+#[async_trait::async_trait]
+impl FileHandler for EmailHandler {
+    async fn extract_text(&self, path: &Path) -> Result<String> {
+        Ok(self.extract(path).await?.text())
+    }
+
+    async fn extract(&self, path: &Path) -> Result<Extracted> {
+        let bytes = tokio::fs::read(path).await?;
+        let mut extracted = parse_email(&bytes)
+            .ok_or_else(|| anyhow::anyhow!("Not an email message: {:?}", path))?;
+        // The file itself is the document; only mbox messages need a part key
+        extracted.part = None;
+        Ok(extracted)
+    }
+}
+
+/// Handles mbox archives, one document per message keyed by Message-ID.
+pub struct MboxHandler;
+
+#[async_trait::async_trait]
+impl FileHandler for MboxHandler {
+    async fn extract_text(&self, path: &Path) -> Result<String> {
+        let messages = self.extract_all(path).await?;
+        Ok(messages
+            .iter()
+            .map(Extracted::text)
+            .collect::<Vec<_>>()
+            .join("\n\n"))
+    }
+
+    async fn extract_all(&self, path: &Path) -> Result<Vec<Extracted>> {
+        let path = path.to_path_buf();
+        tokio::task::spawn_blocking(move || read_mbox(&path)).await?
+    }
+}
+
+fn read_mbox(path: &Path) -> Result<Vec<Extracted>> {
+    let file = std::fs::File::open(path)?;
+    let mut seen = HashSet::new();
+    let mut messages = Vec::new();
+
+    for (i, entry) in MessageIterator::new(file).enumerate() {
+        let Ok(entry) = entry else {
+            warn!("Skipping malformed message {} in {:?}", i + 1, path);
+            continue;
+        };
+        let Some(mut extracted) = parse_email(entry.contents()) else {
+            warn!("Skipping unparsable message {} in {:?}", i + 1, path);
+            continue;
+        };
+
+        // Without a Message-ID, a content hash still keeps the key stable
+        // when other messages are added to or removed from the mailbox
+        let key = extracted
+            .part
+            .take()
+            .unwrap_or_else(|| blake3::hash(entry.contents()).to_hex()[..16].to_string());
+        if !seen.insert(key.clone()) {
+            // The same message filed twice
+            continue;
+        }
+
+        extracted.part = Some(key);
+        messages.push(extracted);
+    }
+
+    Ok(messages)
+}
+
+/// Parses one message into a single section: a short header block followed
+/// by the body with quoted replies and the signature removed. Sender and
+/// recipients go into `from`/`to`/`cc` and, names and addresses both, into
+/// `people`. `part` is set to the Message-ID.
+pub fn parse_email(bytes: &[u8]) -> Option<Extracted> {
+    let message = MessageParser::default().parse(bytes)?;
+    let mut extracted = Extracted::default();
+
+    let subject = message
+        .subject()
+        .map(str::trim)
+        .filter(|subject| !subject.is_empty())
+        .unwrap_or("(no subject)")
+        .to_string();
+    extracted.title = Some(subject.clone());
+    extracted
+        .metadata
+        .insert("subject".to_string(), Value::String(subject.clone()));
+
+    let mut header = Vec::new();
+    let mut people = Vec::new();
+    for (key, label, address) in [
+        ("from", "From", message.from()),
+        ("to", "To", message.to()),
+        ("cc", "Cc", message.cc()),
+    ] {
+        let Some(address) = address else {
+            continue;
+        };
+        let formatted = format_addresses(address, &mut people);
+        if formatted.is_empty() {
+            continue;
+        }
+        header.push(format!("{}: {}", label, formatted.join(", ")));
+        extracted.metadata.insert(key.to_string(), Value::from(formatted));
+    }
+    if !people.is_empty() {
+        extracted
+            .metadata
+            .insert("people".to_string(), Value::from(people));
+    }
+
+    if let Some(date) = message.date() {
+        let sent_at = date.to_rfc3339();
+        extracted.metadata.insert(
+            "date".to_string(),
+            Value::String(sent_at.chars().take(10).collect()),
+        );
+        extracted
+            .metadata
+            .insert("sent_at".to_string(), Value::String(sent_at));
+    }
+
+    let attachments: Vec<String> = message
+        .attachments()
+        .filter_map(|part| part.attachment_name())
+        .map(str::to_string)
+        .collect();
+    if !attachments.is_empty() {
+        extracted
+            .metadata
+            .insert("attachments".to_string(), Value::from(attachments));
+    }
+
+    extracted.part = message.message_id().map(str::to_string);
+
+    // mail-parser has already decoded transfer encodings and charsets, and
+    // renders HTML-only bodies as text
+    let body = (0..message.text_body_count())
+        .filter_map(|i| message.body_text(i))
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    let stripped = strip_reply(&body);
+    // A bare forward or a reply that only quotes keeps its full body
+    let body = if stripped.is_empty() { body.trim().to_string() } else { stripped };
+
+    header.push(format!("Subject: {}", subject));
+    let mut text = header.join("\n");
+    if !body.is_empty() {
+        text.push_str("\n\n");
+        text.push_str(&body);
+    }
+    extracted.sections.push(Section {
+        text,
+        ..Default::default()
+    });
+
+    Some(extracted)
+}
+
+/// Formats each address as `Name <addr>` and collects names and lowercased
+/// addresses into `people`.
+fn format_addresses(address: &Address, people: &mut Vec<String>) -> Vec<String> {
+    let mut formatted = Vec::new();
+    for addr in address.iter() {
+        let name = addr.name().map(str::trim).filter(|name| !name.is_empty());
+        let email = addr.address().map(|email| email.trim().to_lowercase());
+
+        for person in name.map(str::to_string).into_iter().chain(email.clone()) {
+            if !people.contains(&person) {
+                people.push(person);
+            }
+        }
+
+        match (name, email) {
+            (Some(name), Some(email)) => formatted.push(format!("{} <{}>", name, email)),
+            (Some(name), None) => formatted.push(name.to_string()),
+            (None, Some(email)) => formatted.push(email),
+            (None, None) => {}
+        }
+    }
+    formatted
+}
+
+/// Keeps only what the sender wrote: drops `>` quoted lines and their
+/// "On ... wrote:" attribution, and stops at the signature delimiter or
+/// the start of quoted history in Outlook-style replies.
+fn strip_reply(body: &str) -> String {
+    let lines: Vec<&str> = body.lines().collect();
+    let mut kept: Vec<&str> = Vec::new();
+    let mut i = 0;
+
+    while i < lines.len() {
+        let line = lines[i];
+        let trimmed = line.trim();
+
+        if line.trim_end() == "--" || is_history_marker(trimmed, lines.get(i + 1)) {
+            break;
+        }
+
+        if is_attribution(trimmed) {
+            i += 1;
+            continue;
+        }
+        // Attribution wrapped over two lines
+        if trimmed.starts_with("On ") && lines.get(i + 1).is_some_and(|next| is_attribution(next.trim())) {
+            i += 2;
+            continue;
+        }
+
+        if !trimmed.starts_with('>') && !trimmed.starts_with("Sent from my ") {
+            kept.push(line.trim_end());
+        }
+        i += 1;
+    }
+
+    // Collapse the blank runs left behind by removed quotes
+    let mut text = String::new();
+    for line in kept {
+        if line.is_empty() && (text.is_empty() || text.ends_with("\n\n")) {
+            continue;
+        }
+        text.push_str(line);
+        text.push('\n');
+    }
+    text.trim().to_string()
+}
+
+fn is_attribution(line: &str) -> bool {
+    line.ends_with("wrote:") || line.ends_with("schrieb:") || line.ends_with("a écrit :")
+}
+
+fn is_history_marker(line: &str, next: Option<&&str>) -> bool {
+    let lower = line.to_lowercase();
+    if lower.starts_with("-----") && lower.contains("original message") {
+        return true;
+    }
+    // Outlook puts a rule and a From: header above the quoted message
+    line.len() >= 20
+        && line.chars().all(|c| c == '_')
+        && next.is_some_and(|next| next.trim_start().starts_with("From:"))
+}
@@ -0,0 +1,375 @@
+use anyhow::Result;
+use roxmltree::{Document, Node, ParsingOptions};
+use scraper::{Html, Selector};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::Path;
+use tracing::warn;
+
+use super::html::render_body;
+use super::office::{read_entry, resolve_target, ZipDocument};
+use super::{Extracted, FileHandler};
+
+/// Handles EPUB e-books (`.epub`). Chapters are read in spine (reading)
+/// order; each section records its chapter title as `section` and the
+/// chapter's number as `chapter`, so a hit reads "Book, chapter 7". Title,
+/// author, publisher, language, subjects and publication date come from the
+/// package metadata.
+pub struct EpubHandler {
+    /// Bytes one file of the book may expand to
+    max_entry: u64,
+}
+
+impl EpubHandler {
+    pub fn new(max_file_mb: u64) -> Self {
+        Self { max_entry: max_file_mb * 1024 * 1024 }
+    }
+}
+
+#[async_trait::async_trait]
+impl FileHandler for EpubHandler {
+    async fn extract_text(&self, path: &Path) -> Result<String> {
+        Ok(self.extract(path).await?.text())
+    }
+
+    async fn extract(&self, path: &Path) -> Result<Extracted> {
+        let path = path.to_path_buf();
+        let max_entry = self.max_entry;
+        tokio::task::spawn_blocking(move || read_epub(&path, max_entry)).await?
+    }
+}
+
+fn read_epub(path: &Path, max_entry: u64) -> Result<Extracted> {
+    let mut archive = ZipDocument::open(path, max_entry)?;
+    let container = read_entry(&mut archive, "META-INF/container.xml")?
+        .ok_or_else(|| anyhow::anyhow!("Not an EPUB book: {:?}", path))?;
+    let container = parse_xml(&container)?;
+    let opf_path = container
+        .descendants()
+        .find(|node| node.tag_name().name() == "rootfile")
+        .and_then(|node| node.attribute("full-path"))
+        .ok_or_else(|| anyhow::anyhow!("EPUB book has no package document: {:?}", path))?
+        .to_string();
+    let opf = read_entry(&mut archive, &opf_path)?
+        .ok_or_else(|| anyhow::anyhow!("EPUB package {} is missing: {:?}", opf_path, path))?;
+    let package = Package::parse(&opf, parent(&opf_path))?;
+
+    let titles = match &package.toc {
+        Some(Toc::Nav(nav)) => read_entry(&mut archive, nav)?
+            .map(|xhtml| read_nav(&xhtml, parent(nav)))
+            .unwrap_or_default(),
+        Some(Toc::Ncx(ncx)) => read_entry(&mut archive, ncx)?
+            .map(|xml| read_ncx(&xml, parent(ncx)))
+            .transpose()?
+            .unwrap_or_default(),
+        None => HashMap::new(),
+    };
+
+    let mut extracted = Extracted {
+        title: package.title,
+        metadata: package.metadata,
+        ..Default::default()
+    };
+
+    let mut chapter = 0;
+    let mut chapter_title: Option<String> = None;
+    for item in &package.spine {
+        let Some(xhtml) = read_entry(&mut archive, item)? else {
+            warn!("Skipping {} missing from {:?}", item, path);
+            continue;
+        };
+        let page = render_body(&xhtml);
+        if page.sections.is_empty() {
+            continue;
+        }
+
+        // A file the table of contents doesn't list (the second half of a
+        // split chapter) stays in the chapter before it; front matter before
+        // the first listed chapter has no number
+        if let Some(title) = titles.get(item) {
+            chapter += 1;
+            chapter_title = Some(title.clone());
+        } else if titles.is_empty() {
+            chapter += 1;
+            chapter_title = page.title.clone();
+        }
+
+        for mut section in page.sections {
+            let headings = section
+                .metadata
+                .remove("section")
+                .and_then(|value| value.as_str().map(str::to_string));
+            if let Some(label) = section_label(chapter_title.as_deref(), headings.as_deref()) {
+                section.metadata.insert("section".to_string(), Value::String(label));
+            }
+            if chapter > 0 {
+                section.metadata.insert("chapter".to_string(), Value::from(chapter));
+            }
+            extracted.sections.push(section);
+        }
+    }
+
+    if extracted.sections.is_empty() {
+        return Err(anyhow::anyhow!("EPUB book has no readable chapters: {:?}", path));
+    }
+    Ok(extracted)
+}
+
+/// The chapter title followed by the headings inside the chapter, without
+/// repeating a first heading that is the chapter title itself.
+fn section_label(chapter: Option<&str>, headings: Option<&str>) -> Option<String> {
+    let Some(chapter) = chapter else {
+        return headings.map(str::to_string);
+    };
+    let Some(headings) = headings else {
+        return Some(chapter.to_string());
+    };
+
+    let mut parts: Vec<&str> = headings.split(" > ").collect();
+    let first = parts[0].to_lowercase();
+    let title = chapter.to_lowercase();
+    if title.starts_with(&first) || first.starts_with(&title) {
+        parts.remove(0);
+    }
+    Some(std::iter::once(chapter).chain(parts).collect::<Vec<_>>().join(" > "))
+}
+
+enum Toc {
+    /// EPUB 3 navigation document
+    Nav(String),
+    /// EPUB 2 `toc.ncx`
+    Ncx(String),
+}
+
+/// What the OPF package document says about the book.
+struct Package {
+    title: Option<String>,
+    metadata: HashMap<String, Value>,
+    /// Archive paths of the chapter files in reading order
+    spine: Vec<String>,
+    toc: Option<Toc>,
+}
+
+impl Package {
+    fn parse(opf: &str, dir: &str) -> Result<Self> {
+        let document = parse_xml(opf)?;
+        let root = document.root_element();
+
+        let mut manifest: HashMap<&str, (String, &str, &str)> = HashMap::new();
+        for item in root.descendants().filter(|node| node.tag_name().name() == "item") {
+            let (Some(id), Some(href)) = (item.attribute("id"), item.attribute("href")) else {
+                continue;
+            };
+            let path = resolve_target(dir, &percent_decode(href));
+            let media_type = item.attribute("media-type").unwrap_or("");
+            let properties = item.attribute("properties").unwrap_or("");
+            manifest.insert(id, (path, media_type, properties));
+        }
+
+        let spine_node = root.descendants().find(|node| node.tag_name().name() == "spine");
+        let spine = spine_node
+            .into_iter()
+            .flat_map(|spine| spine.children().filter(|node| node.tag_name().name() == "itemref"))
+            .filter_map(|itemref| manifest.get(itemref.attribute("idref")?))
+            .filter(|(_, media_type, _)| matches!(*media_type, "application/xhtml+xml" | "text/html"))
+            .map(|(path, _, _)| path.clone())
+            .collect();
+
+        let nav = manifest
+            .values()
+            .find(|(_, _, properties)| properties.split_whitespace().any(|property| property == "nav"))
+            .map(|(path, _, _)| Toc::Nav(path.clone()));
+        let ncx = spine_node
+            .and_then(|spine| manifest.get(spine.attribute("toc")?))
+            .or_else(|| manifest.values().find(|(_, media_type, _)| *media_type == "application/x-dtbncx+xml"))
+            .map(|(path, _, _)| Toc::Ncx(path.clone()));
+
+        let mut package = Package {
+            title: None,
+            metadata: HashMap::new(),
+            spine,
+            toc: nav.or(ncx),
+        };
+        if let Some(metadata) = root.descendants().find(|node| node.tag_name().name() == "metadata") {
+            package.read_metadata(metadata);
+        }
+        Ok(package)
+    }
+
+    /// Dublin Core fields plus Calibre's series, which most libraries set.
+    fn read_metadata(&mut self, metadata: Node) {
+        let fields = |name: &'static str| {
+            metadata
+                .children()
+                .filter(move |node| node.tag_name().name() == name)
+                .filter_map(|node| Some((node, normalize(node.text()?))))
+                .filter(|(_, text)| !text.is_empty())
+        };
+
+        self.title = fields("title").map(|(_, title)| title).next();
+
+        // EPUB 2 marks editors, illustrators etc. with opf:role
+        let authors: Vec<String> = fields("creator")
+            .filter(|(node, _)| local_attr(*node, "role").is_none_or(|role| role == "aut"))
+            .map(|(_, author)| author)
+            .collect();
+        if !authors.is_empty() {
+            self.metadata.insert("author".to_string(), Value::String(authors.join(", ")));
+        }
+
+        // EPUB 2 can list several dates; the publication date is the one wanted
+        let dates: Vec<(Node, String)> = fields("date").collect();
+        let date = dates
+            .iter()
+            .find(|(node, _)| local_attr(*node, "event") == Some("publication"))
+            .or_else(|| dates.first());
+        if let Some((_, date)) = date {
+            self.metadata.insert("date".to_string(), Value::String(date.chars().take(10).collect()));
+        }
+
+        if let Some((_, publisher)) = fields("publisher").next() {
+            self.metadata.insert("publisher".to_string(), Value::String(publisher));
+        }
+        if let Some((_, language)) = fields("language").next() {
+            self.metadata.insert("lang".to_string(), Value::String(language));
+        }
+
+        let mut tags: Vec<String> = Vec::new();
+        for (_, subject) in fields("subject") {
+            if !tags.contains(&subject) {
+                tags.push(subject);
+            }
+        }
+        if !tags.is_empty() {
+            self.metadata.insert("tags".to_string(), Value::from(tags));
+        }
+
+        let isbn = fields("identifier").find_map(|(node, id)| {
+            let lower = id.to_lowercase();
+            let scheme = local_attr(node, "scheme").is_some_and(|scheme| scheme.eq_ignore_ascii_case("isbn"));
+            match lower.strip_prefix("urn:isbn:") {
+                Some(isbn) => Some(isbn.to_string()),
+                None => scheme.then_some(id),
+            }
+        });
+        if let Some(isbn) = isbn {
+            self.metadata.insert("isbn".to_string(), Value::String(isbn));
+        }
+
+        let calibre = |name: &str| {
+            metadata
+                .children()
+                .find(|node| node.tag_name().name() == "meta" && node.attribute("name") == Some(name))
+                .and_then(|node| node.attribute("content"))
+                .map(normalize)
+                .filter(|value| !value.is_empty())
+        };
+        if let Some(series) = calibre("calibre:series") {
+            let series = match calibre("calibre:series_index") {
+                Some(index) => format!("{} #{}", series, index.trim_end_matches(".0")),
+                None => series,
+            };
+            self.metadata.insert("series".to_string(), Value::String(series));
+        }
+    }
+}
+
+/// Chapter titles by archive path from an EPUB 3 `<nav epub:type="toc">`.
+/// When several entries point into one file the first names it.
+fn read_nav(xhtml: &str, dir: &str) -> HashMap<String, String> {
+    let document = Html::parse_document(xhtml);
+    let navs = Selector::parse("nav").expect("valid selector");
+    let links = Selector::parse("a[href]").expect("valid selector");
+
+    let toc = document
+        .select(&navs)
+        .find(|nav| nav.value().attrs().any(|(name, value)| name.ends_with("type") && value.split_whitespace().any(|kind| kind == "toc")))
+        .or_else(|| document.select(&navs).next());
+    let mut titles = HashMap::new();
+    for link in toc.iter().flat_map(|toc| toc.select(&links)) {
+        let title = normalize(&link.text().collect::<String>());
+        let href = link.value().attr("href").unwrap_or("");
+        if let Some(path) = toc_target(dir, href).filter(|_| !title.is_empty()) {
+            titles.entry(path).or_insert(title);
+        }
+    }
+    titles
+}
+
+/// Chapter titles by archive path from an EPUB 2 `toc.ncx`.
+fn read_ncx(xml: &str, dir: &str) -> Result<HashMap<String, String>> {
+    let document = parse_xml(xml)?;
+    let mut titles = HashMap::new();
+    for point in document.descendants().filter(|node| node.tag_name().name() == "navPoint") {
+        let title = point
+            .children()
+            .find(|node| node.tag_name().name() == "navLabel")
+            .and_then(|label| label.descendants().find(|node| node.tag_name().name() == "text"))
+            .and_then(|text| text.text())
+            .map(normalize)
+            .unwrap_or_default();
+        let href = point
+            .children()
+            .find(|node| node.tag_name().name() == "content")
+            .and_then(|content| content.attribute("src"))
+            .unwrap_or("");
+        if let Some(path) = toc_target(dir, href).filter(|_| !title.is_empty()) {
+            titles.entry(path).or_insert(title);
+        }
+    }
+    Ok(titles)
+}
+
+/// Archive path a table of contents entry points at, without its `#anchor`.
+fn toc_target(dir: &str, href: &str) -> Option<String> {
+    let file = href.split('#').next().unwrap_or("");
+    (!file.is_empty() && !file.contains("://")).then(|| resolve_target(dir, &percent_decode(file)))
+}
+
+/// NCX and older package documents come with a DOCTYPE.
+fn parse_xml(xml: &str) -> Result<Document<'_>> {
+    let options = ParsingOptions {
+        allow_dtd: true,
+        ..Default::default()
+    };
+    Ok(Document::parse_with_options(xml, options)?)
+}
+
+/// Attribute by local name, ignoring its namespace prefix (`opf:role`).
+fn local_attr<'a>(node: Node<'a, '_>, name: &str) -> Option<&'a str> {
+    node.attributes()
+        .find(|attribute| attribute.name() == name)
+        .map(|attribute| attribute.value())
+}
+
+fn parent(path: &str) -> &str {
+    path.rsplit_once('/').map(|(dir, _)| dir).unwrap_or("")
+}
+
+/// Manifest hrefs are URLs: `Chapter%201.xhtml` is `Chapter 1.xhtml` in the archive.
+fn percent_decode(href: &str) -> String {
+    let bytes = href.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = (bytes[i] == b'%')
+            .then(|| href.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match hex {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+fn normalize(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
@@ -0,0 +1,496 @@
+use anyhow::Result;
+use mail_parser::{MessageParser, MimeHeaders};
+use scraper::{ElementRef, Html, Node, Selector};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use url::Url;
+
+use super::sections::SectionBuilder;
+use super::{Extracted, FileHandler};
+
+/// Elements that never hold article text
+const SKIP_TAGS: &[&str] = &[
+    "script", "style", "noscript", "template", "nav", "aside", "footer", "form", "iframe", "svg",
+    "canvas", "button", "select", "input", "textarea", "object", "embed", "dialog",
+];
+
+const BLOCK_TAGS: &[&str] = &[
+    "p", "div", "section", "article", "main", "header", "blockquote", "table", "ul", "ol", "dl",
+    "figure", "figcaption", "address", "details", "summary", "hr",
+];
+
+/// class/id fragments that mark page chrome rather than content
+const NEGATIVE_HINTS: &[&str] = &[
+    "comment", "sidebar", "footer", "footnote", "masthead", "menu", "nav", "share", "social",
+    "sponsor", "advert", "banner", "cookie", "promo", "related", "breadcrumb", "popup",
+    "newsletter", "subscribe", "widget", "pagination",
+];
+
+const POSITIVE_HINTS: &[&str] = &["article", "body", "content", "entry", "main", "post", "text", "story"];
+
+/// Below this many characters the picked content block is probably wrong
+/// (a teaser or a caption) and the whole body is used instead
+const MIN_CONTENT_CHARS: usize = 250;
+
+/// Cap on recorded outbound links per page
+const MAX_LINKS: usize = 500;
+
+/// Handles `.html`/`.htm` and saved `.mhtml` pages.
+pub struct HtmlHandler;
+
+#[async_trait::async_trait]
+impl FileHandler for HtmlHandler {
+    async fn extract_text(&self, path: &Path) -> Result<String> {
+        Ok(self.extract(path).await?.text())
+    }
+
+    async fn extract(&self, path: &Path) -> Result<Extracted> {
+        let bytes = tokio::fs::read(path).await?;
+        let is_mhtml = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.eq_ignore_ascii_case("mhtml") || ext.eq_ignore_ascii_case("mht"))
+            .unwrap_or(false);
+
+        if is_mhtml {
+            let (html, location) = read_mhtml(&bytes)
+                .ok_or_else(|| anyhow::anyhow!("No HTML part in {:?}", path))?;
+            Ok(parse_html(&html, location.as_deref()))
+        } else {
+            Ok(parse_html(&String::from_utf8_lossy(&bytes), None))
+        }
+    }
+}
+
+/// Pulls the HTML body and the original page URL out of an MHTML archive.
+fn read_mhtml(bytes: &[u8]) -> Option<(String, Option<String>)> {
+    let message = MessageParser::new().parse(bytes)?;
+    let html = message.body_html(0)?.into_owned();
+    let location = message
+        .header_raw("Snapshot-Content-Location")
+        .or_else(|| message.html_part(0)?.content_location())
+        .map(|location| location.trim().to_string());
+    Some((html, location))
+}
+
+/// Extracts the main content of a page as sections split at headings.
+/// `<title>` and `<meta>` tags become document metadata, and links inside
+/// the content are recorded as `links`, resolved against `location` or the
+/// page's own `<base>`/canonical URL when there is one.
+pub fn parse_html(html: &str, location: Option<&str>) -> Extracted {
+    let document = Html::parse_document(html);
+
+    let mut extracted = Extracted::default();
+    read_head(&document, &mut extracted);
+
+    let base = location
+        .or_else(|| attr(&document, "base[href]", "href"))
+        .or_else(|| attr(&document, "link[rel=canonical]", "href"))
+        .and_then(|url| Url::parse(url).ok());
+
+    let body = document
+        .select(&selector("body"))
+        .next()
+        .unwrap_or_else(|| document.root_element());
+    let root = find_content(&document)
+        .filter(|content| text_len(*content) >= MIN_CONTENT_CHARS)
+        .unwrap_or(body);
+
+    let mut renderer = Renderer {
+        builder: SectionBuilder::default(),
+        base,
+        links: Vec::new(),
+        seen: HashSet::new(),
+        in_pre: false,
+        // Outside a detected article the page header is usually site chrome
+        skip_header: root.id() == body.id(),
+    };
+    renderer.render(root);
+
+    if extracted.title.is_none() {
+        extracted.title = document
+            .select(&selector("h1"))
+            .map(|h1| normalize(&h1.text().collect::<String>()))
+            .find(|title| !title.is_empty());
+    }
+    if !renderer.links.is_empty() {
+        extracted
+            .metadata
+            .insert("links".to_string(), Value::from(renderer.links));
+    }
+    extracted.sections = renderer.builder.finish();
+    extracted
+}
+
+/// Renders the whole body of a page without looking for its main content,
+/// for pages that are all content such as e-book chapters. The title is the
+/// first heading, else `<title>`.
+pub(crate) fn render_body(html: &str) -> Extracted {
+    let document = Html::parse_document(html);
+    let body = document
+        .select(&selector("body"))
+        .next()
+        .unwrap_or_else(|| document.root_element());
+
+    let mut renderer = Renderer {
+        builder: SectionBuilder::default(),
+        base: None,
+        links: Vec::new(),
+        seen: HashSet::new(),
+        in_pre: false,
+        skip_header: false,
+    };
+    renderer.render(body);
+
+    let title = body
+        .select(&selector("h1, h2, h3"))
+        .chain(document.select(&selector("title")))
+        .map(|heading| normalize(&heading.text().collect::<String>()))
+        .find(|title| !title.is_empty());
+    Extracted {
+        title,
+        sections: renderer.builder.finish(),
+        ..Default::default()
+    }
+}
+
+/// Copies title, description, author, date, tags, site name, canonical
+/// URL and language from the document head.
+fn read_head(document: &Html, extracted: &mut Extracted) {
+    let meta = |keys: &[&str]| {
+        keys.iter().find_map(|key| {
+            let query = format!("meta[name=\"{0}\" i], meta[property=\"{0}\" i]", key);
+            attr(document, &query, "content")
+                .map(normalize)
+                .filter(|value| !value.is_empty())
+        })
+    };
+
+    extracted.title = meta(&["og:title", "twitter:title"]).or_else(|| {
+        document
+            .select(&selector("title"))
+            .next()
+            .map(|title| normalize(&title.text().collect::<String>()))
+            .filter(|title| !title.is_empty())
+    });
+
+    let fields = [
+        ("description", meta(&["description", "og:description"])),
+        ("author", meta(&["author", "article:author", "dc.creator"])),
+        ("site", meta(&["og:site_name", "application-name"])),
+        (
+            "date",
+            meta(&["article:published_time", "date", "dc.date", "pubdate"]).map(|date| short_date(&date)),
+        ),
+        (
+            "url",
+            attr(document, "link[rel=canonical]", "href")
+                .map(str::to_string)
+                .or_else(|| meta(&["og:url"])),
+        ),
+        ("lang", attr(document, "html[lang]", "lang").map(str::to_string)),
+    ];
+    for (key, value) in fields {
+        if let Some(value) = value {
+            extracted.metadata.insert(key.to_string(), Value::String(value));
+        }
+    }
+
+    let mut tags: Vec<String> = Vec::new();
+    let keywords = selector("meta[name=keywords i], meta[property=\"article:tag\" i]");
+    for keyword in document.select(&keywords).filter_map(|meta| meta.value().attr("content")) {
+        for tag in keyword.split(',').map(str::trim).filter(|tag| !tag.is_empty()) {
+            if !tags.iter().any(|known| known == tag) {
+                tags.push(tag.to_string());
+            }
+        }
+    }
+    if !tags.is_empty() {
+        extracted.metadata.insert("tags".to_string(), Value::from(tags));
+    }
+}
+
+/// Picks the element most likely to hold the article: an explicit
+/// `<article>`/`<main>` if present, otherwise the readability-style best
+/// scoring parent of the page's paragraphs.
+fn find_content(document: &Html) -> Option<ElementRef<'_>> {
+    let structural = document
+        .select(&selector("article, main, [role=main]"))
+        .filter(|element| !is_boilerplate(*element))
+        .max_by_key(|element| text_len(*element));
+    if let Some(element) = structural.filter(|element| text_len(*element) >= MIN_CONTENT_CHARS) {
+        return Some(element);
+    }
+
+    let mut scores = HashMap::new();
+    for paragraph in document.select(&selector("p, pre, td, blockquote")) {
+        let text = normalize(&paragraph.text().collect::<String>());
+        let len = text.chars().count();
+        if len < 25 {
+            continue;
+        }
+
+        // Longer paragraphs with more clauses are more article-like
+        let score = 1.0 + text.matches(',').count() as f64 + (len as f64 / 100.0).min(3.0);
+        let parent = paragraph.parent().and_then(ElementRef::wrap);
+        let grandparent = parent.and_then(|parent| parent.parent()).and_then(ElementRef::wrap);
+        for (ancestor, share) in [(parent, 1.0), (grandparent, 0.5)] {
+            if let Some(ancestor) = ancestor {
+                *scores.entry(ancestor.id()).or_insert_with(|| class_weight(ancestor)) += score * share;
+            }
+        }
+    }
+
+    scores
+        .into_iter()
+        .filter_map(|(id, score)| {
+            let element = ElementRef::wrap(document.tree.get(id)?)?;
+            Some((element, score * (1.0 - link_density(element))))
+        })
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(element, _)| element)
+}
+
+fn class_weight(element: ElementRef) -> f64 {
+    let hints = hints(element);
+    let mut weight = 0.0;
+    if NEGATIVE_HINTS.iter().any(|hint| hints.contains(hint)) {
+        weight -= 25.0;
+    }
+    if POSITIVE_HINTS.iter().any(|hint| hints.contains(hint)) {
+        weight += 25.0;
+    }
+    weight
+}
+
+/// Share of an element's text that sits inside links; menus score high.
+fn link_density(element: ElementRef) -> f64 {
+    let total = text_len(element);
+    if total == 0 {
+        return 1.0;
+    }
+    let linked: usize = element.select(&selector("a")).map(text_len).sum();
+    linked as f64 / total as f64
+}
+
+/// Hidden elements, page chrome tags and containers whose class or id
+/// looks like navigation, ads or comments.
+fn is_boilerplate(element: ElementRef) -> bool {
+    let value = element.value();
+    if SKIP_TAGS.contains(&value.name()) {
+        return true;
+    }
+    if value.attr("hidden").is_some() || value.attr("aria-hidden") == Some("true") {
+        return true;
+    }
+    let style = value.attr("style").unwrap_or("").replace(' ', "").to_lowercase();
+    if style.contains("display:none") || style.contains("visibility:hidden") {
+        return true;
+    }
+    if matches!(
+        value.attr("role"),
+        Some("navigation" | "banner" | "contentinfo" | "complementary" | "dialog")
+    ) {
+        return true;
+    }
+
+    let hints = hints(element);
+    NEGATIVE_HINTS.iter().any(|hint| hints.contains(hint))
+        && !POSITIVE_HINTS.iter().any(|hint| hints.contains(hint))
+}
+
+fn hints(element: ElementRef) -> String {
+    let value = element.value();
+    format!(
+        "{} {}",
+        value.attr("class").unwrap_or(""),
+        value.attr("id").unwrap_or("")
+    )
+    .to_lowercase()
+}
+
+struct Renderer {
+    builder: SectionBuilder,
+    base: Option<Url>,
+    links: Vec<String>,
+    seen: HashSet<String>,
+    in_pre: bool,
+    skip_header: bool,
+}
+
+impl Renderer {
+    fn render(&mut self, element: ElementRef) {
+        for child in element.children() {
+            match child.value() {
+                Node::Text(text) => self.push_text(text),
+                Node::Element(_) => {
+                    if let Some(child) = ElementRef::wrap(child) {
+                        self.render_element(child);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn render_element(&mut self, element: ElementRef) {
+        let name = element.value().name();
+        if is_boilerplate(element) || (self.skip_header && name == "header") {
+            return;
+        }
+
+        match name {
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                let level = name[1..].parse().unwrap_or(6);
+                let title = normalize(&element.text().collect::<String>());
+                if !title.is_empty() {
+                    self.builder.start_section(level, title);
+                }
+            }
+            "br" => self.builder.push_break("\n"),
+            "pre" => {
+                self.builder.push_break("\n\n");
+                if let Some(language) = code_language(element) {
+                    self.builder.add_code_language(&language);
+                }
+                self.in_pre = true;
+                self.render(element);
+                self.in_pre = false;
+                self.builder.push_break("\n\n");
+            }
+            "li" | "tr" | "dt" | "dd" => {
+                self.builder.push_break("\n");
+                self.render(element);
+                self.builder.push_break("\n");
+            }
+            "td" | "th" => {
+                self.render(element);
+                self.builder.push(" ");
+            }
+            "a" => {
+                if let Some(href) = element.value().attr("href") {
+                    self.add_link(href);
+                }
+                self.render(element);
+            }
+            _ if BLOCK_TAGS.contains(&name) => {
+                self.builder.push_break("\n\n");
+                self.render(element);
+                self.builder.push_break("\n\n");
+            }
+            _ => self.render(element),
+        }
+    }
+
+    fn push_text(&mut self, text: &str) {
+        if self.in_pre {
+            self.builder.push(text);
+            return;
+        }
+
+        let mut collapsed = String::with_capacity(text.len());
+        for (i, word) in text.split_whitespace().enumerate() {
+            if i > 0 {
+                collapsed.push(' ');
+            }
+            collapsed.push_str(word);
+        }
+        let leading = text.starts_with(char::is_whitespace) && !self.builder.at_word_boundary();
+        let trailing = text.ends_with(char::is_whitespace) && !collapsed.is_empty();
+
+        if leading {
+            self.builder.push(" ");
+        }
+        self.builder.push(&collapsed);
+        if trailing {
+            self.builder.push(" ");
+        }
+    }
+
+    fn add_link(&mut self, href: &str) {
+        let href = href.trim();
+        if href.is_empty() || href.starts_with('#') || self.links.len() >= MAX_LINKS {
+            return;
+        }
+
+        let link = match &self.base {
+            Some(base) => match base.join(href) {
+                Ok(url) => url,
+                Err(_) => return,
+            },
+            None => match Url::parse(href) {
+                Ok(url) => url,
+                // Relative link in a local file with no known origin
+                Err(_) => {
+                    let path = href.split('#').next().unwrap_or(href).to_string();
+                    if self.seen.insert(path.clone()) {
+                        self.links.push(path);
+                    }
+                    return;
+                }
+            },
+        };
+
+        if !matches!(link.scheme(), "http" | "https" | "file") {
+            return;
+        }
+        let mut link = link;
+        link.set_fragment(None);
+        let link = link.to_string();
+        if self.seen.insert(link.clone()) {
+            self.links.push(link);
+        }
+    }
+}
+
+/// Language from `class="language-rust"` / `lang-rust` on a `<pre>` or its `<code>`.
+fn code_language(pre: ElementRef) -> Option<String> {
+    std::iter::once(pre)
+        .chain(pre.select(&selector("code")))
+        .flat_map(|element| element.value().classes())
+        .find_map(|class| {
+            class
+                .strip_prefix("language-")
+                .or_else(|| class.strip_prefix("lang-"))
+                .map(str::to_string)
+        })
+}
+
+fn attr<'a>(document: &'a Html, query: &str, name: &str) -> Option<&'a str> {
+    document
+        .select(&selector(query))
+        .find_map(|element| element.value().attr(name))
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+}
+
+fn text_len(element: ElementRef) -> usize {
+    element
+        .text()
+        .flat_map(|text| text.split_whitespace())
+        .map(|word| word.chars().count() + 1)
+        .sum()
+}
+
+fn normalize(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Keeps the `YYYY-MM-DD` part of an ISO timestamp.
+fn short_date(date: &str) -> String {
+    let bytes = date.as_bytes();
+    let is_iso = bytes.len() >= 10
+        && bytes[4] == b'-'
+        && bytes[7] == b'-'
+        && bytes[..4].iter().chain(&bytes[5..7]).chain(&bytes[8..10]).all(u8::is_ascii_digit);
+    if is_iso {
+        date[..10].to_string()
+    } else {
+        date.to_string()
+    }
+}
+
+fn selector(query: &str) -> Selector {
+    Selector::parse(query).expect("valid selector")
+}
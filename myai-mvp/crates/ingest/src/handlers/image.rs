@@ -0,0 +1,380 @@
+use anyhow::Result;
+use chrono::{DateTime, FixedOffset, Local, NaiveDateTime, TimeZone};
+use models::OcrModel;
+use serde_json::{json, Value};
+use std::ops::Range;
+use std::path::Path;
+use std::sync::Arc;
+use tracing::warn;
+
+use super::{Extracted, FileHandler, Section};
+
+const EXIF_DATE_FORMAT: &str = "%Y:%m:%d %H:%M:%S";
+
+/// Handles photos and screenshots (PNG, JPEG, WebP, TIFF, GIF, BMP). EXIF
+/// fills in the capture time (which dates the document), camera and GPS
+/// position; text read by the OCR model, when installed, is indexed line by
+/// line with each line's box in `ocr_lines`.
+#[derive(Clone)]
+pub struct ImageHandler {
+    ocr: Option<Arc<OcrModel>>,
+}
+
+impl ImageHandler {
+    pub fn new(ocr: Option<Arc<OcrModel>>) -> Self {
+        Self { ocr }
+    }
+}
+
+#[async_trait::async_trait]
+impl FileHandler for ImageHandler {
+    async fn extract_text(&self, path: &Path) -> Result<String> {
+        Ok(self.extract(path).await?.text())
+    }
+
+    async fn extract(&self, path: &Path) -> Result<Extracted> {
+        let bytes = tokio::fs::read(path).await?;
+        let ocr = self.ocr.clone();
+        let path = path.to_path_buf();
+        tokio::task::spawn_blocking(move || read_image(&bytes, ocr.as_deref(), &path)).await?
+    }
+}
+
+fn read_image(bytes: &[u8], ocr: Option<&OcrModel>, path: &Path) -> Result<Extracted> {
+    let mut extracted = Extracted::default();
+    let exif = find_exif(bytes).map(read_exif).unwrap_or_default();
+    let metadata = &mut extracted.metadata;
+
+    let mut summary = Vec::new();
+    if let Some(taken_at) = exif.taken_at {
+        summary.push(format!("taken {}", taken_at.format("%Y-%m-%d %H:%M")));
+        metadata.insert("taken_at".to_string(), Value::String(taken_at.to_rfc3339()));
+        metadata.insert("created_at".to_string(), Value::String(taken_at.to_rfc3339()));
+        metadata.insert("date".to_string(), Value::String(taken_at.format("%Y-%m-%d").to_string()));
+    }
+    if let Some(camera) = exif.camera() {
+        summary.push(format!("with {}", camera));
+        metadata.insert("camera".to_string(), Value::String(camera));
+    }
+    if let Some(lens) = exif.lens {
+        metadata.insert("lens".to_string(), Value::String(lens));
+    }
+    if let Some((latitude, longitude)) = exif.position {
+        summary.push(format!("at {:.5}, {:.5}", latitude, longitude));
+        metadata.insert("latitude".to_string(), json!(latitude));
+        metadata.insert("longitude".to_string(), json!(longitude));
+    }
+    if !summary.is_empty() {
+        extracted.sections.push(Section {
+            text: format!("Photo {}", summary.join(" ")),
+            ..Default::default()
+        });
+    }
+
+    let Some(ocr) = ocr else {
+        return Ok(extracted);
+    };
+    let recognized = match ocr.recognize(bytes) {
+        Ok(recognized) => recognized,
+        Err(e) => {
+            warn!("Could not read text in {:?}: {}", path, e);
+            return Ok(extracted);
+        }
+    };
+    extracted.metadata.insert("width".to_string(), Value::from(recognized.width));
+    extracted.metadata.insert("height".to_string(), Value::from(recognized.height));
+    if recognized.lines.is_empty() {
+        return Ok(extracted);
+    }
+
+    let text = recognized
+        .lines
+        .iter()
+        .map(|line| line.text.as_str())
+        .collect::<Vec<_>>()
+        .join("\n");
+    let lines: Vec<Value> = recognized
+        .lines
+        .iter()
+        .map(|line| {
+            json!({
+                "text": line.text,
+                "bbox": line.bbox,
+                "confidence": (line.confidence as f64 * 1000.0).round() / 1000.0,
+            })
+        })
+        .collect();
+    let mut section = Section {
+        text,
+        ..Default::default()
+    };
+    section.metadata.insert("ocr_lines".to_string(), Value::from(lines));
+    extracted.sections.push(section);
+    Ok(extracted)
+}
+
+/// The `ocr_lines` entries of the lines of a section's text that overlap
+/// `range`, so each chunk carries the boxes of the lines it holds rather than
+/// the whole image's. Their text is the section's, which a privacy policy may
+/// have masked. `None` for sections without OCR lines.
+pub(crate) fn ocr_lines_in(section: &Section, range: Range<usize>) -> Option<Value> {
+    let entries = section.metadata.get("ocr_lines")?.as_array()?;
+    let mut start = 0;
+    let mut lines = Vec::new();
+    for (line, entry) in section.text.split('\n').zip(entries) {
+        let end = start + line.len();
+        if start < range.end && range.start < end {
+            let mut entry = entry.clone();
+            entry["text"] = Value::from(line);
+            lines.push(entry);
+        }
+        start = end + 1;
+    }
+    Some(Value::from(lines))
+}
+
+#[derive(Default)]
+struct Exif {
+    taken_at: Option<DateTime<FixedOffset>>,
+    make: Option<String>,
+    model: Option<String>,
+    lens: Option<String>,
+    position: Option<(f64, f64)>,
+}
+
+impl Exif {
+    /// `Apple iPhone 12`, without repeating a make the model already names
+    /// (`Canon` + `Canon EOS R6`).
+    fn camera(&self) -> Option<String> {
+        match (&self.make, &self.model) {
+            (Some(make), Some(model)) if model.to_lowercase().starts_with(&make.to_lowercase()) => Some(model.clone()),
+            (Some(make), Some(model)) => Some(format!("{} {}", make, model)),
+            (make, model) => make.clone().or_else(|| model.clone()),
+        }
+    }
+}
+
+/// The TIFF-structured EXIF block of a JPEG (APP1), PNG (`eXIf`), WebP
+/// (`EXIF`) or TIFF file.
+fn find_exif(bytes: &[u8]) -> Option<&[u8]> {
+    if bytes.starts_with(b"II*\0") || bytes.starts_with(b"MM\0*") {
+        return Some(bytes);
+    }
+
+    if bytes.starts_with(&[0xFF, 0xD8]) {
+        let mut offset = 2;
+        while offset + 4 <= bytes.len() && bytes[offset] == 0xFF {
+            let marker = bytes[offset + 1];
+            // Start of scan: image data follows, no more metadata
+            if marker == 0xDA {
+                break;
+            }
+            let length = u16::from_be_bytes([bytes[offset + 2], bytes[offset + 3]]) as usize;
+            let segment = bytes.get(offset + 4..offset + 2 + length)?;
+            if marker == 0xE1 {
+                if let Some(tiff) = segment.strip_prefix(b"Exif\0\0") {
+                    return Some(tiff);
+                }
+            }
+            offset += 2 + length;
+        }
+        return None;
+    }
+
+    if let Some(mut rest) = bytes.strip_prefix(b"\x89PNG\r\n\x1a\n") {
+        while rest.len() >= 12 {
+            let length = u32::from_be_bytes(rest[..4].try_into().ok()?) as usize;
+            let kind = &rest[4..8];
+            let data = rest.get(8..8 + length)?;
+            if kind == b"eXIf" {
+                return Some(data);
+            }
+            if kind == b"IDAT" || kind == b"IEND" {
+                break;
+            }
+            rest = rest.get(12 + length..)?;
+        }
+        return None;
+    }
+
+    if bytes.starts_with(b"RIFF") && bytes.get(8..12) == Some(b"WEBP") {
+        let mut offset = 12;
+        while offset + 8 <= bytes.len() {
+            let kind = &bytes[offset..offset + 4];
+            let length = u32::from_le_bytes(bytes[offset + 4..offset + 8].try_into().ok()?) as usize;
+            let data = bytes.get(offset + 8..offset + 8 + length)?;
+            if kind == b"EXIF" {
+                return Some(data.strip_prefix(b"Exif\0\0").unwrap_or(data));
+            }
+            // Chunks are padded to an even length
+            offset += 8 + length + (length & 1);
+        }
+    }
+    None
+}
+
+fn read_exif(data: &[u8]) -> Exif {
+    let mut exif = Exif::default();
+    let Some(tiff) = Tiff::new(data) else {
+        return exif;
+    };
+    let Some(ifd0) = tiff.u32(4).map(|offset| offset as usize) else {
+        return exif;
+    };
+
+    let mut modified = None;
+    let mut original = None;
+    let mut offset_time = None;
+    for entry in tiff.entries(ifd0) {
+        match entry.tag {
+            0x010F => exif.make = tiff.ascii(&entry),
+            0x0110 => exif.model = tiff.ascii(&entry),
+            0x0132 => modified = tiff.ascii(&entry),
+            0x8769 => {
+                let Some(sub_ifd) = tiff.u32(entry.value_offset) else {
+                    continue;
+                };
+                for entry in tiff.entries(sub_ifd as usize) {
+                    match entry.tag {
+                        0x9003 => original = tiff.ascii(&entry),
+                        0x9011 => offset_time = tiff.ascii(&entry),
+                        0xA434 => exif.lens = tiff.ascii(&entry),
+                        _ => {}
+                    }
+                }
+            }
+            0x8825 => {
+                if let Some(gps_ifd) = tiff.u32(entry.value_offset) {
+                    exif.position = read_gps(&tiff, gps_ifd as usize);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    exif.taken_at = original.or(modified).and_then(|taken| parse_exif_date(&taken, offset_time.as_deref()));
+    exif
+}
+
+/// Latitude and longitude in degrees, negative south and west.
+fn read_gps(tiff: &Tiff, ifd: usize) -> Option<(f64, f64)> {
+    let mut latitude = None;
+    let mut longitude = None;
+    let mut south = false;
+    let mut west = false;
+    for entry in tiff.entries(ifd) {
+        match entry.tag {
+            1 => south = tiff.ascii(&entry).as_deref() == Some("S"),
+            2 => latitude = degrees(&tiff.rationals(&entry)),
+            3 => west = tiff.ascii(&entry).as_deref() == Some("W"),
+            4 => longitude = degrees(&tiff.rationals(&entry)),
+            _ => {}
+        }
+    }
+    let latitude = latitude? * if south { -1.0 } else { 1.0 };
+    let longitude = longitude? * if west { -1.0 } else { 1.0 };
+    // Cameras without a fix write zeros
+    (latitude != 0.0 || longitude != 0.0).then_some((latitude, longitude))
+}
+
+/// Degrees, minutes and seconds to decimal degrees.
+fn degrees(parts: &[f64]) -> Option<f64> {
+    let [d, m, s] = parts.get(..3)? else {
+        return None;
+    };
+    Some(d + m / 60.0 + s / 3600.0)
+}
+
+/// EXIF times are local to the camera; `OffsetTimeOriginal` says which
+/// zone, and without it the machine's own zone is the best guess.
+fn parse_exif_date(value: &str, offset: Option<&str>) -> Option<DateTime<FixedOffset>> {
+    let local = NaiveDateTime::parse_from_str(value.trim(), EXIF_DATE_FORMAT).ok()?;
+    if let Some(offset) = offset.and_then(|offset| offset.trim().parse::<FixedOffset>().ok()) {
+        return offset.from_local_datetime(&local).single();
+    }
+    Local.from_local_datetime(&local).earliest().map(|taken| taken.fixed_offset())
+}
+
+/// A directory entry: the value sits inline in the entry when it fits in
+/// four bytes, otherwise at the offset stored there.
+struct Entry {
+    tag: u16,
+    kind: u16,
+    count: usize,
+    value_offset: usize,
+}
+
+/// Reads TIFF IFDs in either byte order, bounds-checked throughout.
+struct Tiff<'a> {
+    data: &'a [u8],
+    little_endian: bool,
+}
+
+impl<'a> Tiff<'a> {
+    fn new(data: &'a [u8]) -> Option<Self> {
+        let little_endian = match data.get(..2)? {
+            b"II" => true,
+            b"MM" => false,
+            _ => return None,
+        };
+        Some(Self { data, little_endian })
+    }
+
+    fn u16(&self, offset: usize) -> Option<u16> {
+        let bytes: [u8; 2] = self.data.get(offset..offset + 2)?.try_into().ok()?;
+        Some(if self.little_endian { u16::from_le_bytes(bytes) } else { u16::from_be_bytes(bytes) })
+    }
+
+    fn u32(&self, offset: usize) -> Option<u32> {
+        let bytes: [u8; 4] = self.data.get(offset..offset + 4)?.try_into().ok()?;
+        Some(if self.little_endian { u32::from_le_bytes(bytes) } else { u32::from_be_bytes(bytes) })
+    }
+
+    fn entries(&self, ifd: usize) -> Vec<Entry> {
+        let count = self.u16(ifd).unwrap_or(0) as usize;
+        (0..count)
+            .filter_map(|i| {
+                let at = ifd + 2 + i * 12;
+                let kind = self.u16(at + 2)?;
+                let count = self.u32(at + 4)? as usize;
+                let size: usize = match kind {
+                    1 | 2 | 6 | 7 => 1,
+                    3 | 8 => 2,
+                    4 | 9 | 11 => 4,
+                    _ => 8,
+                };
+                let value_offset = if size.saturating_mul(count) <= 4 { at + 8 } else { self.u32(at + 8)? as usize };
+                Some(Entry {
+                    tag: self.u16(at)?,
+                    kind,
+                    count,
+                    value_offset,
+                })
+            })
+            .collect()
+    }
+
+    fn ascii(&self, entry: &Entry) -> Option<String> {
+        if entry.kind != 2 {
+            return None;
+        }
+        let bytes = self.data.get(entry.value_offset..entry.value_offset.checked_add(entry.count)?)?;
+        let text = String::from_utf8_lossy(bytes);
+        let text = text.trim_end_matches('\0').trim();
+        (!text.is_empty()).then(|| text.to_string())
+    }
+
+    fn rationals(&self, entry: &Entry) -> Vec<f64> {
+        if entry.kind != 5 {
+            return Vec::new();
+        }
+        (0..entry.count.min(16))
+            .filter_map(|i| {
+                let at = entry.value_offset + i * 8;
+                let numerator = self.u32(at)? as f64;
+                let denominator = self.u32(at + 4)? as f64;
+                (denominator != 0.0).then(|| numerator / denominator)
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,249 @@
+use anyhow::Result;
+use pulldown_cmark::{CodeBlockKind, Event, HeadingLevel, Options, Parser, Tag, TagEnd};
+use serde_json::Value;
+use std::path::Path;
+use std::sync::Arc;
+use tracing::warn;
+
+use super::sections::SectionBuilder;
+use super::vault::{parse_note, Vaults};
+use super::{Extracted, FileHandler};
+
+/// Handles Markdown files. Notes in an Obsidian vault or Logseq graph also
+/// get their wikilinks resolved and their tags collected (see `vault`).
+#[derive(Default)]
+pub struct MarkdownHandler {
+    vaults: Arc<Vaults>,
+}
+
+#[async_trait::async_trait]
+impl FileHandler for MarkdownHandler {
+    async fn extract_text(&self, path: &Path) -> Result<String> {
+        Ok(self.extract(path).await?.text())
+    }
+
+    async fn extract(&self, path: &Path) -> Result<Extracted> {
+        let content = tokio::fs::read_to_string(path).await?;
+        let vaults = self.vaults.clone();
+        let path = path.to_path_buf();
+        Ok(tokio::task::spawn_blocking(move || match vaults.get(&path) {
+            Some(vault) => parse_note(&content, &path, &vault),
+            None => parse_markdown(&content),
+        })
+        .await?)
+    }
+}
+
+/// Parses Markdown into plain-text sections, one per heading, each tagged
+/// with its heading breadcrumb (`Guide > Install > Linux`) as `section`.
+/// Front matter fills in the title, tags and date.
+pub fn parse_markdown(content: &str) -> Extracted {
+    let (front_matter, body) = split_front_matter(content);
+
+    let mut extracted = Extracted::default();
+    if let Some(front_matter) = front_matter {
+        apply_front_matter(&front_matter, &mut extracted);
+    }
+
+    let mut builder = SectionBuilder::default();
+    let mut heading: Option<(HeadingLevel, String)> = None;
+
+    for event in Parser::new_ext(body, Options::all()) {
+        match event {
+            Event::Start(Tag::Heading { level, .. }) => {
+                heading = Some((level, String::new()));
+            }
+            Event::End(TagEnd::Heading(_)) => {
+                if let Some((level, title)) = heading.take() {
+                    let title = title.trim().to_string();
+                    if level == HeadingLevel::H1 && extracted.title.is_none() {
+                        extracted.title = Some(title.clone());
+                    }
+                    builder.start_section(level as u8, title);
+                }
+            }
+            Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(info))) => {
+                // Code is kept verbatim; its language is recorded on the section
+                if let Some(language) = info.split_whitespace().next() {
+                    builder.add_code_language(language);
+                }
+            }
+            Event::End(TagEnd::CodeBlock) => builder.push_break("\n\n"),
+            Event::Text(text) | Event::Code(text) | Event::InlineMath(text) | Event::DisplayMath(text) => {
+                match &mut heading {
+                    Some((_, title)) => title.push_str(&text),
+                    None => builder.push(&text),
+                }
+            }
+            Event::SoftBreak => match &mut heading {
+                Some((_, title)) => title.push(' '),
+                None => builder.push(" "),
+            },
+            Event::HardBreak
+            | Event::End(TagEnd::Item)
+            | Event::End(TagEnd::TableRow)
+            | Event::End(TagEnd::TableHead) => {
+                builder.push_break("\n");
+            }
+            Event::End(TagEnd::TableCell) => builder.push(" "),
+            Event::End(TagEnd::Paragraph)
+            | Event::End(TagEnd::BlockQuote(_))
+            | Event::End(TagEnd::List(_))
+            | Event::End(TagEnd::Table)
+            | Event::Rule => {
+                builder.push_break("\n\n");
+            }
+            // Raw HTML, link targets, footnote markers and task checkboxes are noise for search
+            _ => {}
+        }
+    }
+
+    extracted.sections = builder.finish();
+    extracted
+}
+
+enum FrontMatter {
+    Yaml(String),
+    Toml(String),
+}
+
+/// Splits a leading `---` YAML or `+++` TOML block off the document.
+fn split_front_matter(content: &str) -> (Option<FrontMatter>, &str) {
+    let content = content.strip_prefix('\u{feff}').unwrap_or(content);
+
+    for (fence, yaml) in [("---", true), ("+++", false)] {
+        let Some(rest) = content.strip_prefix(fence) else {
+            continue;
+        };
+        let Some(rest) = rest.strip_prefix('\n').or_else(|| rest.strip_prefix("\r\n")) else {
+            continue;
+        };
+
+        let mut offset = 0;
+        for line in rest.split_inclusive('\n') {
+            if line.trim_end() == fence {
+                let block = rest[..offset].to_string();
+                let body = &rest[offset + line.len()..];
+                let front_matter = if yaml {
+                    FrontMatter::Yaml(block)
+                } else {
+                    FrontMatter::Toml(block)
+                };
+                return (Some(front_matter), body);
+            }
+            offset += line.len();
+        }
+    }
+
+    (None, content)
+}
+
+fn apply_front_matter(front_matter: &FrontMatter, extracted: &mut Extracted) {
+    let fields = match front_matter_fields(front_matter) {
+        Ok(Some(fields)) => fields,
+        Ok(None) => return,
+        Err(e) => {
+            warn!("Ignoring malformed front matter: {}", e);
+            return;
+        }
+    };
+
+    if let Some(title) = fields.get("title").and_then(Value::as_str) {
+        extracted.title = Some(title.trim().to_string());
+    }
+
+    if let Some(tags) = fields.get("tags").map(parse_tags).filter(|tags| !tags.is_empty()) {
+        extracted.metadata.insert("tags".to_string(), Value::from(tags));
+    }
+
+    let aliases = aliases(&fields);
+    if !aliases.is_empty() {
+        extracted.metadata.insert("aliases".to_string(), Value::from(aliases));
+    }
+
+    if let Some(date) = fields.get("date").and_then(value_to_string) {
+        extracted.metadata.insert("date".to_string(), Value::String(date));
+    }
+}
+
+fn front_matter_fields(front_matter: &FrontMatter) -> Result<Option<serde_json::Map<String, Value>>, String> {
+    let fields = match front_matter {
+        FrontMatter::Yaml(block) => serde_yaml::from_str::<serde_yaml::Value>(block)
+            .map_err(|e| e.to_string())
+            .and_then(|value| serde_json::to_value(value).map_err(|e| e.to_string())),
+        FrontMatter::Toml(block) => toml::from_str::<toml::Value>(block)
+            .map(toml_to_json)
+            .map_err(|e| e.to_string()),
+    };
+    match fields? {
+        Value::Object(fields) => Ok(Some(fields)),
+        _ => Ok(None),
+    }
+}
+
+/// Other names a note goes by (Obsidian's `aliases`), which wikilinks may
+/// use instead of its file name.
+pub(crate) fn front_matter_aliases(content: &str) -> Vec<String> {
+    match split_front_matter(content).0.map(|front_matter| front_matter_fields(&front_matter)) {
+        Some(Ok(Some(fields))) => aliases(&fields),
+        _ => Vec::new(),
+    }
+}
+
+/// Accepts `aliases: [a, b]` as well as a single `alias: "Full Name"`.
+fn aliases(fields: &serde_json::Map<String, Value>) -> Vec<String> {
+    let value = fields.get("aliases").or_else(|| fields.get("alias"));
+    let aliases: Vec<String> = match value {
+        Some(Value::Array(items)) => items.iter().filter_map(value_to_string).collect(),
+        Some(Value::String(list)) => list.split(',').map(str::to_string).collect(),
+        _ => vec![],
+    };
+    aliases
+        .into_iter()
+        .map(|alias| alias.trim().to_string())
+        .filter(|alias| !alias.is_empty())
+        .collect()
+}
+
+/// Accepts both `tags: [a, b]` and `tags: "a, b"` / `tags: a b`.
+fn parse_tags(value: &Value) -> Vec<String> {
+    let tags: Vec<String> = match value {
+        Value::Array(items) => items.iter().filter_map(value_to_string).collect(),
+        Value::String(list) => list
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .map(str::to_string)
+            .collect(),
+        _ => vec![],
+    };
+
+    tags.into_iter()
+        .map(|tag| tag.trim().trim_start_matches('#').to_string())
+        .filter(|tag| !tag.is_empty())
+        .collect()
+}
+
+fn value_to_string(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+fn toml_to_json(value: toml::Value) -> Value {
+    match value {
+        toml::Value::String(s) => Value::String(s),
+        toml::Value::Integer(i) => Value::from(i),
+        toml::Value::Float(f) => Value::from(f),
+        toml::Value::Boolean(b) => Value::Bool(b),
+        toml::Value::Datetime(dt) => Value::String(dt.to_string()),
+        toml::Value::Array(items) => Value::Array(items.into_iter().map(toml_to_json).collect()),
+        toml::Value::Table(table) => Value::Object(
+            table
+                .into_iter()
+                .map(|(key, value)| (key, toml_to_json(value)))
+                .collect(),
+        ),
+    }
+}
@@ -0,0 +1,118 @@
+use anyhow::Result;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::Path;
+
+pub mod archive;
+pub mod audio;
+pub mod browser;
+pub mod calendar;
+pub mod chat;
+pub mod code;
+pub mod contacts;
+pub mod email;
+pub mod epub;
+pub mod html;
+pub mod image;
+pub mod markdown;
+pub mod office;
+pub mod pdf;
+mod sections;
+pub mod spreadsheet;
+pub mod structured;
+pub mod vault;
+
+pub use archive::ArchiveHandler;
+pub use audio::AudioHandler;
+pub use browser::BrowserHandler;
+pub use calendar::CalendarHandler;
+pub use code::CodeHandler;
+pub use contacts::VcardHandler;
+pub use email::{EmailHandler, MboxHandler};
+pub use epub::EpubHandler;
+pub use html::HtmlHandler;
+pub use image::ImageHandler;
+pub use markdown::MarkdownHandler;
+pub use office::{DocxHandler, OdtHandler, PptxHandler};
+pub use pdf::PdfHandler;
+pub use spreadsheet::SpreadsheetHandler;
+pub use structured::{CsvHandler, JsonHandler, JsonLinesHandler};
+
+/// Text pulled out of a file, split along the units the format knows about
+/// (pages, sections, slides, ...). Chunks never span two sections.
+#[derive(Debug, Clone, Default)]
+pub struct Extracted {
+    /// Title from the file's own metadata, used instead of the file name
+    pub title: Option<String>,
+    /// Document-level metadata such as author or date
+    pub metadata: HashMap<String, Value>,
+    pub sections: Vec<Section>,
+    /// Key of one document within a file that holds several, such as a
+    /// message in an mbox. Stored as `path!/part` so it can be updated alone.
+    pub part: Option<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Section {
+    pub text: String,
+    /// Copied into every chunk cut from this section, e.g. `page`
+    pub metadata: HashMap<String, Value>,
+}
+
+impl Extracted {
+    pub fn from_text(text: String) -> Self {
+        Self {
+            sections: vec![Section {
+                text,
+                metadata: HashMap::new(),
+            }],
+            ..Default::default()
+        }
+    }
+    
+    /// All section text joined with blank lines.
+    pub fn text(&self) -> String {
+        self.sections
+            .iter()
+            .map(|section| section.text.as_str())
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    }
+}
+
+#[async_trait::async_trait]
+pub trait FileHandler: Send + Sync {
+    async fn extract_text(&self, path: &Path) -> Result<String>;
+    
+    /// Structured extraction. Handlers that know about pages or sections
+    /// override this; the default is a single untitled section.
+    async fn extract(&self, path: &Path) -> Result<Extracted> {
+        Ok(Extracted::from_text(self.extract_text(path).await?))
+    }
+    
+    /// Every document in the file. Container formats (mbox, chat exports)
+    /// return one `Extracted` per document, each with its own `part`.
+    async fn extract_all(&self, path: &Path) -> Result<Vec<Extracted>> {
+        Ok(vec![self.extract(path).await?])
+    }
+}
+
+pub struct TextHandler;
+
+#[async_trait::async_trait]
+impl FileHandler for TextHandler {
+    async fn extract_text(&self, path: &Path) -> Result<String> {
+        let content = tokio::fs::read_to_string(path).await?;
+        Ok(content)
+    }
+    
+    /// WhatsApp and Signal exports are plain text too; files named like
+    /// one are split into conversation threads instead.
+    async fn extract(&self, path: &Path) -> Result<Extracted> {
+        let content = self.extract_text(path).await?;
+        if !chat::is_chat_export(path) {
+            return Ok(Extracted::from_text(content));
+        }
+        Ok(chat::parse_chat_text(&content, path).unwrap_or_else(|| Extracted::from_text(content)))
+    }
+}
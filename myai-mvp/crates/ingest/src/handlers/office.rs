@@ -0,0 +1,616 @@
+use anyhow::Result;
+use roxmltree::Node;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use zip::ZipArchive;
+
+use super::archive::{MAX_COMPRESSION_RATIO, MIN_BOMB_BYTES};
+use super::sections::SectionBuilder;
+use super::{Extracted, FileHandler, Section};
+
+const DRAWING_NS: &str = "http://schemas.openxmlformats.org/drawingml/2006/main";
+const RELATIONSHIP_NS: &str = "http://schemas.openxmlformats.org/officeDocument/2006/relationships";
+
+/// Handles Word documents (`.docx`): paragraphs, tables and heading structure.
+pub struct DocxHandler {
+    max_entry: u64,
+}
+
+/// Handles OpenDocument text (`.odt`): paragraphs, lists, tables and headings.
+pub struct OdtHandler {
+    max_entry: u64,
+}
+
+/// Handles PowerPoint decks (`.pptx`): one section per slide, with its speaker notes.
+pub struct PptxHandler {
+    max_entry: u64,
+}
+
+impl DocxHandler {
+    /// Each part of a document may expand to at most `max_file_mb`.
+    pub fn new(max_file_mb: u64) -> Self {
+        Self { max_entry: max_file_mb * 1024 * 1024 }
+    }
+}
+
+impl OdtHandler {
+    /// Each part of a document may expand to at most `max_file_mb`.
+    pub fn new(max_file_mb: u64) -> Self {
+        Self { max_entry: max_file_mb * 1024 * 1024 }
+    }
+}
+
+impl PptxHandler {
+    /// Each part of a deck may expand to at most `max_file_mb`.
+    pub fn new(max_file_mb: u64) -> Self {
+        Self { max_entry: max_file_mb * 1024 * 1024 }
+    }
+}
+
+#[async_trait::async_trait]
+impl FileHandler for DocxHandler {
+    async fn extract_text(&self, path: &Path) -> Result<String> {
+        Ok(self.extract(path).await?.text())
+    }
+
+    async fn extract(&self, path: &Path) -> Result<Extracted> {
+        let path = path.to_path_buf();
+        let max_entry = self.max_entry;
+        tokio::task::spawn_blocking(move || extract_docx(&path, max_entry)).await?
+    }
+}
+
+#[async_trait::async_trait]
+impl FileHandler for OdtHandler {
+    async fn extract_text(&self, path: &Path) -> Result<String> {
+        Ok(self.extract(path).await?.text())
+    }
+
+    async fn extract(&self, path: &Path) -> Result<Extracted> {
+        let path = path.to_path_buf();
+        let max_entry = self.max_entry;
+        tokio::task::spawn_blocking(move || extract_odt(&path, max_entry)).await?
+    }
+}
+
+#[async_trait::async_trait]
+impl FileHandler for PptxHandler {
+    async fn extract_text(&self, path: &Path) -> Result<String> {
+        Ok(self.extract(path).await?.text())
+    }
+
+    async fn extract(&self, path: &Path) -> Result<Extracted> {
+        let path = path.to_path_buf();
+        let max_entry = self.max_entry;
+        tokio::task::spawn_blocking(move || extract_pptx(&path, max_entry)).await?
+    }
+}
+
+fn extract_docx(path: &Path, max_entry: u64) -> Result<Extracted> {
+    let mut archive = ZipDocument::open(path, max_entry)?;
+    let xml = read_entry(&mut archive, "word/document.xml")?
+        .ok_or_else(|| anyhow::anyhow!("Not a Word document: {:?}", path))?;
+    let styles = read_entry(&mut archive, "word/styles.xml")?
+        .map(|styles| heading_styles(&styles))
+        .transpose()?
+        .unwrap_or_default();
+
+    let mut extracted = read_properties(&mut archive)?;
+    let document = roxmltree::Document::parse(&xml)?;
+    let body = document
+        .descendants()
+        .find(|node| node.tag_name().name() == "body")
+        .ok_or_else(|| anyhow::anyhow!("Word document has no body: {:?}", path))?;
+
+    let mut walker = DocxWalker {
+        styles,
+        builder: SectionBuilder::default(),
+        title: None,
+    };
+    walker.walk(body);
+
+    if extracted.title.is_none() {
+        extracted.title = walker.title;
+    }
+    extracted.sections = walker.builder.finish();
+    Ok(extracted)
+}
+
+/// Maps paragraph style ids to heading levels using the style names, so
+/// localized or renamed heading styles are still recognized. The `Title`
+/// style sits above `Heading 1` at level 0.
+fn heading_styles(xml: &str) -> Result<HashMap<String, u8>> {
+    let document = roxmltree::Document::parse(xml)?;
+    let mut levels = HashMap::new();
+
+    for style in document.descendants().filter(|node| node.tag_name().name() == "style") {
+        let Some(id) = local_attr(style, "styleId") else {
+            continue;
+        };
+        let name = child(style, "name")
+            .and_then(|name| local_attr(name, "val"))
+            .unwrap_or(id)
+            .to_lowercase();
+
+        let level = if name == "title" {
+            Some(0)
+        } else {
+            name.strip_prefix("heading")
+                .and_then(|rest| rest.trim().parse::<u8>().ok())
+        };
+        if let Some(level) = level {
+            levels.insert(id.to_string(), level);
+        }
+    }
+
+    Ok(levels)
+}
+
+struct DocxWalker {
+    styles: HashMap<String, u8>,
+    builder: SectionBuilder,
+    title: Option<String>,
+}
+
+impl DocxWalker {
+    fn walk(&mut self, node: Node) {
+        for child in node.children().filter(Node::is_element) {
+            match child.tag_name().name() {
+                "p" => self.paragraph(child),
+                "tbl" => self.table(child),
+                "sectPr" => {}
+                // Content controls, custom XML and the like wrap ordinary paragraphs
+                _ => self.walk(child),
+            }
+        }
+    }
+
+    fn paragraph(&mut self, paragraph: Node) {
+        let text = docx_text(paragraph);
+        let text = text.trim();
+        if text.is_empty() {
+            return;
+        }
+
+        match self.heading_level(paragraph) {
+            Some(level) => {
+                if level == 0 && self.title.is_none() {
+                    self.title = Some(text.to_string());
+                }
+                self.builder.start_section(level, text.to_string());
+            }
+            None => {
+                self.builder.push(text);
+                self.builder.push_break("\n\n");
+            }
+        }
+    }
+
+    fn heading_level(&self, paragraph: Node) -> Option<u8> {
+        let properties = child(paragraph, "pPr")?;
+        let style = child(properties, "pStyle")
+            .and_then(|style| local_attr(style, "val"))
+            .and_then(|id| self.styles.get(id).copied());
+        // Direct outline levels are 0-based
+        let outline = child(properties, "outlineLvl")
+            .and_then(|level| local_attr(level, "val"))
+            .and_then(|level| level.parse::<u8>().ok())
+            .filter(|level| *level < 9)
+            .map(|level| level + 1);
+        style.or(outline)
+    }
+
+    fn table(&mut self, table: Node) {
+        for row in table.children().filter(|node| node.tag_name().name() == "tr") {
+            let cells: Vec<String> = row
+                .children()
+                .filter(|node| node.tag_name().name() == "tc")
+                .map(|cell| {
+                    cell.descendants()
+                        .filter(|node| node.tag_name().name() == "p")
+                        .map(docx_text)
+                        .map(|text| text.trim().to_string())
+                        .filter(|text| !text.is_empty())
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                })
+                .collect();
+            push_row(&mut self.builder, &cells);
+        }
+        self.builder.push_break("\n\n");
+    }
+}
+
+/// Text of a Word paragraph, with tabs and line breaks.
+fn docx_text(paragraph: Node) -> String {
+    let mut text = String::new();
+    // Paragraph properties hold tab stop definitions, not text
+    let content = paragraph
+        .children()
+        .filter(|node| node.tag_name().name() != "pPr")
+        .flat_map(|node| node.descendants());
+    for node in content {
+        match node.tag_name().name() {
+            "t" => text.push_str(node.text().unwrap_or("")),
+            "tab" => text.push('\t'),
+            "br" | "cr" => text.push('\n'),
+            _ => {}
+        }
+    }
+    text
+}
+
+fn extract_odt(path: &Path, max_entry: u64) -> Result<Extracted> {
+    let mut archive = ZipDocument::open(path, max_entry)?;
+    let xml = read_entry(&mut archive, "content.xml")?
+        .ok_or_else(|| anyhow::anyhow!("Not an OpenDocument file: {:?}", path))?;
+
+    let mut extracted = read_properties(&mut archive)?;
+    let document = roxmltree::Document::parse(&xml)?;
+    let body = document
+        .descendants()
+        .find(|node| {
+            node.tag_name().name() == "text"
+                && node.parent_element().is_some_and(|parent| parent.tag_name().name() == "body")
+        })
+        .ok_or_else(|| anyhow::anyhow!("OpenDocument file has no text body: {:?}", path))?;
+
+    let mut builder = SectionBuilder::default();
+    walk_odt(body, &mut builder);
+    extracted.sections = builder.finish();
+    Ok(extracted)
+}
+
+fn walk_odt(node: Node, builder: &mut SectionBuilder) {
+    for child in node.children().filter(Node::is_element) {
+        match child.tag_name().name() {
+            "h" => {
+                let level = local_attr(child, "outline-level")
+                    .and_then(|level| level.parse().ok())
+                    .unwrap_or(1);
+                let title = odt_text(child);
+                let title = title.trim();
+                if !title.is_empty() {
+                    builder.start_section(level, title.to_string());
+                }
+            }
+            "p" => {
+                let text = odt_text(child);
+                if !text.trim().is_empty() {
+                    builder.push(text.trim());
+                    builder.push_break("\n\n");
+                }
+            }
+            "list-item" => {
+                let text = child
+                    .children()
+                    .filter(|node| node.tag_name().name() == "p")
+                    .map(odt_text)
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                if !text.trim().is_empty() {
+                    builder.push(text.trim());
+                    builder.push_break("\n");
+                }
+                // Nested lists
+                for nested in child.children().filter(|node| node.tag_name().name() == "list") {
+                    walk_odt(nested, builder);
+                }
+            }
+            "table" => {
+                for row in child.descendants().filter(|node| node.tag_name().name() == "table-row") {
+                    let cells: Vec<String> = row
+                        .children()
+                        .filter(|node| node.tag_name().name() == "table-cell")
+                        .map(|cell| odt_text(cell).trim().to_string())
+                        .collect();
+                    push_row(builder, &cells);
+                }
+                builder.push_break("\n\n");
+            }
+            "tracked-changes" | "sequence-decls" | "variable-decls" | "user-field-decls" => {}
+            // Lists, sections and frames wrap ordinary paragraphs
+            _ => walk_odt(child, builder),
+        }
+    }
+}
+
+/// Text of an ODF paragraph or heading, expanding spacing elements and
+/// skipping annotations.
+fn odt_text(node: Node) -> String {
+    let mut text = String::new();
+    for child in node.children() {
+        if child.is_text() {
+            text.push_str(child.text().unwrap_or(""));
+            continue;
+        }
+        match child.tag_name().name() {
+            "s" => {
+                let count = local_attr(child, "c")
+                    .and_then(|count| count.parse().ok())
+                    .unwrap_or(1);
+                text.push_str(&" ".repeat(count));
+            }
+            "tab" => text.push('\t'),
+            "line-break" => text.push('\n'),
+            "annotation" | "note" => {}
+            _ => text.push_str(&odt_text(child)),
+        }
+    }
+    text
+}
+
+fn extract_pptx(path: &Path, max_entry: u64) -> Result<Extracted> {
+    let mut archive = ZipDocument::open(path, max_entry)?;
+    let presentation = read_entry(&mut archive, "ppt/presentation.xml")?
+        .ok_or_else(|| anyhow::anyhow!("Not a PowerPoint file: {:?}", path))?;
+    let relationships = read_relationships(&mut archive, "ppt/presentation.xml")?;
+
+    let mut extracted = read_properties(&mut archive)?;
+
+    // Slide order comes from the presentation, not from the part names
+    let document = roxmltree::Document::parse(&presentation)?;
+    let slide_parts: Vec<String> = document
+        .descendants()
+        .filter(|node| node.tag_name().name() == "sldId")
+        .filter_map(|node| node.attribute((RELATIONSHIP_NS, "id")))
+        .filter_map(|id| relationships.get(id))
+        .map(|(_, target)| target.clone())
+        .collect();
+
+    for (i, part) in slide_parts.iter().enumerate() {
+        let number = i + 1;
+        let Some(xml) = read_entry(&mut archive, part)? else {
+            continue;
+        };
+        let slide = roxmltree::Document::parse(&xml)?;
+        let (title, lines) = slide_text(&slide, false);
+
+        let notes = read_relationships(&mut archive, part)?
+            .into_values()
+            .find(|(kind, _)| kind.ends_with("/notesSlide"))
+            .map(|(_, target)| read_entry(&mut archive, &target))
+            .transpose()?
+            .flatten();
+        let notes = match notes {
+            Some(xml) => slide_text(&roxmltree::Document::parse(&xml)?, true).1,
+            None => vec![],
+        };
+
+        if lines.is_empty() && notes.is_empty() {
+            continue;
+        }
+
+        let mut text = lines.join("\n");
+        if !notes.is_empty() {
+            text.push_str("\n\nNotes: ");
+            text.push_str(&notes.join("\n"));
+        }
+
+        let mut metadata = HashMap::new();
+        metadata.insert("slide".to_string(), Value::from(number));
+        metadata.insert(
+            "section".to_string(),
+            Value::String(title.unwrap_or_else(|| format!("Slide {}", number))),
+        );
+        extracted.sections.push(Section {
+            text: text.trim().to_string(),
+            metadata,
+        });
+    }
+
+    extracted
+        .metadata
+        .insert("slides".to_string(), Value::from(slide_parts.len()));
+    Ok(extracted)
+}
+
+/// Lines of text from a slide's shapes and tables, plus the title
+/// placeholder's text. For notes pages only the notes body is read,
+/// skipping the slide thumbnail and slide number.
+fn slide_text(slide: &roxmltree::Document, notes: bool) -> (Option<String>, Vec<String>) {
+    let mut title = None;
+    let mut lines = Vec::new();
+
+    for shape in slide.descendants().filter(|node| node.tag_name().name() == "sp") {
+        let placeholder = shape
+            .descendants()
+            .find(|node| node.tag_name().name() == "ph")
+            .map(|ph| ph.attribute("type").unwrap_or("body"));
+        if notes && placeholder != Some("body") {
+            continue;
+        }
+
+        let paragraphs = drawing_paragraphs(shape);
+        if matches!(placeholder, Some("title" | "ctrTitle")) && title.is_none() && !paragraphs.is_empty() {
+            title = Some(paragraphs.join(" "));
+        }
+        lines.extend(paragraphs);
+    }
+
+    if !notes {
+        for table in slide.descendants().filter(|node| node.has_tag_name((DRAWING_NS, "tbl"))) {
+            for row in table.children().filter(|node| node.has_tag_name((DRAWING_NS, "tr"))) {
+                let cells: Vec<String> = row
+                    .children()
+                    .filter(|node| node.has_tag_name((DRAWING_NS, "tc")))
+                    .map(|cell| drawing_paragraphs(cell).join(" "))
+                    .collect();
+                if cells.iter().any(|cell| !cell.is_empty()) {
+                    lines.push(cells.join(" | "));
+                }
+            }
+        }
+    }
+
+    (title, lines)
+}
+
+fn drawing_paragraphs(node: Node) -> Vec<String> {
+    node.descendants()
+        .filter(|node| node.has_tag_name((DRAWING_NS, "p")))
+        .map(|paragraph| {
+            let mut text = String::new();
+            for node in paragraph.descendants() {
+                if node.has_tag_name((DRAWING_NS, "t")) {
+                    text.push_str(node.text().unwrap_or(""));
+                } else if node.has_tag_name((DRAWING_NS, "br")) {
+                    text.push('\n');
+                }
+            }
+            text.trim().to_string()
+        })
+        .filter(|text| !text.is_empty())
+        .collect()
+}
+
+fn push_row(builder: &mut SectionBuilder, cells: &[String]) {
+    if let Some(row) = format_row(cells) {
+        builder.push(&row);
+        builder.push_break("\n");
+    }
+}
+
+/// Renders a table row as `cell | cell | cell`, or `None` if it's empty.
+pub(super) fn format_row(cells: &[String]) -> Option<String> {
+    if cells.iter().all(|cell| cell.trim().is_empty()) {
+        return None;
+    }
+    let row = cells
+        .iter()
+        .map(|cell| cell.split_whitespace().collect::<Vec<_>>().join(" "))
+        .collect::<Vec<_>>()
+        .join(" | ");
+    Some(row.trim_end_matches([' ', '|']).to_string())
+}
+
+/// A zip-packaged document (OOXML, OpenDocument, EPUB). Its parts are
+/// read with the limits archives get, so a small file can't unpack into a
+/// compression bomb.
+pub(super) struct ZipDocument {
+    archive: ZipArchive<File>,
+    /// Bytes one part may expand to
+    max_entry: u64,
+}
+
+impl ZipDocument {
+    pub(super) fn open(path: &Path, max_entry: u64) -> Result<Self> {
+        Ok(Self {
+            archive: ZipArchive::new(File::open(path)?)?,
+            max_entry,
+        })
+    }
+}
+
+/// Reads an archive member as UTF-8, or `None` if the member doesn't exist.
+/// Fails for members larger than the document's limit or compressed like a
+/// bomb; sizes in zip headers can lie, so the limit applies to the bytes
+/// actually read.
+pub(super) fn read_entry(archive: &mut ZipDocument, name: &str) -> Result<Option<String>> {
+    let limit = archive.max_entry;
+    let entry = match archive.archive.by_name(name) {
+        Ok(entry) => entry,
+        Err(zip::result::ZipError::FileNotFound) => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let size = entry.size();
+    if size > MIN_BOMB_BYTES && size / entry.compressed_size().max(1) > MAX_COMPRESSION_RATIO {
+        return Err(anyhow::anyhow!("{} is compressed too much to be read", name));
+    }
+    let mut xml = String::new();
+    entry.take(limit + 1).read_to_string(&mut xml)?;
+    if xml.len() as u64 > limit {
+        return Err(anyhow::anyhow!("{} is larger than {}MB", name, limit / (1024 * 1024)));
+    }
+    Ok(Some(xml))
+}
+
+/// Relationship id -> (type, archive path) for an OOXML part.
+fn read_relationships(archive: &mut ZipDocument, part: &str) -> Result<HashMap<String, (String, String)>> {
+    let (dir, file) = part.rsplit_once('/').unwrap_or(("", part));
+    let Some(xml) = read_entry(archive, &format!("{}/_rels/{}.rels", dir, file))? else {
+        return Ok(HashMap::new());
+    };
+
+    let document = roxmltree::Document::parse(&xml)?;
+    Ok(document
+        .descendants()
+        .filter(|node| node.tag_name().name() == "Relationship")
+        .filter_map(|node| {
+            let id = node.attribute("Id")?;
+            let target = resolve_target(dir, node.attribute("Target")?);
+            Some((id.to_string(), (node.attribute("Type").unwrap_or("").to_string(), target)))
+        })
+        .collect())
+}
+
+/// Resolves a relationship target relative to the directory of its source part.
+pub(super) fn resolve_target(dir: &str, target: &str) -> String {
+    if let Some(absolute) = target.strip_prefix('/') {
+        return absolute.to_string();
+    }
+
+    let mut parts: Vec<&str> = dir.split('/').filter(|part| !part.is_empty()).collect();
+    for segment in target.split('/') {
+        match segment {
+            ".." => {
+                parts.pop();
+            }
+            "." | "" => {}
+            segment => parts.push(segment),
+        }
+    }
+    parts.join("/")
+}
+
+/// Title, author and creation date from OOXML `docProps/core.xml` or ODF
+/// `meta.xml`, whichever the archive has.
+pub(super) fn read_properties(archive: &mut ZipDocument) -> Result<Extracted> {
+    let mut extracted = Extracted::default();
+    let xml = match read_entry(archive, "docProps/core.xml")? {
+        Some(xml) => xml,
+        None => match read_entry(archive, "meta.xml")? {
+            Some(xml) => xml,
+            None => return Ok(extracted),
+        },
+    };
+
+    let document = roxmltree::Document::parse(&xml)?;
+    let field = |names: &[&str]| {
+        names.iter().find_map(|name| {
+            document
+                .descendants()
+                .find(|node| node.tag_name().name() == *name)
+                .and_then(|node| node.text())
+                .map(str::trim)
+                .filter(|text| !text.is_empty())
+                .map(str::to_string)
+        })
+    };
+
+    extracted.title = field(&["title"]);
+    if let Some(author) = field(&["creator", "initial-creator"]) {
+        extracted.metadata.insert("author".to_string(), Value::String(author));
+    }
+    if let Some(date) = field(&["created", "creation-date"]) {
+        extracted
+            .metadata
+            .insert("date".to_string(), Value::String(date.chars().take(10).collect()));
+    }
+
+    Ok(extracted)
+}
+
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children().find(|child| child.tag_name().name() == name)
+}
+
+/// Attribute by local name, ignoring its namespace prefix.
+fn local_attr<'a>(node: Node<'a, '_>, name: &str) -> Option<&'a str> {
+    node.attributes()
+        .find(|attribute| attribute.name() == name)
+        .map(|attribute| attribute.value())
+}
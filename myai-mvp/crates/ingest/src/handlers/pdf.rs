@@ -0,0 +1,351 @@
+use anyhow::Result;
+use pdf_extract::{
+    decode_text_string, output_doc_page, Document as PdfDocument, MediaBox, OutputDev, OutputError,
+    Transform,
+};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::Path;
+use tracing::warn;
+
+use super::{Extracted, FileHandler, Section};
+
+/// Share of lines allowed to cross a column gutter (headings, footers)
+const GUTTER_TOLERANCE: f64 = 0.15;
+/// Widest page the PDF spec allows (200 inches), in points; a MediaBox
+/// wider than this is bogus and only measured up to it
+const MAX_PAGE_WIDTH: f64 = 14_400.0;
+
+pub struct PdfHandler;
+
+#[async_trait::async_trait]
+impl FileHandler for PdfHandler {
+    async fn extract_text(&self, path: &Path) -> Result<String> {
+        Ok(self.extract(path).await?.text())
+    }
+
+    async fn extract(&self, path: &Path) -> Result<Extracted> {
+        let path = path.to_path_buf();
+        tokio::task::spawn_blocking(move || extract_pdf(&path)).await?
+    }
+}
+
+fn extract_pdf(path: &Path) -> Result<Extracted> {
+    let mut doc = PdfDocument::load(path)?;
+    if doc.is_encrypted() {
+        // Many PDFs are "encrypted" with an empty user password
+        doc.decrypt("")
+            .map_err(|e| anyhow::anyhow!("Cannot decrypt {:?}: {}", path, e))?;
+    }
+
+    let mut extracted = Extracted::default();
+    read_info(&doc, &mut extracted);
+
+    let pages: Vec<u32> = doc.get_pages().keys().copied().collect();
+    extracted
+        .metadata
+        .insert("pages".to_string(), Value::from(pages.len()));
+
+    for page_num in pages {
+        let mut layout = PageLayout::default();
+        if let Err(e) = output_doc_page(&doc, &mut layout, page_num) {
+            warn!("Skipping page {} of {:?}: {:?}", page_num, path, e);
+            continue;
+        }
+
+        let text = layout.text.trim();
+        if text.is_empty() {
+            continue;
+        }
+
+        let mut metadata = HashMap::new();
+        metadata.insert("page".to_string(), Value::from(page_num));
+        extracted.sections.push(Section {
+            text: text.to_string(),
+            metadata,
+        });
+    }
+
+    Ok(extracted)
+}
+
+/// Copies title, author and creation date from the PDF Info dictionary.
+fn read_info(doc: &PdfDocument, extracted: &mut Extracted) {
+    let Ok(info) = doc
+        .trailer
+        .get_deref(b"Info", doc)
+        .and_then(|info| info.as_dict())
+    else {
+        return;
+    };
+
+    let field = |key: &[u8]| {
+        info.get_deref(key, doc)
+            .ok()
+            .and_then(|value| decode_text_string(value).ok())
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+    };
+
+    extracted.title = field(b"Title");
+    if let Some(author) = field(b"Author") {
+        extracted.metadata.insert("author".to_string(), Value::String(author));
+    }
+    if let Some(date) = field(b"CreationDate").and_then(|date| parse_pdf_date(&date)) {
+        extracted.metadata.insert("date".to_string(), Value::String(date));
+    }
+}
+
+/// Turns a PDF date (`D:YYYYMMDDHHmmSS...`) into `YYYY-MM-DD`.
+fn parse_pdf_date(date: &str) -> Option<String> {
+    let digits = date.strip_prefix("D:").unwrap_or(date).as_bytes().get(..8)?;
+    if !digits.iter().all(u8::is_ascii_digit) {
+        return None;
+    }
+    // All ASCII, so every byte is a char
+    let digits = std::str::from_utf8(digits).ok()?;
+    Some(format!("{}-{}-{}", &digits[..4], &digits[4..6], &digits[6..8]))
+}
+
+#[derive(Debug, Clone)]
+struct Glyph {
+    x: f64,
+    y: f64,
+    end: f64,
+    size: f64,
+    text: String,
+}
+
+/// Collects positioned glyphs for one page and lays them out as text in
+/// reading order, detecting a two-column layout by its empty gutter.
+#[derive(Default)]
+struct PageLayout {
+    glyphs: Vec<Glyph>,
+    left: f64,
+    width: f64,
+    height: f64,
+    text: String,
+}
+
+impl OutputDev for PageLayout {
+    fn begin_page(&mut self, _page_num: u32, media_box: &MediaBox, _: Option<(f64, f64, f64, f64)>) -> Result<(), OutputError> {
+        self.left = media_box.llx;
+        self.width = media_box.urx - media_box.llx;
+        self.height = media_box.ury - media_box.lly;
+        self.glyphs.clear();
+        Ok(())
+    }
+
+    fn end_page(&mut self) -> Result<(), OutputError> {
+        self.text = layout_page(&self.glyphs, self.width);
+        Ok(())
+    }
+
+    fn output_character(&mut self, trm: &Transform, width: f64, _spacing: f64, font_size: f64, char: &str) -> Result<(), OutputError> {
+        // Font size as scaled by the text matrix, with y flipped to grow down the page
+        let scaled_x = font_size * (trm.m11 + trm.m21);
+        let scaled_y = font_size * (trm.m12 + trm.m22);
+        let size = (scaled_x * scaled_y).abs().sqrt().max(1.0);
+
+        let x = trm.m31 - self.left;
+        self.glyphs.push(Glyph {
+            x,
+            y: self.height - trm.m32,
+            end: x + width * size,
+            size,
+            text: char.to_string(),
+        });
+        Ok(())
+    }
+
+    fn begin_word(&mut self) -> Result<(), OutputError> {
+        Ok(())
+    }
+
+    fn end_word(&mut self) -> Result<(), OutputError> {
+        Ok(())
+    }
+
+    fn end_line(&mut self) -> Result<(), OutputError> {
+        Ok(())
+    }
+}
+
+struct Line {
+    y: f64,
+    size: f64,
+    glyphs: Vec<Glyph>,
+}
+
+fn layout_page(glyphs: &[Glyph], page_width: f64) -> String {
+    let lines = group_lines(glyphs.to_vec());
+    if lines.is_empty() {
+        return String::new();
+    }
+
+    let Some(gutter) = find_gutter(&lines, page_width) else {
+        return render_lines(&lines);
+    };
+
+    // Walk down the page; full-width lines (titles, figure captions spanning
+    // both columns) flush the columns collected above them.
+    let mut out = Vec::new();
+    let mut left = Vec::new();
+    let mut right = Vec::new();
+
+    for line in lines {
+        let crosses = line.glyphs.iter().any(|g| g.x < gutter && g.end > gutter);
+        if crosses {
+            flush_columns(&mut out, &mut left, &mut right);
+            out.push(render_lines(&[line]));
+            continue;
+        }
+
+        let (l, r): (Vec<Glyph>, Vec<Glyph>) = line.glyphs.into_iter().partition(|g| g.x < gutter);
+        if !l.is_empty() {
+            left.push(Line { y: line.y, size: line.size, glyphs: l });
+        }
+        if !r.is_empty() {
+            right.push(Line { y: line.y, size: line.size, glyphs: r });
+        }
+    }
+    flush_columns(&mut out, &mut left, &mut right);
+
+    out.join("\n\n")
+}
+
+fn flush_columns(out: &mut Vec<String>, left: &mut Vec<Line>, right: &mut Vec<Line>) {
+    for column in [left, right] {
+        if !column.is_empty() {
+            out.push(render_lines(column));
+            column.clear();
+        }
+    }
+}
+
+/// Groups glyphs whose baselines are within half a font size into lines,
+/// top to bottom, each sorted left to right.
+fn group_lines(mut glyphs: Vec<Glyph>) -> Vec<Line> {
+    glyphs.sort_by(|a, b| a.y.total_cmp(&b.y).then(a.x.total_cmp(&b.x)));
+
+    let mut lines: Vec<Line> = Vec::new();
+    for glyph in glyphs {
+        match lines.last_mut() {
+            Some(line) if (glyph.y - line.y).abs() <= line.size * 0.5 => {
+                line.size = line.size.max(glyph.size);
+                line.glyphs.push(glyph);
+            }
+            _ => lines.push(Line {
+                y: glyph.y,
+                size: glyph.size,
+                glyphs: vec![glyph],
+            }),
+        }
+    }
+
+    for line in &mut lines {
+        line.glyphs.sort_by(|a, b| a.x.total_cmp(&b.x));
+    }
+    lines
+}
+
+/// Finds the x position of an empty vertical band in the middle of the page
+/// that most lines respect, i.e. the gap between two text columns.
+fn find_gutter(lines: &[Line], page_width: f64) -> Option<f64> {
+    if lines.len() < 6 || page_width.is_nan() || page_width <= 0.0 {
+        return None;
+    }
+
+    let page_width = page_width.min(MAX_PAGE_WIDTH);
+    let bins = page_width.ceil() as usize + 1;
+    let mut coverage = vec![0usize; bins];
+    let mut covered = vec![false; bins];
+    for line in lines {
+        covered.fill(false);
+        for glyph in &line.glyphs {
+            let start = glyph.x.max(0.0) as usize;
+            let end = (glyph.end.max(glyph.x + 1.0) as usize).min(bins - 1);
+            for bin in covered.iter_mut().take(end + 1).skip(start.min(bins - 1)) {
+                *bin = true;
+            }
+        }
+        for (count, &hit) in coverage.iter_mut().zip(&covered) {
+            *count += hit as usize;
+        }
+    }
+
+    let allowed = (lines.len() as f64 * GUTTER_TOLERANCE) as usize;
+    let lo = (page_width * 0.3) as usize;
+    let hi = ((page_width * 0.7) as usize).min(bins - 1);
+
+    // Widest run of nearly-empty bins within the central band
+    let mut best: Option<(usize, usize)> = None;
+    let mut run_start = None;
+    for (bin, &count) in coverage.iter().enumerate().take(hi + 1).skip(lo) {
+        if count <= allowed {
+            run_start.get_or_insert(bin);
+        } else if let Some(start) = run_start.take() {
+            if best.is_none_or(|(s, e)| bin - start > e - s) {
+                best = Some((start, bin));
+            }
+        }
+    }
+    if let Some(start) = run_start {
+        if best.is_none_or(|(s, e)| hi + 1 - start > e - s) {
+            best = Some((start, hi + 1));
+        }
+    }
+
+    let (start, end) = best?;
+    let mut sizes: Vec<f64> = lines.iter().map(|line| line.size).collect();
+    sizes.sort_by(|a, b| a.total_cmp(b));
+    let median_size = sizes[sizes.len() / 2];
+    if ((end - start) as f64) < median_size * 1.5 {
+        return None;
+    }
+
+    // Both sides need real text, otherwise it's just an indented page
+    let gutter = (start + end) as f64 / 2.0;
+    let both_sides = lines
+        .iter()
+        .filter(|line| {
+            line.glyphs.iter().any(|g| g.end <= gutter) && line.glyphs.iter().any(|g| g.x >= gutter)
+        })
+        .count();
+    if both_sides * 5 < lines.len() {
+        return None;
+    }
+
+    Some(gutter)
+}
+
+/// Renders lines as text, adding spaces at word gaps and a blank line
+/// where the vertical gap suggests a new paragraph.
+fn render_lines(lines: &[Line]) -> String {
+    let mut out = String::new();
+    let mut previous: Option<(f64, f64)> = None;
+
+    for line in lines {
+        if let Some((y, size)) = previous {
+            out.push('\n');
+            if line.y - y > size * 1.8 {
+                out.push('\n');
+            }
+        }
+
+        let mut last_end: Option<f64> = None;
+        for glyph in &line.glyphs {
+            if let Some(end) = last_end {
+                if glyph.x > end + glyph.size * 0.15 && !out.ends_with(' ') {
+                    out.push(' ');
+                }
+            }
+            out.push_str(&glyph.text);
+            last_end = Some(glyph.end);
+        }
+
+        previous = Some((line.y, line.size));
+    }
+
+    out
+}
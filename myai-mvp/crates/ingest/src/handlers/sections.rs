@@ -0,0 +1,98 @@
+use serde_json::Value;
+use std::collections::HashMap;
+
+use super::Section;
+
+/// Accumulates text under a stack of headings and cuts a `Section` each
+/// time a new heading starts, tagged with the heading breadcrumb
+/// (`Guide > Install > Linux`) as `section`.
+#[derive(Default)]
+pub(crate) struct SectionBuilder {
+    sections: Vec<Section>,
+    breadcrumb: Vec<(u8, String)>,
+    text: String,
+    code_languages: Vec<String>,
+}
+
+impl SectionBuilder {
+    pub(crate) fn start_section(&mut self, level: u8, title: String) {
+        self.flush();
+        self.breadcrumb.retain(|(parent, _)| *parent < level);
+        self.breadcrumb.push((level, title.clone()));
+        // Keep the heading in the text so it's searchable
+        self.text.push_str(&title);
+        self.text.push_str("\n\n");
+    }
+
+    pub(crate) fn push(&mut self, text: &str) {
+        self.text.push_str(text);
+    }
+
+    /// Ends the current line or paragraph. A break never weakens one that is
+    /// already there, so nested blocks don't collapse paragraph gaps.
+    pub(crate) fn push_break(&mut self, separator: &str) {
+        let content = self.text.trim_end_matches([' ', '\n']).len();
+        let existing = self.text[content..].matches('\n').count();
+        let newlines = separator.matches('\n').count().max(existing);
+        self.text.truncate(content);
+        if !self.text.is_empty() {
+            self.text.push_str(&"\n".repeat(newlines));
+        }
+    }
+
+    /// Whether the text so far ends at a word boundary, so leading
+    /// whitespace in the next piece can be dropped.
+    pub(crate) fn at_word_boundary(&self) -> bool {
+        self.text.is_empty() || self.text.ends_with([' ', '\n'])
+    }
+
+    pub(crate) fn add_code_language(&mut self, language: &str) {
+        if !self.code_languages.iter().any(|known| known == language) {
+            self.code_languages.push(language.to_string());
+        }
+    }
+
+    fn flush(&mut self) {
+        // A heading with nothing under it is folded into the next section
+        let text = self.text.trim();
+        let heading_only = self
+            .breadcrumb
+            .last()
+            .map(|(_, title)| title == text)
+            .unwrap_or(false);
+        if text.is_empty() || heading_only {
+            return;
+        }
+        self.emit();
+    }
+
+    fn emit(&mut self) {
+        let text = self.text.trim().to_string();
+        self.text.clear();
+        let code_languages = std::mem::take(&mut self.code_languages);
+        if text.is_empty() {
+            return;
+        }
+
+        let mut metadata = HashMap::new();
+        if !self.breadcrumb.is_empty() {
+            let breadcrumb = self
+                .breadcrumb
+                .iter()
+                .map(|(_, title)| title.as_str())
+                .collect::<Vec<_>>()
+                .join(" > ");
+            metadata.insert("section".to_string(), Value::String(breadcrumb));
+        }
+        if !code_languages.is_empty() {
+            metadata.insert("code_languages".to_string(), Value::from(code_languages));
+        }
+
+        self.sections.push(Section { text, metadata });
+    }
+
+    pub(crate) fn finish(mut self) -> Vec<Section> {
+        self.emit();
+        self.sections
+    }
+}
@@ -0,0 +1,88 @@
+use anyhow::Result;
+use calamine::{open_workbook_auto, Data, DataType, Reader};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::Path;
+use tracing::warn;
+
+use super::office::{format_row, read_properties, ZipDocument};
+use super::{Extracted, FileHandler, Section};
+
+/// Handles XLSX, XLS and ODS workbooks: one section per sheet, rendered as
+/// `cell | cell` rows with the sheet name in `sheet`.
+pub struct SpreadsheetHandler {
+    /// Bytes one part of an XLSX or ODS package may expand to
+    max_entry: u64,
+}
+
+impl SpreadsheetHandler {
+    pub fn new(max_file_mb: u64) -> Self {
+        Self { max_entry: max_file_mb * 1024 * 1024 }
+    }
+}
+
+#[async_trait::async_trait]
+impl FileHandler for SpreadsheetHandler {
+    async fn extract_text(&self, path: &Path) -> Result<String> {
+        Ok(self.extract(path).await?.text())
+    }
+
+    async fn extract(&self, path: &Path) -> Result<Extracted> {
+        let path = path.to_path_buf();
+        let max_entry = self.max_entry;
+        tokio::task::spawn_blocking(move || extract_workbook(&path, max_entry)).await?
+    }
+}
+
+fn extract_workbook(path: &Path, max_entry: u64) -> Result<Extracted> {
+    let mut extracted = match ZipDocument::open(path, max_entry) {
+        Ok(mut archive) => read_properties(&mut archive)?,
+        // Legacy .xls isn't a zip archive
+        Err(_) => Extracted::default(),
+    };
+
+    let mut workbook = open_workbook_auto(path)
+        .map_err(|e| anyhow::anyhow!("Cannot open workbook {:?}: {}", path, e))?;
+    let sheet_names = workbook.sheet_names();
+
+    for name in &sheet_names {
+        let range = match workbook.worksheet_range(name) {
+            Ok(range) => range,
+            Err(e) => {
+                warn!("Skipping sheet {:?} of {:?}: {}", name, path, e);
+                continue;
+            }
+        };
+
+        let text = range
+            .rows()
+            .filter_map(|row| format_row(&row.iter().map(format_cell).collect::<Vec<_>>()))
+            .collect::<Vec<_>>()
+            .join("\n");
+        if text.is_empty() {
+            continue;
+        }
+
+        let mut metadata = HashMap::new();
+        metadata.insert("sheet".to_string(), Value::String(name.clone()));
+        metadata.insert("section".to_string(), Value::String(name.clone()));
+        extracted.sections.push(Section { text, metadata });
+    }
+
+    extracted
+        .metadata
+        .insert("sheets".to_string(), Value::from(sheet_names));
+    Ok(extracted)
+}
+
+fn format_cell(cell: &Data) -> String {
+    match cell {
+        Data::DateTime(_) => match cell.as_datetime() {
+            Some(datetime) if datetime.time() == chrono::NaiveTime::MIN => datetime.format("%Y-%m-%d").to_string(),
+            Some(datetime) => datetime.format("%Y-%m-%d %H:%M:%S").to_string(),
+            None => cell.to_string(),
+        },
+        Data::Error(_) => String::new(),
+        _ => cell.to_string(),
+    }
+}
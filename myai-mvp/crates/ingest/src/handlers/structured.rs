@@ -0,0 +1,544 @@
+use anyhow::Result;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use serde::de::{self, DeserializeSeed, MapAccess, SeqAccess, Visitor};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use tracing::warn;
+
+use super::chat::read_chat_json;
+use super::{Extracted, FileHandler, Section};
+
+/// Rows kept per file. Larger files are sampled down to this many so they
+/// never have to be held in memory whole.
+const MAX_ROWS: usize = 5_000;
+
+/// Share of a column's values that must parse as dates for it to date rows
+const DATE_COLUMN_MIN_SHARE: f64 = 0.8;
+
+/// Column names that suggest a date, checked before any other column
+const DATE_HINTS: &[&str] = &["date", "time", "timestamp", "created", "posted", "booked", "when", "day"];
+
+/// Handles CSV and TSV exports: one section per row.
+pub struct CsvHandler;
+
+#[async_trait::async_trait]
+impl FileHandler for CsvHandler {
+    async fn extract_text(&self, path: &Path) -> Result<String> {
+        Ok(self.extract(path).await?.text())
+    }
+
+    async fn extract(&self, path: &Path) -> Result<Extracted> {
+        let path = path.to_path_buf();
+        tokio::task::spawn_blocking(move || {
+            let mut rows = RowSample::new(MAX_ROWS);
+            let columns = read_csv(&path, &mut rows)?;
+            Ok(rows.into_extracted(columns, Map::new()))
+        })
+        .await?
+    }
+}
+
+/// Handles JSON files: an array of records, an object wrapping one or more
+/// arrays of records (`{"transactions": [...]}`), or a single record. Slack
+/// and Discord exports are read as conversations instead.
+pub struct JsonHandler;
+
+#[async_trait::async_trait]
+impl FileHandler for JsonHandler {
+    async fn extract_text(&self, path: &Path) -> Result<String> {
+        Ok(self.extract(path).await?.text())
+    }
+
+    async fn extract(&self, path: &Path) -> Result<Extracted> {
+        let path = path.to_path_buf();
+        tokio::task::spawn_blocking(move || {
+            if let Some(chat) = read_chat_json(&path)? {
+                return Ok(chat);
+            }
+            let mut rows = RowSample::new(MAX_ROWS);
+            let envelope = read_json(&path, &mut rows)?;
+            Ok(rows.into_extracted(Vec::new(), envelope))
+        })
+        .await?
+    }
+}
+
+/// Handles JSON Lines: one record per line.
+pub struct JsonLinesHandler;
+
+#[async_trait::async_trait]
+impl FileHandler for JsonLinesHandler {
+    async fn extract_text(&self, path: &Path) -> Result<String> {
+        Ok(self.extract(path).await?.text())
+    }
+
+    async fn extract(&self, path: &Path) -> Result<Extracted> {
+        let path = path.to_path_buf();
+        tokio::task::spawn_blocking(move || {
+            let mut rows = RowSample::new(MAX_ROWS);
+            read_json_lines(&path, &mut rows)?;
+            Ok(rows.into_extracted(Vec::new(), Map::new()))
+        })
+        .await?
+    }
+}
+
+type Record = Vec<(String, Value)>;
+
+/// Reservoir sample of a file's rows, in file order once finished. A fixed
+/// seed keeps the sample the same between runs so unchanged rows keep their
+/// embeddings on re-ingest.
+struct RowSample {
+    limit: usize,
+    seen: usize,
+    rows: Vec<(usize, Record)>,
+    state: u64,
+}
+
+impl RowSample {
+    fn new(limit: usize) -> Self {
+        Self {
+            limit,
+            seen: 0,
+            rows: Vec::new(),
+            state: 0x9E37_79B9_7F4A_7C15,
+        }
+    }
+
+    fn push(&mut self, record: Record) {
+        let index = self.seen;
+        self.seen += 1;
+        if self.rows.len() < self.limit {
+            self.rows.push((index, record));
+            return;
+        }
+        let slot = (self.next_random() % self.seen as u64) as usize;
+        if slot < self.limit {
+            self.rows[slot] = (index, record);
+        }
+    }
+
+    fn push_value(&mut self, value: Value) {
+        let mut record = Vec::new();
+        match value {
+            Value::Object(_) => flatten("", value, &mut record),
+            value => record.push(("value".to_string(), value)),
+        }
+        if !record.is_empty() {
+            self.push(record);
+        }
+    }
+
+    /// splitmix64
+    fn next_random(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Renders every kept row as `column: value` lines, with the typed values
+    /// in `fields` and the inferred date column as `created_at`.
+    fn into_extracted(mut self, mut columns: Vec<String>, envelope: Map<String, Value>) -> Extracted {
+        self.rows.sort_by_key(|(index, _)| *index);
+
+        if columns.is_empty() {
+            for (_, record) in &self.rows {
+                for (key, _) in record {
+                    if !columns.contains(key) {
+                        columns.push(key.clone());
+                    }
+                }
+            }
+        }
+        let date_column = infer_date_column(&columns, &self.rows);
+
+        let mut extracted = Extracted::default();
+        for (index, record) in self.rows {
+            let text = record
+                .iter()
+                .filter_map(|(key, value)| render_value(value).map(|value| format!("{}: {}", key, value)))
+                .collect::<Vec<_>>()
+                .join("\n");
+            if text.is_empty() {
+                continue;
+            }
+
+            let mut metadata = HashMap::new();
+            if let Some(created_at) = date_column.as_ref().and_then(|column| {
+                let (_, value) = record.iter().find(|(key, _)| key == column)?;
+                parse_date_value(value, true)
+            }) {
+                metadata.insert("created_at".to_string(), Value::String(created_at.to_rfc3339()));
+            }
+            metadata.insert("row".to_string(), Value::from(index + 1));
+            metadata.insert("section".to_string(), Value::String(format!("Row {}", index + 1)));
+            metadata.insert("fields".to_string(), Value::Object(record.into_iter().collect()));
+            extracted.sections.push(Section { text, metadata });
+        }
+
+        // Scalars next to the record arrays describe the whole file
+        for (key, value) in envelope {
+            if !value.is_array() && !value.is_object() && !value.is_null() {
+                extracted.metadata.insert(key, value);
+            }
+        }
+        extracted.metadata.insert("columns".to_string(), Value::from(columns));
+        extracted.metadata.insert("rows".to_string(), Value::from(self.seen));
+        if self.seen > self.limit {
+            extracted.metadata.insert("sampled".to_string(), Value::Bool(true));
+        }
+        if let Some(column) = date_column {
+            extracted.metadata.insert("date_column".to_string(), Value::String(column));
+        }
+        extracted
+    }
+}
+
+/// Streams a CSV file into `rows` and returns its header. The delimiter is
+/// sniffed from the first line so `;`-separated exports work too.
+fn read_csv(path: &Path, rows: &mut RowSample) -> Result<Vec<String>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let is_tsv = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("tsv"));
+    let delimiter = if is_tsv { b'\t' } else { sniff_delimiter(reader.fill_buf()?) };
+    // Exports that separate with `;` usually write decimals as `3,50`
+    let decimal_comma = delimiter == b';';
+
+    let mut csv = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .flexible(true)
+        .from_reader(reader);
+
+    let columns: Vec<String> = csv
+        .byte_headers()?
+        .iter()
+        .enumerate()
+        .map(|(i, name)| {
+            let name = String::from_utf8_lossy(name).trim().to_string();
+            if name.is_empty() { format!("column_{}", i + 1) } else { name }
+        })
+        .collect();
+
+    for (i, row) in csv.byte_records().enumerate() {
+        let row = match row {
+            Ok(row) => row,
+            Err(e) => {
+                warn!("Skipping malformed row {} of {:?}: {}", i + 2, path, e);
+                continue;
+            }
+        };
+        let record: Record = row
+            .iter()
+            .enumerate()
+            .map(|(i, cell)| {
+                let column = columns.get(i).cloned().unwrap_or_else(|| format!("column_{}", i + 1));
+                (column, typed_cell(&String::from_utf8_lossy(cell), decimal_comma))
+            })
+            .filter(|(_, value)| !value.is_null())
+            .collect();
+        if !record.is_empty() {
+            rows.push(record);
+        }
+    }
+
+    Ok(columns)
+}
+
+fn sniff_delimiter(head: &[u8]) -> u8 {
+    let first_line = head.split(|&b| b == b'\n').next().unwrap_or_default();
+    [b',', b';', b'\t', b'|']
+        .into_iter()
+        .max_by_key(|delimiter| first_line.iter().filter(|&b| b == delimiter).count())
+        .filter(|delimiter| first_line.contains(delimiter))
+        .unwrap_or(b',')
+}
+
+/// Numbers and booleans become typed values; anything that looks like an
+/// identifier (leading zeros, as in account numbers or ZIP codes) stays text.
+fn typed_cell(cell: &str, decimal_comma: bool) -> Value {
+    let cell = cell.trim();
+    if cell.is_empty() {
+        return Value::Null;
+    }
+    if decimal_comma && cell.matches(',').count() == 1 && !cell.contains('.') {
+        if let Value::Number(number) = typed_cell(&cell.replace(',', "."), false) {
+            return Value::Number(number);
+        }
+    }
+    if cell.eq_ignore_ascii_case("true") || cell.eq_ignore_ascii_case("false") {
+        return Value::Bool(cell.eq_ignore_ascii_case("true"));
+    }
+    let digits = cell.trim_start_matches('-');
+    let leading_zero = digits.len() > 1 && digits.starts_with('0') && !digits.starts_with("0.");
+    if !leading_zero {
+        if let Ok(number) = cell.parse::<i64>() {
+            return Value::from(number);
+        }
+        if digits.starts_with(|c: char| c.is_ascii_digit()) {
+            if let Some(number) = cell.parse::<f64>().ok().filter(|n| n.is_finite()) {
+                return Value::from(number);
+            }
+        }
+    }
+    Value::String(cell.to_string())
+}
+
+/// Streams a JSON document into `rows`, never holding more than one record
+/// of a top-level array at a time. Returns the top-level object's other
+/// fields when it wraps arrays of records.
+fn read_json(path: &Path, rows: &mut RowSample) -> Result<Map<String, Value>> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut deserializer = serde_json::Deserializer::from_reader(reader);
+    let envelope = TopLevel { rows }.deserialize(&mut deserializer)?;
+    deserializer.end()?;
+    Ok(envelope)
+}
+
+fn read_json_lines(path: &Path, rows: &mut RowSample) -> Result<()> {
+    let reader = BufReader::new(File::open(path)?);
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str::<Value>(&line) {
+            Ok(value) => rows.push_value(value),
+            Err(e) => warn!("Skipping malformed line {} of {:?}: {}", i + 1, path, e),
+        }
+    }
+    Ok(())
+}
+
+struct TopLevel<'a> {
+    rows: &'a mut RowSample,
+}
+
+impl<'de> DeserializeSeed<'de> for TopLevel<'_> {
+    type Value = Map<String, Value>;
+
+    fn deserialize<D: de::Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de> Visitor<'de> for TopLevel<'_> {
+    type Value = Map<String, Value>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a JSON array or object")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        while let Some(value) = seq.next_element::<Value>()? {
+            self.rows.push_value(value);
+        }
+        Ok(Map::new())
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut envelope = Map::new();
+        let mut streamed = false;
+        while let Some(key) = map.next_key::<String>()? {
+            match map.next_value_seed(Field { rows: &mut *self.rows })? {
+                Some(value) => {
+                    envelope.insert(key, value);
+                }
+                None => streamed = true,
+            }
+        }
+        if streamed {
+            return Ok(envelope);
+        }
+        // No array of records inside: the object is the one record
+        self.rows.push_value(Value::Object(envelope));
+        Ok(Map::new())
+    }
+}
+
+/// A value of the top-level object. Arrays of objects are streamed into the
+/// sample as records (`None`); anything else is returned as is.
+struct Field<'a> {
+    rows: &'a mut RowSample,
+}
+
+impl<'de> DeserializeSeed<'de> for Field<'_> {
+    type Value = Option<Value>;
+
+    fn deserialize<D: de::Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de> Visitor<'de> for Field<'_> {
+    type Value = Option<Value>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a JSON value")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let Some(first) = seq.next_element::<Value>()? else {
+            return Ok(Some(Value::Array(Vec::new())));
+        };
+        if !first.is_object() {
+            let mut items = vec![first];
+            while let Some(value) = seq.next_element::<Value>()? {
+                items.push(value);
+            }
+            return Ok(Some(Value::Array(items)));
+        }
+        self.rows.push_value(first);
+        while let Some(value) = seq.next_element::<Value>()? {
+            self.rows.push_value(value);
+        }
+        Ok(None)
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Self::Value, A::Error> {
+        Value::deserialize(de::value::MapAccessDeserializer::new(map)).map(Some)
+    }
+
+    fn visit_bool<E: de::Error>(self, value: bool) -> Result<Self::Value, E> {
+        Ok(Some(Value::Bool(value)))
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<Self::Value, E> {
+        Ok(Some(Value::from(value)))
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<Self::Value, E> {
+        Ok(Some(Value::from(value)))
+    }
+
+    fn visit_f64<E: de::Error>(self, value: f64) -> Result<Self::Value, E> {
+        Ok(Some(Value::from(value)))
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<Self::Value, E> {
+        Ok(Some(Value::String(value.to_string())))
+    }
+
+    fn visit_string<E: de::Error>(self, value: String) -> Result<Self::Value, E> {
+        Ok(Some(Value::String(value)))
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Self::Value, E> {
+        Ok(Some(Value::Null))
+    }
+}
+
+/// Nested objects become dotted keys: `{"address": {"city": ..}}` gives
+/// `address.city`.
+fn flatten(prefix: &str, value: Value, record: &mut Record) {
+    match value {
+        Value::Object(object) => {
+            for (key, value) in object {
+                let key = if prefix.is_empty() { key } else { format!("{}.{}", prefix, key) };
+                flatten(&key, value, record);
+            }
+        }
+        Value::Null => {}
+        value => record.push((prefix.to_string(), value)),
+    }
+}
+
+fn render_value(value: &Value) -> Option<String> {
+    let text = match value {
+        Value::Null => return None,
+        Value::String(text) => text.trim().to_string(),
+        Value::Array(items) => items
+            .iter()
+            .filter_map(render_value)
+            .collect::<Vec<_>>()
+            .join(", "),
+        Value::Object(_) => value.to_string(),
+        value => value.to_string(),
+    };
+    (!text.is_empty()).then_some(text)
+}
+
+/// The column whose values date each row. Columns named like dates are tried
+/// first; any column qualifies when most of its values parse as dates.
+fn infer_date_column(columns: &[String], rows: &[(usize, Record)]) -> Option<String> {
+    let hinted = |column: &String| {
+        let lower = column.to_lowercase();
+        DATE_HINTS.iter().any(|hint| lower.contains(hint))
+    };
+    let (named, others): (Vec<&String>, Vec<&String>) = columns.iter().partition(|column| hinted(column));
+
+    named
+        .into_iter()
+        .map(|column| (column, true))
+        .chain(others.into_iter().map(|column| (column, false)))
+        .find(|(column, hinted)| {
+            let values: Vec<&Value> = rows
+                .iter()
+                .filter_map(|(_, record)| record.iter().find(|(key, _)| key == *column).map(|(_, value)| value))
+                .collect();
+            let parsed = values.iter().filter(|value| parse_date_value(value, *hinted).is_some()).count();
+            parsed > 0 && parsed as f64 >= values.len() as f64 * DATE_COLUMN_MIN_SHARE
+        })
+        .map(|(column, _)| column.clone())
+}
+
+/// Parses a cell as a date. Unix timestamps (seconds or milliseconds) are
+/// only accepted from columns named like dates, since any number would do.
+fn parse_date_value(value: &Value, allow_timestamps: bool) -> Option<DateTime<Utc>> {
+    match value {
+        Value::String(text) => parse_date(text),
+        Value::Number(number) if allow_timestamps => {
+            let seconds = number.as_i64()?;
+            match seconds {
+                1_000_000_000..=9_999_999_999 => DateTime::from_timestamp(seconds, 0),
+                1_000_000_000_000..=9_999_999_999_999 => DateTime::from_timestamp_millis(seconds),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+fn parse_date(text: &str) -> Option<DateTime<Utc>> {
+    let text = text.trim();
+    if text.len() < 6 {
+        return None;
+    }
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(text) {
+        return Some(datetime.with_timezone(&Utc));
+    }
+    if let Ok(datetime) = DateTime::parse_from_rfc2822(text) {
+        return Some(datetime.with_timezone(&Utc));
+    }
+    for format in [
+        "%Y-%m-%d %H:%M:%S%.f",
+        "%Y-%m-%dT%H:%M:%S%.f",
+        "%Y-%m-%d %H:%M",
+        "%Y/%m/%d %H:%M:%S",
+        "%d/%m/%Y %H:%M:%S",
+        "%d/%m/%Y %H:%M",
+        "%m/%d/%Y %H:%M:%S",
+        "%d.%m.%Y %H:%M:%S",
+        "%d.%m.%Y %H:%M",
+    ] {
+        if let Ok(datetime) = NaiveDateTime::parse_from_str(text, format) {
+            return Some(datetime.and_utc());
+        }
+    }
+    // Day-first is tried before month-first; ambiguous dates like 03/04/2024
+    // are read the European way
+    for format in ["%Y-%m-%d", "%Y/%m/%d", "%d.%m.%Y", "%d/%m/%Y", "%m/%d/%Y", "%d-%m-%Y", "%b %d, %Y", "%d %b %Y", "%B %d, %Y", "%d %B %Y"] {
+        if let Ok(date) = NaiveDate::parse_from_str(text, format) {
+            return date.and_hms_opt(0, 0, 0).map(|datetime| datetime.and_utc());
+        }
+    }
+    None
+}
@@ -0,0 +1,433 @@
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::info;
+use walkdir::WalkDir;
+
+use super::markdown::{front_matter_aliases, parse_markdown};
+use super::Extracted;
+
+/// How long a vault's index of notes is trusted before it is rebuilt, so
+/// notes created since are found
+const RESCAN_AFTER: Duration = Duration::from_secs(60);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum VaultKind {
+    Obsidian,
+    Logseq,
+}
+
+impl VaultKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            VaultKind::Obsidian => "obsidian",
+            VaultKind::Logseq => "logseq",
+        }
+    }
+}
+
+/// The nearest folder at or above `path` that is an Obsidian vault (has
+/// `.obsidian/`) or a Logseq graph (has `logseq/config.edn`).
+pub fn detect(path: &Path) -> Option<(PathBuf, VaultKind)> {
+    path.ancestors().find_map(|dir| {
+        if dir.join(".obsidian").is_dir() {
+            Some((dir.to_path_buf(), VaultKind::Obsidian))
+        } else if dir.join("logseq").join("config.edn").is_file() {
+            Some((dir.to_path_buf(), VaultKind::Logseq))
+        } else {
+            None
+        }
+    })
+}
+
+/// Whether `path` is app state rather than a note: Logseq's own `logseq/`
+/// folder holds backups and versions of every page.
+pub fn is_internal(root: &Path, kind: VaultKind, path: &Path) -> bool {
+    let Ok(relative) = path.strip_prefix(root) else {
+        return false;
+    };
+    relative.components().any(|component| match component {
+        Component::Normal(name) => name.to_string_lossy().starts_with('.'),
+        _ => false,
+    }) || (kind == VaultKind::Logseq && relative.starts_with("logseq"))
+}
+
+/// Index of the notes and attachments in one vault, for resolving links.
+pub struct Vault {
+    pub root: PathBuf,
+    pub kind: VaultKind,
+    /// Notes by lowercased page name: the file stem, or for Logseq the
+    /// namespaced name (`project/alpha` for `project___alpha.md`)
+    notes: HashMap<String, Vec<PathBuf>>,
+    /// Notes by lowercased alias
+    aliases: HashMap<String, PathBuf>,
+    /// Attachments by lowercased file name
+    files: HashMap<String, Vec<PathBuf>>,
+    scanned_at: Instant,
+}
+
+impl Vault {
+    fn scan(root: &Path, kind: VaultKind) -> Self {
+        let mut vault = Vault {
+            root: root.to_path_buf(),
+            kind,
+            notes: HashMap::new(),
+            aliases: HashMap::new(),
+            files: HashMap::new(),
+            scanned_at: Instant::now(),
+        };
+
+        let entries = WalkDir::new(root)
+            .into_iter()
+            .filter_entry(|entry| entry.depth() == 0 || !is_internal(root, kind, entry.path()))
+            .filter_map(Result::ok)
+            .filter(|entry| entry.file_type().is_file());
+        for entry in entries {
+            let path = entry.path().to_path_buf();
+            if is_note(&path) {
+                let name = page_name(&path, kind);
+                if let Ok(content) = std::fs::read_to_string(&path) {
+                    for alias in front_matter_aliases(&content).into_iter().chain(logseq_properties(&content).aliases) {
+                        vault.aliases.entry(alias.to_lowercase()).or_insert_with(|| path.clone());
+                    }
+                }
+                vault.notes.entry(name).or_default().push(path);
+            } else {
+                let name = entry.file_name().to_string_lossy().to_lowercase();
+                vault.files.entry(name).or_default().push(path);
+            }
+        }
+
+        info!(
+            "Indexed {} vault at {:?}: {} notes, {} attachments",
+            kind.name(),
+            root,
+            vault.notes.values().map(Vec::len).sum::<usize>(),
+            vault.files.values().map(Vec::len).sum::<usize>()
+        );
+        vault
+    }
+
+    /// The file a `[[target]]` written in `from` points at, resolved the
+    /// way Obsidian does: a note of that name (a path narrows it down),
+    /// then an alias, then an attachment. Among notes sharing a name the
+    /// one next to `from`, else the one with the shortest path, wins.
+    pub fn resolve(&self, target: &str, from: &Path) -> Option<PathBuf> {
+        let target = target.trim().trim_start_matches("./");
+        let lower = target.to_lowercase();
+        let without_extension = lower.strip_suffix(".md").unwrap_or(&lower);
+
+        let key = match self.kind {
+            VaultKind::Logseq => without_extension,
+            VaultKind::Obsidian => without_extension.rsplit('/').next().unwrap_or(without_extension),
+        };
+        let note = self.notes.get(key).and_then(|candidates| {
+            let matching: Vec<&PathBuf> = candidates
+                .iter()
+                .filter(|candidate| self.kind == VaultKind::Logseq || ends_with(candidate, without_extension, true))
+                .collect();
+            self.closest(&matching, from)
+        });
+        if note.is_some() {
+            return note;
+        }
+        if let Some(path) = self.aliases.get(&lower) {
+            return Some(path.clone());
+        }
+
+        let name = lower.rsplit('/').next().unwrap_or(&lower);
+        let files = self.files.get(name)?;
+        let matching: Vec<&PathBuf> = files.iter().filter(|file| ends_with(file, &lower, false)).collect();
+        self.closest(&matching, from)
+    }
+
+    /// Where a note that doesn't exist yet would be created, so links to it
+    /// connect once it is written.
+    pub fn new_note_path(&self, target: &str) -> PathBuf {
+        let target = target.trim();
+        let name = target.strip_suffix(".md").unwrap_or(target);
+        match self.kind {
+            VaultKind::Obsidian => self.root.join(format!("{}.md", name)),
+            VaultKind::Logseq => self.root.join("pages").join(format!("{}.md", name.replace('/', "___"))),
+        }
+    }
+
+    fn closest(&self, candidates: &[&PathBuf], from: &Path) -> Option<PathBuf> {
+        let folder = from.parent();
+        candidates
+            .iter()
+            .find(|candidate| candidate.parent() == folder)
+            .or_else(|| candidates.iter().min_by_key(|candidate| candidate.components().count()))
+            .map(|candidate| candidate.to_path_buf())
+    }
+}
+
+/// Scanned vaults by root, shared by every note read from them.
+#[derive(Default)]
+pub struct Vaults {
+    vaults: Mutex<HashMap<PathBuf, Arc<Vault>>>,
+}
+
+impl Vaults {
+    /// The vault holding `path`, scanned on first use and again once the
+    /// scan is older than `RESCAN_AFTER`. Scans the disk; call from a
+    /// blocking task.
+    pub fn get(&self, path: &Path) -> Option<Arc<Vault>> {
+        let (root, kind) = detect(path)?;
+        let mut vaults = self.vaults.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        match vaults.get(&root) {
+            Some(vault) if vault.scanned_at.elapsed() < RESCAN_AFTER => Some(vault.clone()),
+            _ => {
+                let vault = Arc::new(Vault::scan(&root, kind));
+                vaults.insert(root, vault.clone());
+                Some(vault)
+            }
+        }
+    }
+}
+
+fn is_note(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("md") || extension.eq_ignore_ascii_case("markdown"))
+}
+
+/// Lowercased name a note is linked by. Logseq writes the `/` of
+/// namespaced pages as `___` (older graphs: `%2F`).
+fn page_name(path: &Path, kind: VaultKind) -> String {
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    match kind {
+        VaultKind::Obsidian => stem,
+        VaultKind::Logseq => stem.replace("___", "/").replace("%2f", "/"),
+    }
+}
+
+/// Whether `path` ends with the `/`-separated `suffix`, ignoring case.
+fn ends_with(path: &Path, suffix: &str, strip_extension: bool) -> bool {
+    let path = if strip_extension { path.with_extension("") } else { path.to_path_buf() };
+    let path = path.to_string_lossy().replace('\\', "/").to_lowercase();
+    path == suffix || path.ends_with(&format!("/{}", suffix))
+}
+
+#[derive(Default)]
+struct LogseqProperties {
+    title: Option<String>,
+    tags: Vec<String>,
+    aliases: Vec<String>,
+}
+
+/// Page properties: the `key:: value` lines Logseq puts at the top of a page.
+fn logseq_properties(content: &str) -> LogseqProperties {
+    let mut properties = LogseqProperties::default();
+    for line in content.lines() {
+        let line = line.trim().trim_start_matches("- ");
+        let Some((key, value)) = line.split_once(":: ") else {
+            break;
+        };
+        let values = || {
+            value
+                .split(',')
+                .map(|item| item.trim().trim_start_matches('#').trim_start_matches("[[").trim_end_matches("]]").trim().to_string())
+                .filter(|item| !item.is_empty())
+                .collect::<Vec<_>>()
+        };
+        match key.trim().to_lowercase().as_str() {
+            "title" => properties.title = Some(value.trim().to_string()),
+            "tags" => properties.tags.extend(values()),
+            "alias" | "aliases" => properties.aliases.extend(values()),
+            _ => {}
+        }
+    }
+    properties
+}
+
+/// A `[[link]]` or `![[embed]]` as written, before resolving.
+struct Link {
+    target: String,
+    embed: bool,
+    /// A Markdown link, whose target is a path relative to the note
+    relative: bool,
+}
+
+/// Parses a note inside a vault. Wikilinks are replaced by the text they
+/// show so they read naturally, and recorded with their targets resolved
+/// to paths: `links` for links and `embeds` for `![[embeds]]`. Front matter
+/// and Logseq `tags::`/`alias::` properties are merged with inline
+/// `#tags` into `tags` and `aliases`.
+pub fn parse_note(content: &str, path: &Path, vault: &Vault) -> Extracted {
+    let properties = logseq_properties(content);
+    let (text, written, inline_tags) = rewrite_links(content);
+    let mut extracted = parse_markdown(&text);
+    if extracted.title.is_none() {
+        extracted.title = properties.title;
+    }
+
+    let mut links: Vec<String> = Vec::new();
+    let mut embeds: Vec<String> = Vec::new();
+    for link in written {
+        let relative = link.relative.then(|| relative_to(path, &link.target)).flatten();
+        let resolved = relative
+            .or_else(|| vault.resolve(&link.target, path))
+            .unwrap_or_else(|| vault.new_note_path(&link.target));
+        let resolved = resolved.to_string_lossy().to_string();
+        let list = if link.embed { &mut embeds } else { &mut links };
+        if !list.contains(&resolved) && resolved != path.to_string_lossy() {
+            list.push(resolved);
+        }
+    }
+
+    let metadata = &mut extracted.metadata;
+    let mut tags = string_list(metadata.get("tags"));
+    for tag in properties.tags.into_iter().chain(inline_tags) {
+        if !tags.iter().any(|known| known.eq_ignore_ascii_case(&tag)) {
+            tags.push(tag);
+        }
+    }
+    let mut aliases = string_list(metadata.get("aliases"));
+    aliases.extend(properties.aliases);
+
+    for (key, values) in [("tags", tags), ("aliases", aliases), ("links", links), ("embeds", embeds)] {
+        if !values.is_empty() {
+            metadata.insert(key.to_string(), Value::from(values));
+        }
+    }
+    metadata.insert("vault".to_string(), Value::String(vault.root.to_string_lossy().to_string()));
+    metadata.insert("vault_kind".to_string(), Value::String(vault.kind.name().to_string()));
+    extracted
+}
+
+/// The existing file a relative link in `from` points at, with `..` folded.
+fn relative_to(from: &Path, target: &str) -> Option<PathBuf> {
+    let mut path = from.parent()?.to_path_buf();
+    for component in Path::new(target).components() {
+        match component {
+            Component::ParentDir => {
+                path.pop();
+            }
+            Component::Normal(name) => path.push(name),
+            _ => {}
+        }
+    }
+    path.is_file().then_some(path)
+}
+
+fn string_list(value: Option<&Value>) -> Vec<String> {
+    value
+        .and_then(Value::as_array)
+        .map(|items| items.iter().filter_map(Value::as_str).map(str::to_string).collect())
+        .unwrap_or_default()
+}
+
+/// Replaces wikilinks outside code with their display text and collects
+/// them along with inline `#tags` and relative `[text](note.md)` links.
+fn rewrite_links(content: &str) -> (String, Vec<Link>, Vec<String>) {
+    let mut text = String::with_capacity(content.len());
+    let mut links = Vec::new();
+    let mut tags = Vec::new();
+    let mut fence: Option<&str> = None;
+
+    for line in content.split_inclusive('\n') {
+        let trimmed = line.trim_start();
+        if let Some(marker) = fence {
+            if trimmed.starts_with(marker) {
+                fence = None;
+            }
+            text.push_str(line);
+            continue;
+        }
+        if let Some(marker) = ["```", "~~~"].into_iter().find(|marker| trimmed.starts_with(marker)) {
+            fence = Some(marker);
+            text.push_str(line);
+            continue;
+        }
+
+        // Inline code spans sit between odd and even backticks
+        for (i, span) in line.split('`').enumerate() {
+            if i > 0 {
+                text.push('`');
+            }
+            if i % 2 == 1 {
+                text.push_str(span);
+            } else {
+                rewrite_span(span, &mut text, &mut links, &mut tags);
+            }
+        }
+    }
+    (text, links, tags)
+}
+
+fn rewrite_span(span: &str, text: &mut String, links: &mut Vec<Link>, tags: &mut Vec<String>) {
+    let mut i = 0;
+    while i < span.len() {
+        let rest = &span[i..];
+
+        // Logseq multi-word tags: #[[Project Alpha]]
+        if let Some(inner) = rest.strip_prefix("#[[").and_then(|inner| inner.split_once("]]")) {
+            tags.push(inner.0.trim().to_string());
+            text.push_str(inner.0);
+            i += 3 + inner.0.len() + 2;
+            continue;
+        }
+
+        let embed = rest.starts_with("![[");
+        if let Some((inner, _)) = rest
+            .strip_prefix(if embed { "![[" } else { "[[" })
+            .and_then(|inner| inner.split_once("]]"))
+        {
+            // [[Note#Heading|shown text]], [[Note#^block]]
+            let (target, shown) = inner.split_once('|').unwrap_or((inner, ""));
+            let (note, heading) = target.split_once('#').unwrap_or((target, ""));
+            if !note.trim().is_empty() {
+                links.push(Link {
+                    target: note.trim().to_string(),
+                    embed,
+                    relative: false,
+                });
+            }
+            if !shown.is_empty() {
+                text.push_str(shown);
+            } else if heading.is_empty() || heading.starts_with('^') {
+                text.push_str(note);
+            } else if note.is_empty() {
+                text.push_str(heading);
+            } else {
+                text.push_str(&format!("{} > {}", note, heading));
+            }
+            i += inner.len() + if embed { 5 } else { 4 };
+            continue;
+        }
+
+        // [text](Other%20Note.md): a Markdown link to another note
+        if let Some(target) = rest.strip_prefix("](").and_then(|target| target.split_once(')')).map(|(target, _)| target) {
+            let target = target.split_whitespace().next().unwrap_or("");
+            if !target.contains("://") && is_note(Path::new(target.split('#').next().unwrap_or(""))) {
+                let decoded = target.split('#').next().unwrap_or("").replace("%20", " ");
+                links.push(Link {
+                    target: decoded,
+                    embed: false,
+                    relative: true,
+                });
+            }
+        }
+
+        let c = rest.chars().next().unwrap_or(' ');
+        if c == '#' && span[..i].chars().next_back().is_none_or(char::is_whitespace) {
+            let tag: String = rest[1..]
+                .chars()
+                .take_while(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '/'))
+                .collect();
+            let tag = tag.trim_end_matches('/');
+            // Obsidian tags need something besides digits: #2024 is not a tag
+            if tag.chars().any(|c| !c.is_ascii_digit()) {
+                tags.push(tag.to_string());
+            }
+        }
+        text.push(c);
+        i += c.len_utf8();
+    }
+}
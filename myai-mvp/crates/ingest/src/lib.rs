@@ -1,49 +1,172 @@
 use anyhow::Result;
-use chrono::Utc;
-use std::collections::HashMap;
-use std::path::Path;
-use tracing::{info, warn};
-use types::{AppConfig, Chunk, Document, IngestResult};
+use chrono::{DateTime, Utc};
+use futures::stream::{self, StreamExt};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tracing::{debug, info, warn};
+use types::{AppConfig, Chunk, Document, IngestResult, ParentChunk, PiiAction, PiiKind, PiiReport};
 use uuid::Uuid;
 
+use models::ModelManager;
+use retrieval::HybridIndex;
+
 pub mod chunker;
+pub mod entities;
 pub mod handlers;
+pub mod language;
+pub mod pii;
+pub mod semantic;
+pub mod strategy;
+pub mod walker;
+pub mod watcher;
 
 pub use chunker::Chunker;
-pub use handlers::FileHandler;
+pub use entities::EntityExtractor;
+pub use handlers::{Extracted, FileHandler, Section};
+pub use pii::{PiiMatch, PiiScanner};
+pub use semantic::SemanticChunker;
+pub use strategy::{ChunkingRules, ChunkingStrategy, UnitChunker};
+pub use walker::{DirectoryWalker, WalkOptions};
+pub use watcher::FileWatcher;
+
+const DOCX: &str = "application/vnd.openxmlformats-officedocument.wordprocessingml.document";
+const PPTX: &str = "application/vnd.openxmlformats-officedocument.presentationml.presentation";
+const XLSX: &str = "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet";
+const XLSM: &str = "application/vnd.ms-excel.sheet.macroEnabled.12";
+const XLS: &str = "application/vnd.ms-excel";
+const ODT: &str = "application/vnd.oasis.opendocument.text";
+const ODS: &str = "application/vnd.oasis.opendocument.spreadsheet";
+const NDJSON: &str = "application/x-ndjson";
+const ZIP: &str = "application/zip";
+const TAR: &str = "application/x-tar";
+const TAR_GZ: &str = "application/x-compressed-tar";
+const FIREFOX_PLACES: &str = "application/x-firefox-places";
+const CHROMIUM_HISTORY: &str = "application/x-chromium-history";
+const CHROMIUM_BOOKMARKS: &str = "application/x-chromium-bookmarks";
+const EPUB: &str = "application/epub+zip";
+/// Image formats the OCR model can decode; HEIC needs a codec we don't ship
+const IMAGES: [&str; 6] = ["image/png", "image/jpeg", "image/webp", "image/tiff", "image/gif", "image/bmp"];
+/// Audio formats the speech model can decode; Opus isn't among them
+const AUDIO: [&str; 7] = ["audio/wav", "audio/mpeg", "audio/m4a", "audio/mp4", "audio/aac", "audio/ogg", "audio/flac"];
+
+/// Outcome of ingesting every file under a directory.
+#[derive(Debug, Default)]
+pub struct DirIngestReport {
+    pub ingested: usize,
+    pub unchanged: usize,
+    pub skipped: usize,
+    pub failed: Vec<(PathBuf, String)>,
+    pub chunks: u32,
+    pub took_ms: u64,
+    /// Files PII was found in, with what was found
+    pub pii: Vec<(PathBuf, PiiReport)>,
+}
 
 pub struct IngestPipeline {
     config: AppConfig,
-    chunker: Chunker,
+    index: Arc<HybridIndex>,
+    /// Picks how each file is chunked
+    chunking: ChunkingRules,
+    /// Groups chunks into the parents they link to
+    parents: Chunker,
+    /// Finds PII and picks the privacy policy for each source
+    pii: PiiScanner,
+    /// Finds the entities chunks mention; `None` when turned off
+    entities: Option<EntityExtractor>,
     handlers: HashMap<String, Box<dyn FileHandler>>,
 }
 
 impl IngestPipeline {
-    pub fn new(config: AppConfig) -> Result<Self> {
-        let chunker = Chunker::new(config.ingest.chunk_size, config.ingest.overlap);
-        let mut handlers: HashMap<String, Box<dyn FileHandler>> = HashMap::new();
+    pub fn new(config: AppConfig, index: Arc<HybridIndex>) -> Result<Self> {
+        let embedder = &index.models().embedder;
+        let chunking = ChunkingRules::new(embedder.tokenizer(), &config.ingest, models::EmbeddingModel::MAX_TOKENS)?;
+        // Parents are stored for reading, not embedded, so the model's limit doesn't apply
+        let parents = Chunker::new(embedder.tokenizer(), config.ingest.parent_size, 0, usize::MAX)?;
+        let pii = PiiScanner::new(&config.privacy.pii)?;
+        let entities = config
+            .ingest
+            .entities
+            .enabled
+            .then(|| EntityExtractor::new(index.models().ner.clone(), &config.ingest.entities))
+            .transpose()?;
+        let mut handlers = Self::file_handlers(&config, index.models());
         
-        // Register file handlers
-        handlers.insert("text/plain".to_string(), Box::new(handlers::TextHandler));
-        handlers.insert("text/markdown".to_string(), Box::new(handlers::MarkdownHandler));
-        handlers.insert("application/pdf".to_string(), Box::new(handlers::PdfHandler));
+        // Archive entries go to the same handlers; nested archives are skipped
+        let archive = handlers::ArchiveHandler::new(Self::file_handlers(&config, index.models()), config.privacy.clone());
+        for mime in [ZIP, TAR, TAR_GZ] {
+            handlers.insert(mime.to_string(), Box::new(archive.clone()));
+        }
         
         Ok(Self {
             config,
-            chunker,
+            index,
+            chunking,
+            parents,
+            pii,
+            entities,
             handlers,
         })
     }
     
+    /// Handlers for every supported file type except archives, by MIME type.
+    /// Audio is only supported when the speech model is installed.
+    fn file_handlers(config: &AppConfig, models: &ModelManager) -> HashMap<String, Box<dyn FileHandler>> {
+        let max_file_mb = config.privacy.max_file_mb;
+        let mut handlers: HashMap<String, Box<dyn FileHandler>> = HashMap::new();
+        handlers.insert("text/plain".to_string(), Box::new(handlers::TextHandler));
+        handlers.insert("text/markdown".to_string(), Box::new(handlers::MarkdownHandler::default()));
+        handlers.insert("application/pdf".to_string(), Box::new(handlers::PdfHandler));
+        handlers.insert("text/html".to_string(), Box::new(handlers::HtmlHandler));
+        handlers.insert("application/xhtml+xml".to_string(), Box::new(handlers::HtmlHandler));
+        handlers.insert("multipart/related".to_string(), Box::new(handlers::HtmlHandler));
+        handlers.insert("message/rfc822".to_string(), Box::new(handlers::EmailHandler));
+        handlers.insert("application/mbox".to_string(), Box::new(handlers::MboxHandler));
+        handlers.insert(DOCX.to_string(), Box::new(handlers::DocxHandler::new(max_file_mb)));
+        handlers.insert(ODT.to_string(), Box::new(handlers::OdtHandler::new(max_file_mb)));
+        handlers.insert(PPTX.to_string(), Box::new(handlers::PptxHandler::new(max_file_mb)));
+        handlers.insert(EPUB.to_string(), Box::new(handlers::EpubHandler::new(max_file_mb)));
+        for spreadsheet in [XLSX, XLSM, XLS, ODS] {
+            handlers.insert(spreadsheet.to_string(), Box::new(handlers::SpreadsheetHandler::new(max_file_mb)));
+        }
+        handlers.insert("text/csv".to_string(), Box::new(handlers::CsvHandler));
+        handlers.insert("text/tab-separated-values".to_string(), Box::new(handlers::CsvHandler));
+        handlers.insert("application/json".to_string(), Box::new(handlers::JsonHandler));
+        handlers.insert(NDJSON.to_string(), Box::new(handlers::JsonLinesHandler));
+        handlers.insert("text/calendar".to_string(), Box::new(handlers::CalendarHandler));
+        for vcard in ["text/vcard", "text/x-vcard"] {
+            handlers.insert(vcard.to_string(), Box::new(handlers::VcardHandler));
+        }
+        for browser in [FIREFOX_PLACES, CHROMIUM_HISTORY, CHROMIUM_BOOKMARKS] {
+            handlers.insert(browser.to_string(), Box::new(handlers::BrowserHandler));
+        }
+        let images = handlers::ImageHandler::new(models.ocr.clone());
+        for image in IMAGES {
+            handlers.insert(image.to_string(), Box::new(images.clone()));
+        }
+        if let Some(speech) = &models.speech {
+            let audio = handlers::AudioHandler::new(speech.clone());
+            for format in AUDIO {
+                handlers.insert(format.to_string(), Box::new(audio.clone()));
+            }
+        }
+        for language in handlers::code::LANGUAGES {
+            handlers.insert(language.mime.to_string(), Box::new(handlers::CodeHandler));
+        }
+        handlers
+    }
+    
     pub async fn ingest_path(&self, path: &Path) -> Result<IngestResult> {
         let start_time = std::time::Instant::now();
+        // Documents are keyed by path, so the CLI's relative paths and the
+        // watcher's absolute ones have to name a file the same way
+        let path = &canonical_path(path);
         
         info!("Ingesting file: {:?}", path);
         
         // Determine MIME type
-        let mime_type = mime_guess::from_path(path)
-            .first_or_octet_stream()
-            .to_string();
+        let mime_type = mime_type(path);
         
         // Check if MIME type is allowed
         if !self.is_mime_allowed(&mime_type) {
@@ -61,46 +184,149 @@ impl IngestPipeline {
             ));
         }
         
-        // Create document
-        let doc = Document::new(
-            path.to_string_lossy().to_string(),
-            path.file_name()
-                .unwrap_or_default()
-                .to_string_lossy()
-                .to_string(),
-            "file".to_string(),
-            mime_type.clone(),
-        );
+        // Skip files that haven't changed since they were last ingested
+        let path_str = path.to_string_lossy().to_string();
+        let modified_at: DateTime<Utc> = metadata
+            .modified()
+            .map(DateTime::from)
+            .unwrap_or_else(|_| Utc::now());
+        let existing = self.index.get_documents_by_file(&path_str).await?;
+        
+        if let Some(first) = existing.first() {
+            if first.size == metadata.len() && first.modified_at.timestamp() == modified_at.timestamp() {
+                return Ok(Self::unchanged(first, start_time));
+            }
+        }
+        
+        let content_hash = hash_file(path).await?;
+        
+        if let Some(first) = existing.first() {
+            if first.content_hash == content_hash {
+                // Touched but not edited: record the new mtime so the next
+                // sync can skip the file without hashing it again
+                for doc in &existing {
+                    let mut doc = doc.clone();
+                    doc.modified_at = modified_at;
+                    doc.size = metadata.len();
+                    self.index.add_document(&doc).await?;
+                }
+                return Ok(Self::unchanged(first, start_time));
+            }
+        }
         
         // Extract text content
-        let handler = self.handlers.get(&mime_type)
-            .ok_or_else(|| anyhow::anyhow!("No handler for MIME type: {}", mime_type))?;
+        let extracted = self.extract(path).await?;
+        let doc_paths: Vec<String> = extracted
+            .iter()
+            .map(|extracted| match &extracted.part {
+                Some(part) => Document::part_path(&path_str, part),
+                None => path_str.clone(),
+            })
+            .collect();
         
-        let content = handler.extract_text(path).await?;
+        // Drop documents that are gone from the file, e.g. messages deleted
+        // from an mbox, and keep the ids of the rest
+        let mut existing: HashMap<String, Document> = existing
+            .into_iter()
+            .map(|doc| (doc.path.clone(), doc))
+            .collect();
+        let stale: Vec<String> = existing
+            .keys()
+            .filter(|doc_path| !doc_paths.contains(doc_path))
+            .cloned()
+            .collect();
+        for doc_path in stale {
+            existing.remove(&doc_path);
+            self.index.remove_path(&doc_path).await?;
+        }
         
-        // Generate chunks
-        let chunks = self.chunker.chunk(&content, &doc.id)?;
+        let file_name = path.file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string();
+        let mut doc_ids = Vec::new();
+        let mut total_chunks = 0;
+        let mut skipped = 0;
+        let mut pii = PiiReport::default();
         
-        // Deduplicate chunks
-        let (unique_chunks, skipped) = self.deduplicate_chunks(chunks)?;
+        for (extracted, doc_path) in extracted.iter().zip(doc_paths) {
+            // Create document, keeping the id of an earlier version of the file
+            let mut doc = Document::new(
+                doc_path,
+                extracted.title.clone().unwrap_or_else(|| file_name.clone()),
+                "file".to_string(),
+                mime_type.clone(),
+            );
+            if let Some(previous) = existing.remove(&doc.path) {
+                doc.id = previous.id;
+            }
+            doc.modified_at = modified_at;
+            doc.size = metadata.len();
+            doc.content_hash = content_hash.clone();
+            doc.metadata = extracted.metadata.clone();
+            
+            // Apply the privacy policy, dropping an earlier version of a
+            // document that is now skipped
+            let Some(screened) = self.screen(extracted, &doc, &mut pii) else {
+                self.index.remove_path(&doc.path).await?;
+                continue;
+            };
+            
+            // Generate chunks
+            let (chunks, parents) = self.chunk(&screened, &doc).await?;
+            
+            // Deduplicate chunks
+            let (unique_chunks, duplicates) = self.deduplicate_chunks(chunks)?;
+            
+            // Store document and embedded chunks
+            self.store(&doc, &unique_chunks, &parents, screened.action).await?;
+            
+            if screened.action == PiiAction::NoEmbed {
+                pii.unembedded += unique_chunks.iter().filter(|chunk| chunk.metadata.contains_key("pii")).count() as u32;
+            }
+            total_chunks += unique_chunks.len() as u32;
+            skipped += duplicates;
+            doc_ids.push(doc.id);
+        }
         
         let took_ms = start_time.elapsed().as_millis() as u64;
         
-        info!(
-            "Ingested {} chunks ({} skipped) in {}ms",
-            unique_chunks.len(),
-            skipped,
-            took_ms
-        );
+        if doc_ids.len() > 1 {
+            info!(
+                "Ingested {} documents, {} chunks ({} skipped) in {}ms",
+                doc_ids.len(),
+                total_chunks,
+                skipped,
+                took_ms
+            );
+        } else {
+            info!(
+                "Ingested {} chunks ({} skipped) in {}ms",
+                total_chunks,
+                skipped,
+                took_ms
+            );
+        }
         
         Ok(IngestResult {
-            doc_id: doc.id.clone(),
-            chunks: unique_chunks.len() as u32,
+            doc_id: doc_ids.into_iter().next().unwrap_or_default(),
+            chunks: total_chunks,
             skipped,
+            unchanged: false,
             took_ms,
+            pii,
         })
     }
     
+    /// Every document in a file as its handler reads it, without storing
+    /// anything.
+    pub async fn extract(&self, path: &Path) -> Result<Vec<Extracted>> {
+        let mime_type = mime_type(path);
+        let handler = self.handlers.get(&mime_type)
+            .ok_or_else(|| anyhow::anyhow!("No handler for MIME type: {}", mime_type))?;
+        handler.extract_all(path).await
+    }
+    
     pub async fn ingest_text(&self, text: &str, title: Option<String>, source: Option<String>) -> Result<IngestResult> {
         let start_time = std::time::Instant::now();
         
@@ -114,12 +340,31 @@ impl IngestPipeline {
             "text/plain".to_string(),
         );
         
+        let extracted = Extracted::from_text(text.to_string());
+        let mut pii = PiiReport::default();
+        let Some(screened) = self.screen(&extracted, &doc, &mut pii) else {
+            return Ok(IngestResult {
+                doc_id: String::new(),
+                chunks: 0,
+                skipped: 0,
+                unchanged: false,
+                took_ms: start_time.elapsed().as_millis() as u64,
+                pii,
+            });
+        };
+        
         // Generate chunks
-        let chunks = self.chunker.chunk(text, &doc.id)?;
+        let (chunks, parents) = self.chunk(&screened, &doc).await?;
         
         // Deduplicate chunks
         let (unique_chunks, skipped) = self.deduplicate_chunks(chunks)?;
         
+        // Store document and embedded chunks
+        self.store(&doc, &unique_chunks, &parents, screened.action).await?;
+        
+        if screened.action == PiiAction::NoEmbed {
+            pii.unembedded += unique_chunks.iter().filter(|chunk| chunk.metadata.contains_key("pii")).count() as u32;
+        }
         let took_ms = start_time.elapsed().as_millis() as u64;
         
         info!(
@@ -133,13 +378,264 @@ impl IngestPipeline {
             doc_id: doc.id.clone(),
             chunks: unique_chunks.len() as u32,
             skipped,
+            unchanged: false,
             took_ms,
+            pii,
         })
     }
     
+    /// Removes a deleted file, or everything below a deleted directory.
+    pub async fn remove_path(&self, path: &Path) -> Result<usize> {
+        self.index.remove_path(&canonical_path(path).to_string_lossy()).await
+    }
+    
+    /// Removes documents at or below `root` whose files no longer exist,
+    /// such as ones deleted while nothing was watching.
+    pub async fn remove_missing(&self, root: &Path) -> Result<usize> {
+        let root = canonical_path(root);
+        let mut removed = 0;
+        
+        for path in self.index.get_file_paths_under(&root.to_string_lossy()).await? {
+            if !Path::new(&path).exists() {
+                removed += self.index.remove_path(&path).await?;
+            }
+        }
+        
+        Ok(removed)
+    }
+    
+    /// Moves indexed documents to a new path, keeping their chunks and embeddings.
+    pub async fn rename_path(&self, from: &Path, to: &Path) -> Result<usize> {
+        self.index
+            .rename_path(&canonical_path(from).to_string_lossy(), &canonical_path(to).to_string_lossy())
+            .await
+    }
+    
+    /// Recursively ingests a directory, processing up to `workers` files at a time.
+    pub async fn ingest_dir(&self, root: &Path, options: &WalkOptions, workers: usize) -> Result<DirIngestReport> {
+        let start_time = std::time::Instant::now();
+        
+        info!("Ingesting directory: {:?}", root);
+        if let Some((vault, kind)) = handlers::vault::detect(root) {
+            info!("Directory is in a {} vault at {:?}; resolving links between notes", kind.name(), vault);
+        }
+        
+        let walker = DirectoryWalker::new(root, options)?;
+        let (files, unsupported): (Vec<PathBuf>, Vec<PathBuf>) = walker
+            .walk()
+            .into_iter()
+            .partition(|path| self.supports(path));
+        
+        let mut report = DirIngestReport {
+            skipped: unsupported.len(),
+            ..Default::default()
+        };
+        
+        info!("Found {} files to ingest ({} unsupported)", files.len(), report.skipped);
+        
+        let mut results = stream::iter(files)
+            .map(|path| async move {
+                let result = self.ingest_path(&path).await;
+                (path, result)
+            })
+            .buffer_unordered(workers.max(1));
+        
+        while let Some((path, result)) = results.next().await {
+            match result {
+                Ok(result) if result.unchanged => report.unchanged += 1,
+                Ok(result) => {
+                    report.ingested += 1;
+                    report.chunks += result.chunks;
+                    if !result.pii.is_empty() {
+                        report.pii.push((path, result.pii));
+                    }
+                }
+                Err(e) => {
+                    warn!("Failed to ingest {:?}: {}", path, e);
+                    report.failed.push((path, e.to_string()));
+                }
+            }
+        }
+        
+        report.took_ms = start_time.elapsed().as_millis() as u64;
+        Ok(report)
+    }
+    
+    /// Whether a file has an allowed MIME type with a registered handler.
+    pub fn supports(&self, path: &Path) -> bool {
+        let mime_type = mime_type(path);
+        self.is_mime_allowed(&mime_type) && self.handlers.contains_key(&mime_type) && !is_vault_internal(path, &mime_type)
+    }
+    
+    /// Scans a document for PII and applies the privacy policy for its
+    /// source, adding what was found to `report`. Returns `None` for a
+    /// document to leave out, else the text to chunk, masked if the policy
+    /// says so.
+    fn screen<'a>(&self, extracted: &'a Extracted, doc: &Document, report: &mut PiiReport) -> Option<Screened<'a>> {
+        let action = self.pii.action_for(&doc.path, &doc.mime);
+        let mut screened = Screened {
+            extracted: Cow::Borrowed(extracted),
+            matches: vec![Vec::new(); extracted.sections.len()],
+            action,
+        };
+        if action == PiiAction::Off {
+            return Some(screened);
+        }
+        
+        let mut matches: Vec<Vec<PiiMatch>> = extracted.sections.iter().map(|section| self.pii.scan(&section.text)).collect();
+        let mut found: BTreeMap<PiiKind, u32> = BTreeMap::new();
+        for pii in matches.iter().flatten() {
+            *found.entry(pii.kind).or_default() += 1;
+        }
+        if found.is_empty() {
+            return Some(screened);
+        }
+        
+        let summary = found
+            .iter()
+            .map(|(kind, count)| format!("{} {}", count, kind.name()))
+            .collect::<Vec<_>>()
+            .join(", ");
+        for (kind, count) in found {
+            *report.found.entry(kind.name().to_string()).or_default() += count;
+        }
+        report.documents += 1;
+        
+        match action {
+            PiiAction::Skip => {
+                warn!("Skipping {}: found {}", doc.path, summary);
+                report.skipped += 1;
+                return None;
+            }
+            PiiAction::Mask => {
+                let mut masked = extracted.clone();
+                for (section, matches) in masked.sections.iter_mut().zip(&mut matches) {
+                    let (text, placeholders) = pii::mask(&section.text, matches);
+                    section.text = text;
+                    *matches = placeholders;
+                }
+                screened.extracted = Cow::Owned(masked);
+            }
+            _ => {}
+        }
+        info!("Found {} in {} ({:?})", summary, doc.path, action);
+        
+        screened.matches = matches;
+        Some(screened)
+    }
+    
+    /// Chunks a document with the strategy configured for it, and groups the
+    /// chunks into the parent sections they link to. Chunks are tagged with
+    /// the language they're written in, unless their handler already knows it
+    /// (source code, transcripts), so each is indexed with that language's
+    /// stemming and stop words, with the kinds of PII they hold as `pii`, and
+    /// with the entities they mention, except in source code.
+    async fn chunk(&self, screened: &Screened<'_>, doc: &Document) -> Result<(Vec<Chunk>, Vec<ParentChunk>)> {
+        let extracted = screened.extracted.as_ref();
+        let strategy = self.chunking.strategy_for(&doc.path, &doc.mime);
+        let embedder = &self.index.models().embedder;
+        let mut pieces = Vec::new();
+        // Kinds of PII in each piece, in chunk order
+        let mut found = Vec::new();
+        for (section, matches) in extracted.sections.iter().zip(&screened.matches) {
+            let section_pieces = strategy.split(&section.text, embedder).await?;
+            for piece in &section_pieces {
+                let start = chunker::offset(&section.text, piece);
+                let end = start + piece.len();
+                let kinds: BTreeSet<PiiKind> = matches
+                    .iter()
+                    .filter(|pii| pii.range.start < end && pii.range.end > start)
+                    .map(|pii| pii.kind)
+                    .collect();
+                found.push(kinds);
+            }
+            pieces.push(section_pieces);
+        }
+        
+        let (mut chunks, parents) = Chunker::assemble(extracted, doc, pieces, Some(&self.parents))?;
+        for (chunk, kinds) in chunks.iter_mut().zip(found) {
+            if !chunk.metadata.contains_key("language") {
+                if let Some(language) = language::detect(&chunk.text) {
+                    chunk.metadata.insert("language".to_string(), serde_json::Value::String(language.to_string()));
+                }
+            }
+            if !kinds.is_empty() {
+                let kinds = kinds.into_iter().map(|kind| serde_json::Value::String(kind.name().to_string()));
+                chunk.metadata.insert("pii".to_string(), serde_json::Value::Array(kinds.collect()));
+            }
+        }
+        
+        let is_code = handlers::code::LANGUAGES.iter().any(|language| language.mime == doc.mime);
+        if let Some(entities) = self.entities.as_ref().filter(|_| !is_code) {
+            entities.annotate(&mut chunks).await?;
+        }
+        Ok((chunks, parents))
+    }
+    
+    /// Saves a document with its parent sections and brings its stored chunks
+    /// in line with `chunks`. Chunks that already exist with the same text and
+    /// metadata are kept as they are, so an edit to one section doesn't
+    /// re-embed the whole file. Under a `no-embed` policy, new chunks with
+    /// PII are indexed for keyword search only.
+    async fn store(&self, doc: &Document, chunks: &[Chunk], parents: &[ParentChunk], action: PiiAction) -> Result<()> {
+        self.index.add_document(doc).await?;
+        self.index.save_parents(&doc.id, parents).await?;
+        
+        let mut stale: HashMap<String, Vec<Chunk>> = HashMap::new();
+        for chunk in self.index.get_chunks_by_doc(&doc.id).await? {
+            stale.entry(blake3::hash(chunk.text.as_bytes()).to_string())
+                .or_default()
+                .push(chunk);
+        }
+        
+        let mut reused = 0;
+        for chunk in chunks {
+            let hash = blake3::hash(chunk.text.as_bytes()).to_string();
+            let kept = stale.get_mut(&hash).and_then(|candidates| {
+                let pos = candidates.iter().position(|old| old.metadata == chunk.metadata)?;
+                Some(candidates.swap_remove(pos))
+            });
+            
+            match kept {
+                Some(old) => {
+                    // Text around it may have moved the chunk
+                    if old.parent_id != chunk.parent_id || old.position != chunk.position {
+                        self.index.place_chunk(&old.id, chunk.parent_id.as_deref(), chunk.position).await?;
+                    }
+                    reused += 1;
+                }
+                None if action == PiiAction::NoEmbed && chunk.metadata.contains_key("pii") => {
+                    self.index.add_chunk_unembedded(chunk).await?
+                }
+                None => self.index.add_chunk(chunk).await?,
+            }
+        }
+        
+        let stale_ids: Vec<String> = stale.into_values().flatten().map(|chunk| chunk.id).collect();
+        self.index.remove_chunks(&stale_ids).await?;
+        
+        if reused > 0 || !stale_ids.is_empty() {
+            info!("Reused {} chunks, removed {} stale chunks for {}", reused, stale_ids.len(), doc.path);
+        }
+        
+        Ok(())
+    }
+    
+    fn unchanged(doc: &Document, start_time: std::time::Instant) -> IngestResult {
+        debug!("Unchanged, skipping: {}", doc.path);
+        IngestResult {
+            doc_id: doc.id.clone(),
+            chunks: 0,
+            skipped: 0,
+            unchanged: true,
+            took_ms: start_time.elapsed().as_millis() as u64,
+            pii: PiiReport::default(),
+        }
+    }
+    
     fn is_mime_allowed(&self, mime_type: &str) -> bool {
-        let mime_group = mime_type.split('/').next().unwrap_or("");
-        self.config.privacy.allowed_mime_groups.contains(&mime_group.to_string())
+        let mime_group = mime_group(mime_type);
+        self.config.privacy.allowed_mime_groups.iter().any(|group| group == mime_group)
     }
     
     fn deduplicate_chunks(&self, chunks: Vec<Chunk>) -> Result<(Vec<Chunk>, u32)> {
@@ -159,3 +655,116 @@ impl IngestPipeline {
         Ok((unique_chunks, skipped))
     }
 }
+
+/// A document as the privacy policy leaves it for chunking.
+struct Screened<'a> {
+    /// The document, with PII masked under a `mask` policy
+    extracted: Cow<'a, Extracted>,
+    /// PII in each section of `extracted`
+    matches: Vec<Vec<PiiMatch>>,
+    action: PiiAction,
+}
+
+/// The absolute path of a file with symlinks resolved, as documents are
+/// stored under. A path that no longer exists (a deleted file, the old name
+/// of a moved one) is resolved through its nearest existing ancestor.
+pub fn canonical_path(path: &Path) -> PathBuf {
+    if let Ok(canonical) = std::fs::canonicalize(path) {
+        return canonical;
+    }
+    match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) if !parent.as_os_str().is_empty() => canonical_path(parent).join(name),
+        _ => std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf()),
+    }
+}
+
+/// MIME type of a file, from its name.
+pub fn mime_type(path: &Path) -> String {
+    // mime_guess files saved web pages under message/rfc822 like email
+    let extension = path.extension().and_then(|ext| ext.to_str()).unwrap_or("");
+    if extension.eq_ignore_ascii_case("mhtml") || extension.eq_ignore_ascii_case("mht") {
+        return "multipart/related".to_string();
+    }
+    if extension.eq_ignore_ascii_case("jsonl") || extension.eq_ignore_ascii_case("ndjson") {
+        return NDJSON.to_string();
+    }
+    // Browser profiles name their databases without a telling extension;
+    // Chromium's names are common enough that the content has to agree
+    match path.file_name().and_then(|name| name.to_str()) {
+        Some("places.sqlite") => return FIREFOX_PLACES.to_string(),
+        Some("History") if is_chromium_file(path, is_sqlite) => return CHROMIUM_HISTORY.to_string(),
+        Some("Bookmarks") if is_chromium_file(path, is_bookmarks_json) => return CHROMIUM_BOOKMARKS.to_string(),
+        _ => {}
+    }
+    // mime_guess only sees the .gz of a .tar.gz
+    let name = path.file_name().map(|name| name.to_string_lossy().to_lowercase()).unwrap_or_default();
+    if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+        return TAR_GZ.to_string();
+    }
+    // Source files get one MIME type per language, whatever mime_guess says
+    if let Some(language) = handlers::code::detect_language(path) {
+        return language.mime.to_string();
+    }
+
+    mime_guess::from_path(path)
+        .first_or_octet_stream()
+        .to_string()
+}
+
+/// Whether a file named like one of Chromium's is one: by its first bytes
+/// when it can be read, otherwise (an archive entry's name) by sitting in a
+/// profile directory such as `Default/` or `Profile 2/`.
+fn is_chromium_file(path: &Path, looks_right: fn(&[u8]) -> bool) -> bool {
+    use std::io::Read;
+    
+    if let Ok(file) = std::fs::File::open(path) {
+        let mut head = Vec::new();
+        return file.take(4096).read_to_end(&mut head).is_ok() && looks_right(&head);
+    }
+    path.parent()
+        .and_then(|parent| parent.file_name())
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name == "Default" || name.starts_with("Profile "))
+}
+
+fn is_sqlite(head: &[u8]) -> bool {
+    head.starts_with(b"SQLite format 3\0")
+}
+
+/// Chromium's bookmarks file: a JSON object with its folders under `roots`.
+fn is_bookmarks_json(head: &[u8]) -> bool {
+    head.trim_ascii_start().starts_with(b"{") && head.windows(7).any(|window| window == b"\"roots\"")
+}
+
+/// Maps a MIME type to the group name used by `allowedMimeGroups`.
+pub fn mime_group(mime_type: &str) -> &str {
+    match mime_type {
+        "application/pdf" => "pdf",
+        "text/html" | "application/xhtml+xml" | "multipart/related" => "html",
+        "message/rfc822" | "application/mbox" => "email",
+        DOCX | ODT | PPTX | XLSX | XLSM | XLS | ODS => "office",
+        "application/json" | NDJSON => "text",
+        ZIP | TAR | TAR_GZ => "archive",
+        FIREFOX_PLACES | CHROMIUM_HISTORY | CHROMIUM_BOOKMARKS => "browser",
+        EPUB => "ebook",
+        _ => mime_type.split('/').next().unwrap_or(""),
+    }
+}
+
+/// Whether a Markdown file is a vault app's own copy rather than a note,
+/// like the page backups Logseq keeps under `logseq/`.
+fn is_vault_internal(path: &Path, mime_type: &str) -> bool {
+    mime_type == "text/markdown"
+        && handlers::vault::detect(path).is_some_and(|(root, kind)| handlers::vault::is_internal(&root, kind, path))
+}
+
+/// Blake3 hash of a file's contents, read in a blocking task.
+async fn hash_file(path: &Path) -> Result<String> {
+    let path = path.to_path_buf();
+    tokio::task::spawn_blocking(move || {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update_reader(std::fs::File::open(&path)?)?;
+        Ok(hasher.finalize().to_hex().to_string())
+    })
+    .await?
+}
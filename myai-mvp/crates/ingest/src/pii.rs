@@ -0,0 +1,220 @@
+use anyhow::Result;
+use regex::Regex;
+use std::collections::BTreeMap;
+use std::ops::Range;
+use types::{PiiAction, PiiConfig, PiiKind};
+
+use crate::strategy::SourcePatterns;
+
+/// Finds personal data and secrets in text, and picks the privacy policy for
+/// each source.
+pub struct PiiScanner {
+    /// In order of precedence where matches overlap
+    detectors: Vec<Detector>,
+    rules: Vec<(SourcePatterns, PiiAction)>,
+    default_action: PiiAction,
+}
+
+/// One piece of PII, as a byte range of the scanned text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PiiMatch {
+    pub kind: PiiKind,
+    pub range: Range<usize>,
+}
+
+struct Detector {
+    kind: PiiKind,
+    regex: Regex,
+    /// Capture group holding the PII itself, e.g. the value of `token = ...`
+    group: usize,
+    valid: Check,
+}
+
+impl PiiScanner {
+    pub fn new(config: &PiiConfig) -> Result<Self> {
+        let kinds = if config.kinds.is_empty() {
+            PiiKind::ALL.to_vec()
+        } else {
+            config.kinds.clone()
+        };
+        
+        let mut detectors = Vec::new();
+        for (kind, pattern, group, valid) in DETECTORS {
+            if kinds.contains(kind) {
+                detectors.push(Detector {
+                    kind: *kind,
+                    regex: Regex::new(pattern)?,
+                    group: *group,
+                    valid: *valid,
+                });
+            }
+        }
+        
+        let mut rules = Vec::new();
+        for rule in &config.rules {
+            rules.push((SourcePatterns::new(&rule.patterns)?, rule.action));
+        }
+        
+        Ok(Self {
+            detectors,
+            rules,
+            default_action: config.action,
+        })
+    }
+    
+    /// The policy for the document at `path` with this MIME type.
+    pub fn action_for(&self, path: &str, mime_type: &str) -> PiiAction {
+        self.rules
+            .iter()
+            .find(|(sources, _)| sources.matches(path, mime_type))
+            .map_or(self.default_action, |(_, action)| *action)
+    }
+    
+    /// Every piece of PII in `text`, in order. Where two matches overlap, the
+    /// kind listed first in `DETECTORS` wins, so a card number isn't also
+    /// read as a phone number.
+    pub fn scan(&self, text: &str) -> Vec<PiiMatch> {
+        // Accepted matches by start; they never overlap
+        let mut found: BTreeMap<usize, PiiMatch> = BTreeMap::new();
+        for detector in &self.detectors {
+            for captures in detector.regex.captures_iter(text) {
+                let Some(capture) = captures.get(detector.group) else {
+                    continue;
+                };
+                if !(detector.valid)(capture.as_str()) {
+                    continue;
+                }
+                let range = capture.range();
+                let overlaps = found
+                    .range(..range.end)
+                    .next_back()
+                    .is_some_and(|(_, other)| other.range.end > range.start);
+                if !overlaps {
+                    found.insert(range.start, PiiMatch { kind: detector.kind, range });
+                }
+            }
+        }
+        found.into_values().collect()
+    }
+}
+
+/// `text` with each match replaced by its kind, e.g. `[EMAIL]`, and the
+/// ranges of those placeholders in the new text.
+pub fn mask(text: &str, matches: &[PiiMatch]) -> (String, Vec<PiiMatch>) {
+    let mut masked = String::with_capacity(text.len());
+    let mut placed = Vec::with_capacity(matches.len());
+    let mut copied = 0;
+    for found in matches {
+        masked.push_str(&text[copied..found.range.start]);
+        let start = masked.len();
+        masked.push_str(&format!("[{}]", found.kind.name().to_uppercase()));
+        placed.push(PiiMatch {
+            kind: found.kind,
+            range: start..masked.len(),
+        });
+        copied = found.range.end;
+    }
+    masked.push_str(&text[copied..]);
+    (masked, placed)
+}
+
+/// Tells PII from text that only looks like it.
+type Check = fn(&str) -> bool;
+
+/// Kind, pattern, capture group and check of each detector, by precedence.
+const DETECTORS: &[(PiiKind, &str, usize, Check)] = &[
+    (
+        PiiKind::PrivateKey,
+        r"(?s)-----BEGIN [A-Z0-9 ]*PRIVATE KEY(?: BLOCK)?-----.*?(?:-----END [A-Z0-9 ]*PRIVATE KEY(?: BLOCK)?-----|\z)",
+        0,
+        any,
+    ),
+    // AWS, GitHub, Slack, OpenAI/Anthropic, Google, Stripe and GitLab tokens
+    (
+        PiiKind::ApiKey,
+        r"\b(?:AKIA[0-9A-Z]{16}|gh[pousr]_[A-Za-z0-9]{36,255}|github_pat_[A-Za-z0-9_]{22,255}|xox[abopsr]-[A-Za-z0-9-]{10,}|sk-[A-Za-z0-9_-]{20,}|AIza[0-9A-Za-z_-]{35}|[rs]k_(?:live|test)_[0-9A-Za-z]{16,}|glpat-[0-9A-Za-z_-]{20})\b",
+        0,
+        any,
+    ),
+    (
+        PiiKind::ApiKey,
+        r#"(?i)\b(?:api[_-]?key|access[_-]?key|secret(?:[_-]?key)?|auth[_-]?token|token|password|passwd)["']?\s*[:=]\s*["']?([A-Za-z0-9_\-./+=]{12,})"#,
+        1,
+        looks_random,
+    ),
+    (PiiKind::CreditCard, r"\b[2-6](?:[ -]?\d){12,18}\b", 0, is_card_number),
+    // US Social Security numbers
+    (PiiKind::NationalId, r"\b\d{3}-\d{2}-\d{4}\b", 0, is_ssn),
+    // UK National Insurance numbers
+    (
+        PiiKind::NationalId,
+        r"\b[A-CEGHJ-PR-TW-Z][A-CEGHJ-NPR-TW-Z] ?\d{2} ?\d{2} ?\d{2} ?[A-D]\b",
+        0,
+        is_nino,
+    ),
+    (
+        PiiKind::Email,
+        r"(?i)\b[a-z0-9._%+-]+@[a-z0-9-]+(?:\.[a-z0-9-]+)*\.[a-z]{2,}\b",
+        0,
+        any,
+    ),
+    // International numbers, then North American ones written `(555) 123-4567`
+    (
+        PiiKind::Phone,
+        r"\+\d{1,3}[ .-]?(?:\(\d{1,4}\)[ .-]?)?\d{1,4}(?:[ .-]?\d{2,4}){1,4}\b",
+        0,
+        is_phone,
+    ),
+    (PiiKind::Phone, r"(?:\(\d{3}\) ?|\b\d{3}[.-])\d{3}[.-]\d{4}\b", 0, is_phone),
+];
+
+fn any(_: &str) -> bool {
+    true
+}
+
+fn digits(text: &str) -> Vec<u32> {
+    text.chars().filter_map(|c| c.to_digit(10)).collect()
+}
+
+/// 13 to 19 digits passing the Luhn check.
+fn is_card_number(text: &str) -> bool {
+    let digits = digits(text);
+    if !(13..=19).contains(&digits.len()) {
+        return false;
+    }
+    let sum: u32 = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(i, &digit)| match (i % 2, digit * 2) {
+            (0, _) => digit,
+            (_, doubled) if doubled > 9 => doubled - 9,
+            (_, doubled) => doubled,
+        })
+        .sum();
+    sum.is_multiple_of(10)
+}
+
+/// Rules out area numbers 000, 666 and 900-999, and all-zero groups.
+fn is_ssn(text: &str) -> bool {
+    let mut parts = text.split('-');
+    let (Some(area), Some(group), Some(serial)) = (parts.next(), parts.next(), parts.next()) else {
+        return false;
+    };
+    area != "000" && area != "666" && !area.starts_with('9') && group != "00" && serial != "0000"
+}
+
+/// Rules out prefixes that are never issued.
+fn is_nino(text: &str) -> bool {
+    !["BG", "GB", "KN", "NK", "NT", "TN", "ZZ"].contains(&&text[..2])
+}
+
+fn is_phone(text: &str) -> bool {
+    (10..=15).contains(&digits(text).len())
+}
+
+/// Both letters and digits, so `password = correcthorse` in prose or
+/// `token = process.env.TOKEN` in code don't count.
+fn looks_random(text: &str) -> bool {
+    text.chars().any(|c| c.is_ascii_digit()) && text.chars().any(|c| c.is_ascii_alphabetic())
+}
@@ -0,0 +1,210 @@
+use anyhow::Result;
+use models::{Embedder, EmbeddingModel};
+use tokenizers::Tokenizer;
+use types::{Chunk, Document, SemanticChunkingConfig};
+
+use crate::chunker::Chunker;
+use crate::handlers::Extracted;
+
+/// Sentences embedded per model call
+const BATCH_SIZE: usize = 32;
+
+/// Chunks text by topic. Each section is split into sentences and the
+/// sentences are embedded; a chunk ends at a gap where the sentences on
+/// either side are further apart than the section's `breakpointPercentile`
+/// of such distances, once it has `minTokens`. No chunk grows past
+/// `maxTokens`, and a sentence longer than that is cut like any other text.
+pub struct SemanticChunker {
+    /// Measures sentences and cuts the ones too long for a chunk
+    chunker: Chunker,
+    min_tokens: usize,
+    max_tokens: usize,
+    percentile: f32,
+}
+
+/// A sentence as a byte range of its section, with its length in tokens.
+#[derive(Clone, Copy)]
+struct Sentence {
+    start: usize,
+    end: usize,
+    tokens: usize,
+}
+
+impl SemanticChunker {
+    /// `max_tokens` is the embedding model's input limit, which caps the
+    /// configured `maxTokens`.
+    pub fn new(tokenizer: &Tokenizer, config: &SemanticChunkingConfig, max_tokens: usize) -> Result<Self> {
+        let chunker = Chunker::new(tokenizer, config.max_tokens, 0, max_tokens)?;
+        let max_tokens = chunker.chunk_size();
+        Ok(Self {
+            chunker,
+            min_tokens: config.min_tokens.min(max_tokens),
+            max_tokens,
+            percentile: config.breakpoint_percentile.clamp(0.0, 100.0),
+        })
+    }
+
+    /// Chunks each section separately, like `Chunker::chunk_document`.
+    pub async fn chunk_document(&self, extracted: &Extracted, doc: &Document, embedder: &EmbeddingModel) -> Result<Vec<Chunk>> {
+        let mut pieces = Vec::new();
+        for section in &extracted.sections {
+            pieces.push(self.split(&section.text, embedder).await?);
+        }
+        Ok(Chunker::assemble(extracted, doc, pieces, None)?.0)
+    }
+
+    pub(crate) async fn split<'a>(&self, text: &'a str, embedder: &EmbeddingModel) -> Result<Vec<&'a str>> {
+        let sentences = self.sentences(text)?;
+        if sentences.len() < 2 {
+            return Ok(sentences.iter().map(|sentence| &text[sentence.start..sentence.end]).collect());
+        }
+
+        let mut embeddings = Vec::with_capacity(sentences.len());
+        for batch in sentences.chunks(BATCH_SIZE) {
+            let texts: Vec<String> = batch
+                .iter()
+                .map(|sentence| text[sentence.start..sentence.end].to_string())
+                .collect();
+            embeddings.extend(embedder.embed(&texts).await?);
+        }
+        if embeddings.len() != sentences.len() {
+            return Err(anyhow::anyhow!("Got {} embeddings for {} sentences", embeddings.len(), sentences.len()));
+        }
+
+        let distances = distances(&embeddings);
+        let threshold = percentile(&distances, self.percentile);
+        // Where every gap looks the same there is no shift to find
+        let closest = percentile(&distances, 0.0);
+
+        // Group sentences, ending a group at a topic shift or when the next
+        // sentence would make it too long
+        let mut groups = Vec::new();
+        let (mut first, mut tokens) = (0, sentences[0].tokens);
+        for (gap, distance) in distances.iter().enumerate() {
+            let next = sentences[gap + 1].tokens;
+            let shift = *distance >= threshold && *distance > closest && tokens >= self.min_tokens;
+            if shift || tokens + next > self.max_tokens {
+                groups.push((first, gap, tokens));
+                first = gap + 1;
+                tokens = 0;
+            }
+            tokens += next;
+        }
+        // A short last group joins the one before when they fit together
+        match groups.last_mut() {
+            Some(last) if tokens < self.min_tokens && last.2 + tokens <= self.max_tokens => {
+                last.1 = sentences.len() - 1;
+            }
+            _ => groups.push((first, sentences.len() - 1, tokens)),
+        }
+
+        let mut pieces = Vec::new();
+        for (first, last, _) in groups {
+            let piece = &text[sentences[first].start..sentences[last].end];
+            // Token counts of sentences don't always add up exactly
+            if self.chunker.count_tokens(piece)? > self.max_tokens {
+                pieces.extend(self.chunker.split(piece)?);
+            } else {
+                pieces.push(piece);
+            }
+        }
+        Ok(pieces)
+    }
+
+    /// The section's sentences and lines, with any longer than `maxTokens`
+    /// cut to size.
+    fn sentences(&self, text: &str) -> Result<Vec<Sentence>> {
+        let mut sentences = Vec::new();
+        for sentence in split_sentences(text) {
+            let tokens = self.chunker.count_tokens(sentence)?;
+            if tokens <= self.max_tokens {
+                sentences.push(Sentence::new(text, sentence, tokens));
+                continue;
+            }
+            for piece in self.chunker.split(sentence)? {
+                sentences.push(Sentence::new(text, piece, self.chunker.count_tokens(piece)?));
+            }
+        }
+        Ok(sentences)
+    }
+}
+
+impl Sentence {
+    /// `part` is a slice of `text`.
+    fn new(text: &str, part: &str, tokens: usize) -> Self {
+        let start = part.as_ptr() as usize - text.as_ptr() as usize;
+        Self {
+            start,
+            end: start + part.len(),
+            tokens,
+        }
+    }
+}
+
+/// Splits after `.`, `!` or `?` followed by a space, after CJK full stops,
+/// and at line breaks. Sentences are trimmed and empty ones dropped.
+pub(crate) fn split_sentences(text: &str) -> Vec<&str> {
+    let mut sentences = Vec::new();
+    let mut start = 0;
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        let next = chars.peek().map(|(_, next)| *next);
+        let end = match c {
+            '\n' => Some(i),
+            '。' | '！' | '？' => Some(i + c.len_utf8()),
+            '.' | '!' | '?' if next.is_none_or(char::is_whitespace) => Some(i + 1),
+            _ => None,
+        };
+        if let Some(end) = end {
+            sentences.push(&text[start..end]);
+            start = i + c.len_utf8();
+        }
+    }
+    sentences.push(&text[start..]);
+    sentences
+        .into_iter()
+        .map(str::trim)
+        .filter(|sentence| !sentence.is_empty())
+        .collect()
+}
+
+/// Cosine distance across each gap between sentences, comparing the two
+/// sentences before the gap with the two after it to smooth out short ones.
+fn distances(embeddings: &[Vec<f32>]) -> Vec<f32> {
+    (0..embeddings.len() - 1)
+        .map(|gap| {
+            let before = mean(&embeddings[gap.saturating_sub(1)..=gap]);
+            let after = mean(&embeddings[gap + 1..(gap + 3).min(embeddings.len())]);
+            1.0 - cosine(&before, &after)
+        })
+        .collect()
+}
+
+fn mean(vectors: &[Vec<f32>]) -> Vec<f32> {
+    let mut mean = vec![0.0; vectors.first().map_or(0, Vec::len)];
+    for vector in vectors {
+        for (total, value) in mean.iter_mut().zip(vector) {
+            *total += value / vectors.len() as f32;
+        }
+    }
+    mean
+}
+
+fn cosine(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm = a.iter().map(|x| x * x).sum::<f32>().sqrt() * b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm == 0.0 {
+        0.0
+    } else {
+        dot / norm
+    }
+}
+
+/// The `p`th percentile of `values`, interpolating between the nearest two.
+fn percentile(values: &[f32], p: f32) -> f32 {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f32::total_cmp);
+    let rank = p / 100.0 * (sorted.len() - 1) as f32;
+    let (lower, upper) = (rank.floor() as usize, rank.ceil() as usize);
+    sorted[lower] + (sorted[upper] - sorted[lower]) * (rank - lower as f32)
+}
@@ -0,0 +1,315 @@
+use anyhow::Result;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use models::EmbeddingModel;
+use std::path::Path;
+use tokenizers::Tokenizer;
+use types::{ChunkingRule, ChunkingStrategyKind, IngestConfig};
+
+use crate::chunker::{offset, Chunker};
+use crate::semantic::{split_sentences, SemanticChunker};
+
+/// Cuts the text of one section into chunks.
+#[async_trait::async_trait]
+pub trait ChunkingStrategy: Send + Sync {
+    /// The pieces `text` is cut into, in order. Each is a slice of `text`, so
+    /// chunks can be placed within their parent section.
+    async fn split<'a>(&self, text: &'a str, embedder: &EmbeddingModel) -> Result<Vec<&'a str>>;
+}
+
+#[async_trait::async_trait]
+impl ChunkingStrategy for Chunker {
+    async fn split<'a>(&self, text: &'a str, _embedder: &EmbeddingModel) -> Result<Vec<&'a str>> {
+        Chunker::split(self, text)
+    }
+}
+
+#[async_trait::async_trait]
+impl ChunkingStrategy for SemanticChunker {
+    async fn split<'a>(&self, text: &'a str, embedder: &EmbeddingModel) -> Result<Vec<&'a str>> {
+        SemanticChunker::split(self, text, embedder).await
+    }
+}
+
+/// Keeps whole units of text (sentences, paragraphs, lines...) together,
+/// packing as many consecutive units as fit into each chunk. A unit too long
+/// for a chunk is cut into finer units, and at last into fixed-size pieces.
+pub struct UnitChunker {
+    /// Measures units and cuts the ones no finer unit fits
+    chunker: Chunker,
+    units: Units,
+    /// Most units in one chunk
+    max_units: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Units {
+    /// A Markdown heading and the text below it
+    MarkdownSections,
+    /// Text between blank lines
+    Paragraphs,
+    /// Code between blank lines; too long, it is cut between lines
+    CodeBlocks,
+    Sentences,
+    Lines,
+}
+
+impl UnitChunker {
+    /// `chunk_size` is in tokens of `tokenizer`, capped like `Chunker`'s.
+    pub fn new(tokenizer: &Tokenizer, kind: ChunkingStrategyKind, chunk_size: usize, rows: usize, max_tokens: usize) -> Result<Self> {
+        let (units, max_units) = match kind {
+            ChunkingStrategyKind::Sentence => (Units::Sentences, usize::MAX),
+            ChunkingStrategyKind::Paragraph => (Units::Paragraphs, usize::MAX),
+            ChunkingStrategyKind::Markdown => (Units::MarkdownSections, 1),
+            ChunkingStrategyKind::Code => (Units::CodeBlocks, usize::MAX),
+            ChunkingStrategyKind::Record => (Units::Lines, rows.max(1)),
+            ChunkingStrategyKind::Fixed | ChunkingStrategyKind::Semantic => {
+                return Err(anyhow::anyhow!("{:?} chunking doesn't work by units", kind));
+            }
+        };
+        Ok(Self {
+            chunker: Chunker::new(tokenizer, chunk_size, 0, max_tokens)?,
+            units,
+            max_units,
+        })
+    }
+
+    fn pack<'a>(&self, text: &'a str, units: Units, max_units: usize) -> Result<Vec<&'a str>> {
+        let size = self.chunker.chunk_size();
+        let mut pieces = Vec::new();
+        // Units in the chunk being filled: first, last, tokens, count
+        let mut group: Option<(&str, &str, usize, usize)> = None;
+
+        for unit in units.split(text) {
+            let tokens = self.chunker.count_tokens(unit)?;
+            if tokens > size {
+                pieces.extend(group.take().map(|(first, last, ..)| span(text, first, last)));
+                match units.finer() {
+                    Some(finer) => pieces.extend(self.pack(unit, finer, usize::MAX)?),
+                    None => pieces.extend(self.chunker.split(unit)?),
+                }
+                continue;
+            }
+
+            group = match group {
+                Some((first, _, total, count)) if total + tokens <= size && count < max_units => {
+                    Some((first, unit, total + tokens, count + 1))
+                }
+                full => {
+                    pieces.extend(full.map(|(first, last, ..)| span(text, first, last)));
+                    Some((unit, unit, tokens, 1))
+                }
+            };
+        }
+        pieces.extend(group.map(|(first, last, ..)| span(text, first, last)));
+
+        // Token counts of units don't always add up exactly
+        let mut checked = Vec::with_capacity(pieces.len());
+        for piece in pieces {
+            if self.chunker.count_tokens(piece)? > size {
+                checked.extend(self.chunker.split(piece)?);
+            } else {
+                checked.push(piece);
+            }
+        }
+        Ok(checked)
+    }
+}
+
+#[async_trait::async_trait]
+impl ChunkingStrategy for UnitChunker {
+    async fn split<'a>(&self, text: &'a str, _embedder: &EmbeddingModel) -> Result<Vec<&'a str>> {
+        self.pack(text, self.units, self.max_units)
+    }
+}
+
+impl Units {
+    /// The units of `text`, trimmed, without empty ones.
+    fn split(self, text: &str) -> Vec<&str> {
+        match self {
+            Units::MarkdownSections => markdown_sections(text),
+            Units::Paragraphs | Units::CodeBlocks => paragraphs(text),
+            Units::Sentences => split_sentences(text),
+            Units::Lines => text.lines().map(str::trim).filter(|line| !line.is_empty()).collect(),
+        }
+    }
+
+    /// What a unit too long for a chunk is cut into.
+    fn finer(self) -> Option<Units> {
+        match self {
+            Units::MarkdownSections => Some(Units::Paragraphs),
+            Units::Paragraphs => Some(Units::Sentences),
+            Units::CodeBlocks => Some(Units::Lines),
+            Units::Sentences | Units::Lines => None,
+        }
+    }
+}
+
+/// `text` from the start of `first` to the end of `last`, both slices of it.
+fn span<'a>(text: &'a str, first: &str, last: &str) -> &'a str {
+    &text[offset(text, first)..offset(text, last) + last.len()]
+}
+
+/// Lines of `text` with their byte offsets.
+fn lines(text: &str) -> impl Iterator<Item = (usize, &str)> {
+    text.split_inclusive('\n').scan(0, |start, line| {
+        let item = (*start, line);
+        *start += line.len();
+        Some(item)
+    })
+}
+
+/// Runs of lines between blank lines.
+fn paragraphs(text: &str) -> Vec<&str> {
+    let mut paragraphs = Vec::new();
+    let mut start = None;
+    let mut end = 0;
+    for (offset, line) in lines(text) {
+        if line.trim().is_empty() {
+            paragraphs.extend(start.take().map(|start| text[start..end].trim()));
+        } else {
+            start.get_or_insert(offset);
+            end = offset + line.len();
+        }
+    }
+    paragraphs.extend(start.map(|start| text[start..end].trim()));
+    paragraphs.retain(|paragraph| !paragraph.is_empty());
+    paragraphs
+}
+
+/// Text split before each ATX heading (`## Install`) outside fenced code.
+/// Text without headings is one section.
+fn markdown_sections(text: &str) -> Vec<&str> {
+    let mut sections = Vec::new();
+    let mut start = 0;
+    let mut fence: Option<&str> = None;
+    for (offset, line) in lines(text) {
+        let trimmed = line.trim_start();
+        let marker = ["```", "~~~"].into_iter().find(|marker| trimmed.starts_with(marker));
+        match (fence, marker) {
+            (None, Some(marker)) => fence = Some(marker),
+            (Some(open), Some(marker)) if open == marker => fence = None,
+            _ => {}
+        }
+        if fence.is_none() && marker.is_none() && is_heading(line) && offset > start {
+            sections.push(&text[start..offset]);
+            start = offset;
+        }
+    }
+    sections.push(&text[start..]);
+    sections
+        .into_iter()
+        .map(str::trim)
+        .filter(|section| !section.is_empty())
+        .collect()
+}
+
+/// `#` to `######` after at most three spaces, then a space or the line end.
+fn is_heading(line: &str) -> bool {
+    let indent = line.len() - line.trim_start_matches(' ').len();
+    let rest = &line[indent..];
+    let level = rest.len() - rest.trim_start_matches('#').len();
+    indent <= 3 && (1..=6).contains(&level) && rest[level..].chars().next().is_none_or(char::is_whitespace)
+}
+
+/// Picks the chunking strategy for each file: the first `[[ingest.chunking]]`
+/// rule that matches it, then the `[ingest.semantic]` MIME types, then
+/// fixed-size chunks.
+pub struct ChunkingRules {
+    rules: Vec<Rule>,
+    /// Fixed-size chunks with the global `chunkSize` and `overlap`
+    fallback: Chunker,
+}
+
+struct Rule {
+    sources: SourcePatterns,
+    strategy: Box<dyn ChunkingStrategy>,
+}
+
+/// The `match` list of a config rule: MIME types, MIME groups and globs over
+/// the path and file name.
+pub(crate) struct SourcePatterns {
+    /// Patterns compared with the MIME type and its group
+    patterns: Vec<String>,
+    /// The same patterns as globs over the path and file name
+    globs: GlobSet,
+}
+
+impl ChunkingRules {
+    /// `max_tokens` is the embedding model's input limit, which caps every
+    /// strategy's chunk size.
+    pub fn new(tokenizer: &Tokenizer, config: &IngestConfig, max_tokens: usize) -> Result<Self> {
+        let mut rules = Vec::new();
+        for rule in &config.chunking {
+            let strategy = strategy(tokenizer, rule, config, max_tokens)?;
+            rules.push(Rule {
+                sources: SourcePatterns::new(&rule.patterns)?,
+                strategy,
+            });
+        }
+        if !config.semantic.mime_types.is_empty() {
+            let semantic = SemanticChunker::new(tokenizer, &config.semantic, max_tokens)?;
+            rules.push(Rule {
+                sources: SourcePatterns::new(&config.semantic.mime_types)?,
+                strategy: Box::new(semantic),
+            });
+        }
+
+        Ok(Self {
+            rules,
+            fallback: Chunker::new(tokenizer, config.chunk_size, config.overlap, max_tokens)?,
+        })
+    }
+
+    /// The strategy for the document at `path` (a file, or a document stored
+    /// inside one) with this MIME type.
+    pub fn strategy_for(&self, path: &str, mime_type: &str) -> &dyn ChunkingStrategy {
+        self.rules
+            .iter()
+            .find(|rule| rule.sources.matches(path, mime_type))
+            .map_or(&self.fallback, |rule| rule.strategy.as_ref())
+    }
+}
+
+impl SourcePatterns {
+    pub(crate) fn new(patterns: &[String]) -> Result<Self> {
+        let mut builder = GlobSetBuilder::new();
+        for pattern in patterns {
+            let glob = Glob::new(pattern)
+                .map_err(|e| anyhow::anyhow!("Invalid match pattern {:?}: {}", pattern, e))?;
+            builder.add(glob);
+        }
+        Ok(Self {
+            patterns: patterns.to_vec(),
+            globs: builder.build()?,
+        })
+    }
+
+    /// Whether the document at `path` (a file, or a document stored inside
+    /// one) with this MIME type matches any pattern.
+    pub(crate) fn matches(&self, path: &str, mime_type: &str) -> bool {
+        let group = crate::mime_group(mime_type);
+        let file = path.split(types::PART_SEPARATOR).next().unwrap_or(path);
+        let name = Path::new(file).file_name().map(|name| name.to_string_lossy().to_string());
+        self.patterns.iter().any(|pattern| pattern == mime_type || pattern == group)
+            || self.globs.is_match(file)
+            || name.as_ref().is_some_and(|name| self.globs.is_match(name))
+    }
+}
+
+fn strategy(tokenizer: &Tokenizer, rule: &ChunkingRule, config: &IngestConfig, max_tokens: usize) -> Result<Box<dyn ChunkingStrategy>> {
+    let chunk_size = rule.chunk_size.unwrap_or(config.chunk_size);
+    Ok(match rule.strategy {
+        ChunkingStrategyKind::Fixed => {
+            let overlap = rule.overlap.unwrap_or(config.overlap);
+            Box::new(Chunker::new(tokenizer, chunk_size, overlap, max_tokens)?)
+        }
+        ChunkingStrategyKind::Semantic => {
+            let mut semantic = config.semantic.clone();
+            if let Some(chunk_size) = rule.chunk_size {
+                semantic.max_tokens = chunk_size;
+            }
+            Box::new(SemanticChunker::new(tokenizer, &semantic, max_tokens)?)
+        }
+        kind => Box::new(UnitChunker::new(tokenizer, kind, chunk_size, rule.rows, max_tokens)?),
+    })
+}
@@ -0,0 +1,209 @@
+use anyhow::Result;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use tracing::{debug, warn};
+use types::IngestConfig;
+use walkdir::WalkDir;
+
+/// Ignore files honored in every directory of the walk, `.gitignore` syntax
+const IGNORE_FILES: [&str; 2] = [".gitignore", ".myaiignore"];
+
+#[derive(Debug, Clone, Default)]
+pub struct WalkOptions {
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+    pub follow_symlinks: bool,
+    pub include_hidden: bool,
+}
+
+impl WalkOptions {
+    pub fn from_config(config: &IngestConfig) -> Self {
+        Self {
+            include: config.include.clone(),
+            exclude: config.exclude.clone(),
+            follow_symlinks: config.follow_symlinks,
+            include_hidden: config.include_hidden,
+        }
+    }
+}
+
+/// Recursively lists the files under a directory that should be ingested.
+pub struct DirectoryWalker {
+    root: PathBuf,
+    include: Option<GlobSet>,
+    exclude: GlobSet,
+    follow_symlinks: bool,
+    include_hidden: bool,
+}
+
+impl DirectoryWalker {
+    pub fn new(root: &Path, options: &WalkOptions) -> Result<Self> {
+        let include = if options.include.is_empty() {
+            None
+        } else {
+            Some(build_globset(&options.include)?)
+        };
+        let exclude = build_globset(&options.exclude)?;
+
+        Ok(Self {
+            root: root.to_path_buf(),
+            include,
+            exclude,
+            follow_symlinks: options.follow_symlinks,
+            include_hidden: options.include_hidden,
+        })
+    }
+
+    pub fn walk(&self) -> Vec<PathBuf> {
+        self.walk_dir(&self.root)
+    }
+
+    /// Walks a subdirectory of the root, applying ignore files found above it.
+    pub fn walk_dir(&self, dir: &Path) -> Vec<PathBuf> {
+        let mut ignores: HashMap<PathBuf, Option<Gitignore>> = HashMap::new();
+        let mut files = Vec::new();
+
+        let walker = WalkDir::new(dir)
+            .follow_links(self.follow_symlinks)
+            .sort_by_file_name()
+            .into_iter()
+            .filter_entry(|entry| {
+                entry.depth() == 0
+                    || self.keep_path(entry.path(), entry.file_type().is_dir(), &mut ignores)
+            });
+
+        for entry in walker {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    warn!("Skipping unreadable entry: {}", e);
+                    continue;
+                }
+            };
+
+            if !entry.file_type().is_file() {
+                continue;
+            }
+
+            if !self.matches_include(entry.path()) {
+                continue;
+            }
+
+            files.push(entry.into_path());
+        }
+
+        files
+    }
+
+    /// Whether a single path under the root would be picked up by a walk.
+    pub fn is_included(&self, path: &Path) -> bool {
+        let Ok(relative) = path.strip_prefix(&self.root) else {
+            return false;
+        };
+
+        let mut ignores = HashMap::new();
+        let mut current = self.root.clone();
+        for component in relative.components() {
+            current.push(component);
+            let is_dir = current != path || path.is_dir();
+            if !self.keep_path(&current, is_dir, &mut ignores) {
+                return false;
+            }
+        }
+
+        path.is_dir() || self.matches_include(path)
+    }
+
+    fn matches_include(&self, path: &Path) -> bool {
+        match &self.include {
+            Some(include) => include.is_match(self.relative(path)),
+            None => true,
+        }
+    }
+
+    fn keep_path(&self, path: &Path, is_dir: bool, ignores: &mut HashMap<PathBuf, Option<Gitignore>>) -> bool {
+        if !self.include_hidden && is_hidden(path) {
+            return false;
+        }
+
+        if self.exclude.is_match(self.relative(path)) {
+            debug!("Excluded by pattern: {:?}", path);
+            return false;
+        }
+
+        // The closest ignore file with an opinion wins, so `!pattern` in a
+        // subdirectory can re-include something ignored further up.
+        for dir in path.ancestors().skip(1) {
+            if !dir.starts_with(&self.root) {
+                break;
+            }
+
+            let matcher = ignores
+                .entry(dir.to_path_buf())
+                .or_insert_with(|| load_ignore_files(dir));
+
+            if let Some(matcher) = matcher {
+                let matched = matcher.matched(path, is_dir);
+                if matched.is_ignore() {
+                    debug!("Ignored by {:?}: {:?}", matcher.path(), path);
+                    return false;
+                }
+                if matched.is_whitelist() {
+                    return true;
+                }
+            }
+        }
+
+        true
+    }
+
+    fn relative<'a>(&self, path: &'a Path) -> &'a Path {
+        path.strip_prefix(&self.root).unwrap_or(path)
+    }
+}
+
+fn build_globset(patterns: &[String]) -> Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        let glob = Glob::new(pattern)
+            .map_err(|e| anyhow::anyhow!("Invalid glob pattern {:?}: {}", pattern, e))?;
+        builder.add(glob);
+    }
+    Ok(builder.build()?)
+}
+
+fn load_ignore_files(dir: &Path) -> Option<Gitignore> {
+    let mut builder = GitignoreBuilder::new(dir);
+    let mut found = false;
+
+    for name in IGNORE_FILES {
+        let ignore_path = dir.join(name);
+        if ignore_path.is_file() {
+            if let Some(e) = builder.add(&ignore_path) {
+                warn!("Failed to parse {:?}: {}", ignore_path, e);
+            }
+            found = true;
+        }
+    }
+
+    if !found {
+        return None;
+    }
+
+    match builder.build() {
+        Ok(matcher) => Some(matcher),
+        Err(e) => {
+            warn!("Failed to build ignore rules for {:?}: {}", dir, e);
+            None
+        }
+    }
+}
+
+fn is_hidden(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .map(|name| name.starts_with('.'))
+        .unwrap_or(false)
+}
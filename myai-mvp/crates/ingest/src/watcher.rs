@@ -0,0 +1,301 @@
+use anyhow::Result;
+use notify::event::{ModifyKind, RenameMode};
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+use serde_json::json;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{broadcast, mpsc};
+use tracing::{error, info, warn};
+
+use crate::walker::{DirectoryWalker, WalkOptions};
+use crate::IngestPipeline;
+
+/// How often pending changes are checked against the debounce window
+const FLUSH_INTERVAL: Duration = Duration::from_millis(250);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Change {
+    Upsert,
+    Remove,
+}
+
+/// Keeps the index in sync with the configured watch paths.
+///
+/// At startup each watched root is caught up with what changed while the
+/// watcher wasn't running. After that, bursts of create/modify events are
+/// debounced into a single re-ingest, deletes remove the document and
+/// renames only update the stored path. Status and backlog updates are
+/// published as JSON on the progress channel.
+pub struct FileWatcher {
+    pipeline: Arc<IngestPipeline>,
+    roots: Vec<DirectoryWalker>,
+    root_paths: Vec<PathBuf>,
+    options: WalkOptions,
+    workers: usize,
+    debounce: Duration,
+    progress_tx: broadcast::Sender<String>,
+}
+
+impl FileWatcher {
+    pub fn new(
+        pipeline: Arc<IngestPipeline>,
+        paths: Vec<PathBuf>,
+        options: &WalkOptions,
+        workers: usize,
+        debounce: Duration,
+        progress_tx: broadcast::Sender<String>,
+    ) -> Result<Self> {
+        let roots = paths
+            .iter()
+            .map(|path| DirectoryWalker::new(path, options))
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            pipeline,
+            roots,
+            root_paths: paths,
+            options: options.clone(),
+            workers,
+            debounce,
+            progress_tx,
+        })
+    }
+
+    pub async fn run(self) -> Result<()> {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let mut watcher = notify::recommended_watcher(move |res| {
+            let _ = tx.send(res);
+        })?;
+
+        let mut watching = Vec::new();
+        for path in &self.root_paths {
+            match watcher.watch(path, RecursiveMode::Recursive) {
+                Ok(()) => {
+                    info!("Watching {:?}", path);
+                    watching.push(path.as_path());
+                }
+                Err(e) => warn!("Cannot watch {:?}: {}", path, e),
+            }
+        }
+
+        // Events from here on queue up while the roots are caught up. A root
+        // that can't be watched may just be unmounted, so its documents stay.
+        for path in &watching {
+            self.reconcile(path).await;
+        }
+
+        self.publish(json!({
+            "type": "watch",
+            "status": "watching",
+            "paths": watching.iter().map(|path| path.to_string_lossy()).collect::<Vec<_>>(),
+            "backlog": 0,
+        }));
+
+        let mut pending: HashMap<PathBuf, (Change, Instant)> = HashMap::new();
+        let mut ticker = tokio::time::interval(FLUSH_INTERVAL);
+
+        loop {
+            tokio::select! {
+                event = rx.recv() => match event {
+                    Some(Ok(event)) => self.handle_event(event, &mut pending).await,
+                    Some(Err(e)) => warn!("Watch error: {}", e),
+                    None => break,
+                },
+                _ = ticker.tick() => self.flush(&mut pending).await,
+            }
+        }
+
+        self.publish(json!({ "type": "watch", "status": "stopped", "backlog": pending.len() }));
+        Ok(())
+    }
+
+    /// Ingests what was added or changed below `root` and removes documents
+    /// whose files are gone.
+    async fn reconcile(&self, root: &Path) {
+        self.publish(json!({ "type": "watch", "status": "reconciling", "path": root.to_string_lossy() }));
+
+        match self.pipeline.ingest_dir(root, &self.options, self.workers).await {
+            Ok(report) => info!(
+                "Caught up on {:?}: {} ingested, {} unchanged, {} failed",
+                root,
+                report.ingested,
+                report.unchanged,
+                report.failed.len()
+            ),
+            Err(e) => error!("Failed to catch up on {:?}: {}", root, e),
+        }
+
+        match self.pipeline.remove_missing(root).await {
+            Ok(0) => {}
+            Ok(removed) => {
+                info!("Removed {} documents for files gone from {:?}", removed, root);
+                self.publish_change("removed", root);
+            }
+            Err(e) => error!("Failed to remove missing files under {:?}: {}", root, e),
+        }
+    }
+
+    async fn handle_event(&self, event: Event, pending: &mut HashMap<PathBuf, (Change, Instant)>) {
+        let backlog = pending.len();
+        let now = Instant::now();
+
+        match event.kind {
+            EventKind::Create(_) | EventKind::Modify(ModifyKind::Data(_)) | EventKind::Modify(ModifyKind::Any) => {
+                for path in event.paths {
+                    pending.insert(path, (Change::Upsert, now));
+                }
+            }
+            EventKind::Remove(_) | EventKind::Modify(ModifyKind::Name(RenameMode::From)) => {
+                for path in event.paths {
+                    pending.insert(path, (Change::Remove, now));
+                }
+            }
+            EventKind::Modify(ModifyKind::Name(RenameMode::Both)) if event.paths.len() == 2 => {
+                let (from, to) = (&event.paths[0], &event.paths[1]);
+                // Backends that report both halves of a rename separately queue
+                // a remove and an upsert first; the paired event supersedes them.
+                pending.remove(from);
+                pending.remove(to);
+                self.rename(from, to, pending).await;
+            }
+            EventKind::Modify(ModifyKind::Name(_)) => {
+                for path in event.paths {
+                    let change = if path.exists() { Change::Upsert } else { Change::Remove };
+                    pending.insert(path, (change, now));
+                }
+            }
+            _ => {}
+        }
+
+        if pending.len() != backlog {
+            self.publish_backlog(pending.len());
+        }
+    }
+
+    async fn flush(&self, pending: &mut HashMap<PathBuf, (Change, Instant)>) {
+        let ready: Vec<(PathBuf, Change)> = pending
+            .iter()
+            .filter(|(_, (_, seen))| seen.elapsed() >= self.debounce)
+            .map(|(path, (change, _))| (path.clone(), *change))
+            .collect();
+
+        if ready.is_empty() {
+            return;
+        }
+
+        for (path, change) in ready {
+            pending.remove(&path);
+
+            match change {
+                Change::Upsert if path.is_dir() => {
+                    if let Some(walker) = self.walker_for(&path) {
+                        for file in walker.walk_dir(&path) {
+                            self.reingest(&file).await;
+                        }
+                    }
+                }
+                Change::Upsert if path.is_file() => self.reingest(&path).await,
+                // Gone again before the debounce window closed
+                Change::Upsert | Change::Remove => self.remove(&path).await,
+            }
+
+            self.publish_backlog(pending.len());
+        }
+    }
+
+    async fn reingest(&self, path: &Path) {
+        if !self.is_watched(path) {
+            return;
+        }
+
+        match self.pipeline.ingest_path(path).await {
+            Ok(result) if result.unchanged => {}
+            Ok(result) => {
+                info!("Re-ingested {:?} ({} chunks)", path, result.chunks);
+                self.publish_change("updated", path);
+            }
+            Err(e) => {
+                error!("Failed to re-ingest {:?}: {}", path, e);
+                self.publish(json!({
+                    "type": "watch",
+                    "event": "failed",
+                    "path": path.to_string_lossy(),
+                    "error": e.to_string(),
+                }));
+            }
+        }
+    }
+
+    async fn remove(&self, path: &Path) {
+        match self.pipeline.remove_path(path).await {
+            Ok(0) => {}
+            Ok(removed) => {
+                info!("Removed {} documents for {:?}", removed, path);
+                self.publish_change("removed", path);
+            }
+            Err(e) => error!("Failed to remove {:?}: {}", path, e),
+        }
+    }
+
+    async fn rename(&self, from: &Path, to: &Path, pending: &mut HashMap<PathBuf, (Change, Instant)>) {
+        if !self.is_watched(to) {
+            self.remove(from).await;
+            return;
+        }
+
+        match self.pipeline.rename_path(from, to).await {
+            // Nothing was indexed under the old name, e.g. an editor saving
+            // through a temporary file, so treat the destination as new.
+            Ok(0) => {
+                pending.insert(to.to_path_buf(), (Change::Upsert, Instant::now()));
+            }
+            Ok(renamed) => {
+                info!("Renamed {} documents {:?} -> {:?}", renamed, from, to);
+                self.publish(json!({
+                    "type": "watch",
+                    "event": "renamed",
+                    "from": from.to_string_lossy(),
+                    "path": to.to_string_lossy(),
+                }));
+            }
+            Err(e) => error!("Failed to rename {:?} -> {:?}: {}", from, to, e),
+        }
+    }
+
+    /// Whether a path is under a watch root, passes its filters and has a handler.
+    fn is_watched(&self, path: &Path) -> bool {
+        let included = self
+            .walker_for(path)
+            .map(|walker| walker.is_included(path))
+            .unwrap_or(false);
+        included && (path.is_dir() || self.pipeline.supports(path))
+    }
+
+    fn walker_for(&self, path: &Path) -> Option<&DirectoryWalker> {
+        self.root_paths
+            .iter()
+            .zip(&self.roots)
+            .filter(|(root, _)| path.starts_with(root))
+            .max_by_key(|(root, _)| root.components().count())
+            .map(|(_, walker)| walker)
+    }
+
+    fn publish_change(&self, event: &str, path: &Path) {
+        self.publish(json!({
+            "type": "watch",
+            "event": event,
+            "path": path.to_string_lossy(),
+        }));
+    }
+
+    fn publish_backlog(&self, backlog: usize) {
+        self.publish(json!({ "type": "watch", "status": "watching", "backlog": backlog }));
+    }
+
+    fn publish(&self, message: serde_json::Value) {
+        // No subscribers is fine; progress is best-effort
+        let _ = self.progress_tx.send(message.to_string());
+    }
+}
@@ -14,6 +14,10 @@ pub struct EmbeddingModel {
 }
 
 impl EmbeddingModel {
+    /// Longest input in tokens, special tokens included; the model's
+    /// position embeddings end there
+    pub const MAX_TOKENS: usize = 512;
+    
     pub async fn new(model_dir: &str) -> Result<Self> {
         let model_path = Path::new(model_dir).join("all-MiniLM-L6-v2.onnx");
         let tokenizer_path = Path::new(model_dir).join("all-MiniLM-L6-v2-tokenizer.json");
@@ -52,6 +56,11 @@ impl EmbeddingModel {
     pub fn embedding_dim(&self) -> usize {
         self.embedding_dim
     }
+    
+    /// The tokenizer inputs are encoded with, for measuring text in tokens.
+    pub fn tokenizer(&self) -> &Tokenizer {
+        &self.tokenizer
+    }
 }
 
 impl Embedder for EmbeddingModel {
@@ -8,15 +8,28 @@ use tracing::{info, warn};
 use types::AppConfig;
 
 pub mod embedding;
+pub mod ner;
+pub mod ocr;
 pub mod reranker;
+pub mod speech;
 
 pub use embedding::EmbeddingModel;
+pub use ner::{NamedEntity, NerModel};
+pub use ocr::{OcrModel, Recognized, TextLine};
 pub use reranker::RerankerModel;
+pub use speech::{Segment, SpeechModel, Transcript};
 
 #[derive(Debug)]
 pub struct ModelManager {
     pub embedder: EmbeddingModel,
     pub reranker: RerankerModel,
+    /// Reads text in images; `None` when the OCR models aren't installed
+    pub ocr: Option<Arc<OcrModel>>,
+    /// Transcribes audio; `None` when the Whisper model isn't installed
+    pub speech: Option<Arc<SpeechModel>>,
+    /// Finds names of people, organizations and places; `None` when the
+    /// NER model isn't installed
+    pub ner: Option<Arc<NerModel>>,
 }
 
 impl ModelManager {
@@ -25,9 +38,12 @@ impl ModelManager {
         
         let embedder = EmbeddingModel::new(&config.paths.model_dir).await?;
         let reranker = RerankerModel::new(&config.paths.model_dir).await?;
+        let ocr = OcrModel::load(&config.paths.model_dir).await?.map(Arc::new);
+        let speech = SpeechModel::load(&config.paths.model_dir).await?.map(Arc::new);
+        let ner = NerModel::load(&config.paths.model_dir).await?.map(Arc::new);
         
         info!("Model manager initialized successfully");
-        Ok(Self { embedder, reranker })
+        Ok(Self { embedder, reranker, ocr, speech, ner })
     }
 }
 
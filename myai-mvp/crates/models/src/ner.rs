@@ -0,0 +1,191 @@
+use anyhow::Result;
+use ndarray::Array2;
+use ort::{Session, Value};
+use std::path::Path;
+use tokenizers::{Encoding, Tokenizer, TruncationParams};
+use tracing::info;
+
+use crate::create_session;
+
+/// Longest input the model reads, special tokens included; longer text is
+/// read in consecutive windows
+const MAX_TOKENS: usize = 512;
+
+/// A name the model found, as a byte range of the text.
+#[derive(Debug, Clone)]
+pub struct NamedEntity {
+    /// The label without its B-/I- prefix: `PER`, `ORG`, `LOC` or `MISC`
+    pub label: String,
+    pub start: usize,
+    pub end: usize,
+    /// Mean probability of the entity's words
+    pub score: f32,
+}
+
+/// A BERT-style token classifier trained on CoNLL-2003 labels (such as
+/// `dslim/bert-base-NER`) exported to ONNX.
+pub struct NerModel {
+    session: Session,
+    tokenizer: Tokenizer,
+    /// Label of each output class, e.g. `B-PER`
+    labels: Vec<String>,
+    /// Whether the export takes `token_type_ids`
+    token_types: bool,
+}
+
+impl NerModel {
+    /// Loads the NER model if it was downloaded. It's optional, so missing
+    /// files give `None` rather than an error.
+    pub async fn load(model_dir: &str) -> Result<Option<Self>> {
+        let model_path = Path::new(model_dir).join("ner.onnx");
+        let tokenizer_path = Path::new(model_dir).join("ner-tokenizer.json");
+        let labels_path = Path::new(model_dir).join("ner-labels.txt");
+
+        if !model_path.exists() || !tokenizer_path.exists() || !labels_path.exists() {
+            info!("NER model not found in {}; only addresses and handles are extracted as entities", model_dir);
+            return Ok(None);
+        }
+
+        info!("Loading NER model from {:?}", model_path);
+        let session = create_session(&model_path)?;
+        let mut tokenizer = Tokenizer::from_file(tokenizer_path)
+            .map_err(|e| anyhow::anyhow!("Failed to load NER tokenizer: {}", e))?;
+        tokenizer
+            .with_truncation(Some(TruncationParams {
+                max_length: MAX_TOKENS,
+                ..Default::default()
+            }))
+            .map_err(|e| anyhow::anyhow!("Failed to configure NER tokenizer: {}", e))?;
+        tokenizer.with_padding(None);
+        let labels: Vec<String> = std::fs::read_to_string(&labels_path)?
+            .lines()
+            .map(|line| line.trim().to_string())
+            .filter(|line| !line.is_empty())
+            .collect();
+        let token_types = session.inputs.iter().any(|input| input.name == "token_type_ids");
+
+        info!("NER model loaded successfully ({} labels)", labels.len());
+        Ok(Some(Self {
+            session,
+            tokenizer,
+            labels,
+            token_types,
+        }))
+    }
+
+    /// Names in `text`, in order. Runs the model on the calling thread.
+    pub fn recognize(&self, text: &str) -> Result<Vec<NamedEntity>> {
+        let encoding = self
+            .tokenizer
+            .encode(text, true)
+            .map_err(|e| anyhow::anyhow!("Failed to tokenize text: {}", e))?;
+
+        let mut entities = Vec::new();
+        for window in std::iter::once(&encoding).chain(encoding.get_overflowing()) {
+            entities.extend(self.classify(window)?);
+        }
+        Ok(entities)
+    }
+
+    /// Entities in one window of text.
+    fn classify(&self, encoding: &Encoding) -> Result<Vec<NamedEntity>> {
+        let ids: Vec<i64> = encoding.get_ids().iter().map(|&id| id as i64).collect();
+        let length = ids.len();
+        if length == 0 {
+            return Ok(Vec::new());
+        }
+        let input_ids = Value::from_array(self.session.allocator(), &Array2::from_shape_vec((1, length), ids)?)?;
+        let attention_mask = Value::from_array(self.session.allocator(), &Array2::<i64>::ones((1, length)))?;
+        let token_type_ids = Value::from_array(self.session.allocator(), &Array2::<i64>::zeros((1, length)))?;
+        let outputs = if self.token_types {
+            self.session.run([
+                ("input_ids", &input_ids),
+                ("attention_mask", &attention_mask),
+                ("token_type_ids", &token_type_ids),
+            ])?
+        } else {
+            self.session.run([("input_ids", &input_ids), ("attention_mask", &attention_mask)])?
+        };
+        let logits = outputs[0].try_extract::<f32>()?;
+        if logits.len() < length * self.labels.len() {
+            return Err(anyhow::anyhow!("Unexpected NER output size {}", logits.len()));
+        }
+
+        Ok(decode(&self.labels, encoding, &logits))
+    }
+}
+
+/// Joins runs of words with the same label into entities; `logits` has one
+/// row of scores per token of `encoding`. Words take the label of their
+/// first token.
+fn decode(labels: &[String], encoding: &Encoding, logits: &[f32]) -> Vec<NamedEntity> {
+    let classes = labels.len();
+    let offsets = encoding.get_offsets();
+    let words = encoding.get_word_ids();
+    let special = encoding.get_special_tokens_mask();
+    let mut entities: Vec<NamedEntity> = Vec::new();
+    // The entity the previous word belongs to
+    let mut open: Option<usize> = None;
+    // Probabilities of the open entity's words
+    let mut scores: Vec<f32> = Vec::new();
+
+    for token in 0..offsets.len() {
+        if special[token] == 1 {
+            continue;
+        }
+        let (start, end) = offsets[token];
+        if token > 0 && words[token].is_some() && words[token] == words[token - 1] {
+            // The rest of a word goes wherever its first token went
+            if let Some(index) = open {
+                entities[index].end = end;
+            }
+            continue;
+        }
+
+        let (class, probability) = softmax_max(&logits[token * classes..(token + 1) * classes]);
+        let label = labels.get(class).map(String::as_str).unwrap_or("O");
+        if label == "O" {
+            close(&mut entities, &mut open, &mut scores);
+            continue;
+        }
+
+        let (prefix, kind) = label.split_once('-').unwrap_or(("", label));
+        let continues = prefix != "B" && open.is_some_and(|index| entities[index].label == kind);
+        match open {
+            Some(index) if continues => entities[index].end = end,
+            _ => {
+                close(&mut entities, &mut open, &mut scores);
+                entities.push(NamedEntity {
+                    label: kind.to_string(),
+                    start,
+                    end,
+                    score: 0.0,
+                });
+                open = Some(entities.len() - 1);
+            }
+        }
+        scores.push(probability);
+    }
+    close(&mut entities, &mut open, &mut scores);
+    entities
+}
+
+/// Ends the open entity, scoring it by the mean probability of its words.
+fn close(entities: &mut [NamedEntity], open: &mut Option<usize>, scores: &mut Vec<f32>) {
+    if let Some(index) = open.take() {
+        entities[index].score = scores.iter().sum::<f32>() / scores.len().max(1) as f32;
+    }
+    scores.clear();
+}
+
+/// The most likely class and its probability.
+fn softmax_max(logits: &[f32]) -> (usize, f32) {
+    let max = logits.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+    let total: f32 = logits.iter().map(|logit| (logit - max).exp()).sum();
+    let (class, logit) = logits
+        .iter()
+        .enumerate()
+        .max_by(|a, b| a.1.total_cmp(b.1))
+        .unwrap_or((0, &max));
+    (class, (logit - max).exp() / total)
+}
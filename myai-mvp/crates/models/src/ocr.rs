@@ -0,0 +1,319 @@
+use anyhow::Result;
+use image::{imageops::FilterType, DynamicImage, GenericImageView, RgbImage};
+use ndarray::Array4;
+use ort::{Session, Value};
+use std::collections::VecDeque;
+use std::path::Path;
+use tracing::info;
+
+use crate::create_session;
+
+/// Longest side the detector sees; larger images are scaled down
+const MAX_DETECT_SIDE: u32 = 960;
+/// Probability above which a pixel counts as text
+const PIXEL_THRESHOLD: f32 = 0.3;
+/// Mean probability a region needs to be kept as a text box
+const BOX_THRESHOLD: f32 = 0.6;
+/// How far boxes are grown around the shrunk regions the detector outputs
+const UNCLIP_RATIO: f32 = 1.5;
+/// Height of the text strips fed to the recognizer
+const RECOGNIZE_HEIGHT: u32 = 48;
+/// Lines recognized with less confidence than this are dropped as noise
+const MIN_CONFIDENCE: f32 = 0.5;
+
+/// A line of recognized text and where it is in the image.
+#[derive(Debug, Clone)]
+pub struct TextLine {
+    pub text: String,
+    /// Left, top, width and height in pixels of the original image
+    pub bbox: [u32; 4],
+    pub confidence: f32,
+}
+
+/// Text found in an image, in reading order.
+#[derive(Debug, Clone, Default)]
+pub struct Recognized {
+    pub width: u32,
+    pub height: u32,
+    pub lines: Vec<TextLine>,
+}
+
+/// PP-OCR style two-stage OCR: a DB detector finds text regions and a CRNN
+/// recognizer reads each one with CTC decoding.
+pub struct OcrModel {
+    detector: Session,
+    recognizer: Session,
+    /// Recognizer classes after the CTC blank
+    characters: Vec<String>,
+}
+
+impl OcrModel {
+    /// Loads the OCR models if they were downloaded. OCR is optional, so
+    /// missing files give `None` rather than an error.
+    pub async fn load(model_dir: &str) -> Result<Option<Self>> {
+        let detector_path = Path::new(model_dir).join("ocr-det.onnx");
+        let recognizer_path = Path::new(model_dir).join("ocr-rec.onnx");
+        let keys_path = Path::new(model_dir).join("ocr-keys.txt");
+
+        if !detector_path.exists() || !recognizer_path.exists() || !keys_path.exists() {
+            info!("OCR models not found in {}; images are indexed by their metadata only", model_dir);
+            return Ok(None);
+        }
+
+        info!("Loading OCR models from {:?}", model_dir);
+        let detector = create_session(&detector_path)?;
+        let recognizer = create_session(&recognizer_path)?;
+        let mut characters: Vec<String> = std::fs::read_to_string(&keys_path)?
+            .lines()
+            .map(|line| line.trim_end_matches('\r').to_string())
+            .collect();
+        // PP-OCR recognizers have one more class than the dictionary: a space
+        characters.push(" ".to_string());
+
+        info!("OCR models loaded successfully ({} characters)", characters.len());
+        Ok(Some(Self {
+            detector,
+            recognizer,
+            characters,
+        }))
+    }
+
+    /// Finds and reads the text in an encoded image (PNG, JPEG, WebP, ...).
+    /// Runs the models on the calling thread.
+    pub fn recognize(&self, bytes: &[u8]) -> Result<Recognized> {
+        let image = image::load_from_memory(bytes)?;
+        let (width, height) = image.dimensions();
+        let rgb = image.to_rgb8();
+
+        let mut lines = Vec::new();
+        for bbox in self.detect(&image)? {
+            let [left, top, box_width, box_height] = bbox;
+            let strip = image::imageops::crop_imm(&rgb, left, top, box_width, box_height).to_image();
+            if let Some((text, confidence)) = self.read(&strip)? {
+                if confidence >= MIN_CONFIDENCE && !text.trim().is_empty() {
+                    lines.push(TextLine {
+                        text: text.trim().to_string(),
+                        bbox,
+                        confidence,
+                    });
+                }
+            }
+        }
+
+        Ok(Recognized {
+            width,
+            height,
+            lines: reading_order(lines),
+        })
+    }
+
+    /// Boxes around the text regions, in original image pixels.
+    fn detect(&self, image: &DynamicImage) -> Result<Vec<[u32; 4]>> {
+        let (width, height) = image.dimensions();
+        let scale = (MAX_DETECT_SIDE as f32 / width.max(height) as f32).min(1.0);
+        // The detector downsamples by 32
+        let resized_width = ((width as f32 * scale / 32.0).round() as u32).max(1) * 32;
+        let resized_height = ((height as f32 * scale / 32.0).round() as u32).max(1) * 32;
+        let resized = image
+            .resize_exact(resized_width, resized_height, FilterType::Triangle)
+            .to_rgb8();
+
+        const MEAN: [f32; 3] = [0.485, 0.456, 0.406];
+        const STD: [f32; 3] = [0.229, 0.224, 0.225];
+        let mut input = Array4::<f32>::zeros((1, 3, resized_height as usize, resized_width as usize));
+        for (x, y, pixel) in resized.enumerate_pixels() {
+            for channel in 0..3 {
+                input[[0, channel, y as usize, x as usize]] =
+                    (pixel[channel] as f32 / 255.0 - MEAN[channel]) / STD[channel];
+            }
+        }
+
+        let input_tensor = Value::from_array(self.detector.allocator(), &input)?;
+        let outputs = self.detector.run([("x", &input_tensor)])?;
+        let probabilities = outputs[0].try_extract::<f32>()?;
+        let map_width = resized_width as usize;
+        let map_height = resized_height as usize;
+        if probabilities.len() < map_width * map_height {
+            return Err(anyhow::anyhow!("Unexpected OCR detector output size {}", probabilities.len()));
+        }
+
+        let x_ratio = width as f32 / resized_width as f32;
+        let y_ratio = height as f32 / resized_height as f32;
+        let mut boxes = Vec::new();
+        for region in regions(&probabilities[..map_width * map_height], map_width, map_height) {
+            if region.score < BOX_THRESHOLD {
+                continue;
+            }
+            // Grow the box by the DB "unclip" offset: area * ratio / perimeter
+            let (region_width, region_height) = (region.width() as f32, region.height() as f32);
+            let offset = region_width * region_height * UNCLIP_RATIO / (2.0 * (region_width + region_height));
+            let left = ((region.left as f32 - offset) * x_ratio).max(0.0) as u32;
+            let top = ((region.top as f32 - offset) * y_ratio).max(0.0) as u32;
+            let right = (((region.right + 1) as f32 + offset) * x_ratio).min(width as f32) as u32;
+            let bottom = (((region.bottom + 1) as f32 + offset) * y_ratio).min(height as f32) as u32;
+            if right > left + 3 && bottom > top + 3 {
+                boxes.push([left, top, right - left, bottom - top]);
+            }
+        }
+        Ok(boxes)
+    }
+
+    /// Reads one text strip; returns its text and mean character confidence.
+    fn read(&self, strip: &RgbImage) -> Result<Option<(String, f32)>> {
+        let (width, height) = strip.dimensions();
+        if width == 0 || height == 0 {
+            return Ok(None);
+        }
+        let resized_width = ((RECOGNIZE_HEIGHT as f32 * width as f32 / height as f32).ceil() as u32).clamp(16, 2048);
+        let resized = image::imageops::resize(strip, resized_width, RECOGNIZE_HEIGHT, FilterType::Triangle);
+
+        let mut input = Array4::<f32>::zeros((1, 3, RECOGNIZE_HEIGHT as usize, resized_width as usize));
+        for (x, y, pixel) in resized.enumerate_pixels() {
+            for channel in 0..3 {
+                input[[0, channel, y as usize, x as usize]] = (pixel[channel] as f32 / 255.0 - 0.5) / 0.5;
+            }
+        }
+
+        let input_tensor = Value::from_array(self.recognizer.allocator(), &input)?;
+        let outputs = self.recognizer.run([("x", &input_tensor)])?;
+        let shape = outputs[0].shape();
+        let scores = outputs[0].try_extract::<f32>()?;
+        if shape.len() != 3 {
+            return Err(anyhow::anyhow!("Expected 3D OCR recognizer output, got {:?}", shape));
+        }
+        let (steps, classes) = (shape[1] as usize, shape[2] as usize);
+
+        // Greedy CTC: best class per step, dropping blanks (0) and repeats
+        let mut text = String::new();
+        let mut confidences = Vec::new();
+        let mut previous = 0;
+        for step in 0..steps {
+            let row = &scores[step * classes..(step + 1) * classes];
+            let (best, score) = row
+                .iter()
+                .enumerate()
+                .max_by(|(_, a), (_, b)| a.total_cmp(b))
+                .map(|(class, score)| (class, *score))
+                .unwrap_or((0, 0.0));
+            if best != 0 && best != previous {
+                if let Some(character) = self.characters.get(best - 1) {
+                    text.push_str(character);
+                    confidences.push(score);
+                }
+            }
+            previous = best;
+        }
+
+        if confidences.is_empty() {
+            return Ok(None);
+        }
+        let confidence = confidences.iter().sum::<f32>() / confidences.len() as f32;
+        Ok(Some((text, confidence)))
+    }
+}
+
+/// A connected area of text pixels in the detector's probability map.
+struct Region {
+    left: usize,
+    top: usize,
+    right: usize,
+    bottom: usize,
+    /// Mean probability over the region's pixels
+    score: f32,
+}
+
+impl Region {
+    fn width(&self) -> usize {
+        self.right - self.left + 1
+    }
+
+    fn height(&self) -> usize {
+        self.bottom - self.top + 1
+    }
+}
+
+/// Connected components (4-neighbour) of the pixels above `PIXEL_THRESHOLD`.
+fn regions(probabilities: &[f32], width: usize, height: usize) -> Vec<Region> {
+    let mut seen = vec![false; probabilities.len()];
+    let mut regions = Vec::new();
+    let mut queue = VecDeque::new();
+
+    for start in 0..probabilities.len() {
+        if seen[start] || probabilities[start] < PIXEL_THRESHOLD {
+            continue;
+        }
+        seen[start] = true;
+        queue.push_back(start);
+        let (mut left, mut top, mut right, mut bottom) = (width, height, 0, 0);
+        let (mut total, mut pixels) = (0.0, 0);
+
+        while let Some(index) = queue.pop_front() {
+            let (x, y) = (index % width, index / width);
+            left = left.min(x);
+            right = right.max(x);
+            top = top.min(y);
+            bottom = bottom.max(y);
+            total += probabilities[index];
+            pixels += 1;
+
+            let neighbours = [
+                (x > 0).then(|| index - 1),
+                (x + 1 < width).then(|| index + 1),
+                (y > 0).then(|| index - width),
+                (y + 1 < height).then(|| index + width),
+            ];
+            for neighbour in neighbours.into_iter().flatten() {
+                if !seen[neighbour] && probabilities[neighbour] >= PIXEL_THRESHOLD {
+                    seen[neighbour] = true;
+                    queue.push_back(neighbour);
+                }
+            }
+        }
+
+        // Specks are noise
+        if pixels >= 10 {
+            regions.push(Region {
+                left,
+                top,
+                right,
+                bottom,
+                score: total / pixels as f32,
+            });
+        }
+    }
+    regions
+}
+
+/// Orders boxes top to bottom, then merges boxes on the same row (their
+/// vertical centres inside each other's span) left to right into one line.
+fn reading_order(mut boxes: Vec<TextLine>) -> Vec<TextLine> {
+    boxes.sort_by_key(|line| line.bbox[1]);
+    let mut rows: Vec<Vec<TextLine>> = Vec::new();
+    for line in boxes {
+        let centre = line.bbox[1] + line.bbox[3] / 2;
+        let row = rows.iter_mut().find(|row| {
+            let first = &row[0].bbox;
+            centre >= first[1] && centre <= first[1] + first[3]
+        });
+        match row {
+            Some(row) => row.push(line),
+            None => rows.push(vec![line]),
+        }
+    }
+
+    rows.into_iter()
+        .map(|mut row| {
+            row.sort_by_key(|line| line.bbox[0]);
+            let left = row.iter().map(|line| line.bbox[0]).min().unwrap_or(0);
+            let top = row.iter().map(|line| line.bbox[1]).min().unwrap_or(0);
+            let right = row.iter().map(|line| line.bbox[0] + line.bbox[2]).max().unwrap_or(0);
+            let bottom = row.iter().map(|line| line.bbox[1] + line.bbox[3]).max().unwrap_or(0);
+            let confidence = row.iter().map(|line| line.confidence).sum::<f32>() / row.len() as f32;
+            TextLine {
+                text: row.iter().map(|line| line.text.as_str()).collect::<Vec<_>>().join(" "),
+                bbox: [left, top, right - left, bottom - top],
+                confidence,
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,453 @@
+use anyhow::Result;
+use ndarray::{Array2, Array3};
+use ort::{Session, Value};
+use rustfft::{num_complex::Complex, FftPlanner};
+use std::path::Path;
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::DecoderOptions;
+use symphonia::core::errors::Error as AudioError;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+use tokenizers::Tokenizer;
+use tracing::{debug, info};
+
+use crate::create_session;
+
+/// Whisper's input: 16 kHz audio in 30 second windows of 80 mel bands
+const SAMPLE_RATE: usize = 16_000;
+const WINDOW_SAMPLES: usize = 30 * SAMPLE_RATE;
+const N_FFT: usize = 400;
+const HOP: usize = 160;
+const N_MELS: usize = 80;
+const WINDOW_FRAMES: usize = WINDOW_SAMPLES / HOP;
+/// Timestamp tokens count in steps of 20 ms
+const TIMESTAMP_STEP: f64 = 0.02;
+/// Most tokens the decoder writes for one window
+const MAX_TOKENS: usize = 224;
+
+/// A stretch of speech and when it was said, in seconds from the start.
+#[derive(Debug, Clone)]
+pub struct Segment {
+    pub start: f64,
+    pub end: f64,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Transcript {
+    /// Language code Whisper detected (`en`, `de`, ...)
+    pub language: Option<String>,
+    /// Length of the recording in seconds
+    pub duration: f64,
+    pub segments: Vec<Segment>,
+}
+
+/// Whisper exported to ONNX as an encoder and a decoder (without KV cache),
+/// run greedily with timestamp tokens so every segment knows its time.
+pub struct SpeechModel {
+    encoder: Session,
+    decoder: Session,
+    tokenizer: Tokenizer,
+    tokens: SpecialTokens,
+    mel_filters: Array2<f32>,
+}
+
+struct SpecialTokens {
+    end_of_text: u32,
+    start_of_transcript: u32,
+    transcribe: Option<u32>,
+    /// Language tokens sit between start of transcript and translate; an
+    /// English-only model has none
+    languages: Option<(u32, u32)>,
+    timestamp_begin: u32,
+}
+
+impl SpeechModel {
+    /// Loads the Whisper model if it was downloaded. Transcription is
+    /// optional, so missing files give `None` rather than an error.
+    pub async fn load(model_dir: &str) -> Result<Option<Self>> {
+        let encoder_path = Path::new(model_dir).join("whisper-encoder.onnx");
+        let decoder_path = Path::new(model_dir).join("whisper-decoder.onnx");
+        let tokenizer_path = Path::new(model_dir).join("whisper-tokenizer.json");
+
+        if !encoder_path.exists() || !decoder_path.exists() || !tokenizer_path.exists() {
+            info!("Whisper model not found in {}; audio is not transcribed", model_dir);
+            return Ok(None);
+        }
+
+        info!("Loading speech model from {:?}", model_dir);
+        let encoder = create_session(&encoder_path)?;
+        let decoder = create_session(&decoder_path)?;
+        let tokenizer = Tokenizer::from_file(tokenizer_path)
+            .map_err(|e| anyhow::anyhow!("Failed to load Whisper tokenizer: {}", e))?;
+
+        let token = |name: &str| tokenizer.token_to_id(name);
+        let missing = |name: &str| anyhow::anyhow!("Whisper tokenizer has no {} token", name);
+        let start_of_transcript = token("<|startoftranscript|>").ok_or_else(|| missing("start of transcript"))?;
+        let no_timestamps = token("<|notimestamps|>").ok_or_else(|| missing("no timestamps"))?;
+        let multilingual = tokenizer.get_vocab_size(true) >= 51865;
+        let tokens = SpecialTokens {
+            end_of_text: token("<|endoftext|>").ok_or_else(|| missing("end of text"))?,
+            start_of_transcript,
+            transcribe: token("<|transcribe|>").filter(|_| multilingual),
+            languages: token("<|translate|>")
+                .filter(|_| multilingual)
+                .map(|translate| (start_of_transcript + 1, translate)),
+            timestamp_begin: token("<|0.00|>").unwrap_or(no_timestamps + 1),
+        };
+
+        info!("Speech model loaded successfully (multilingual: {})", multilingual);
+        Ok(Some(Self {
+            encoder,
+            decoder,
+            tokenizer,
+            tokens,
+            mel_filters: mel_filters(),
+        }))
+    }
+
+    /// Decodes an audio file (WAV, MP3, M4A/AAC, Ogg Vorbis, FLAC) and
+    /// transcribes it. `extension` helps pick the container format. Runs
+    /// the models on the calling thread.
+    pub fn transcribe(&self, bytes: Vec<u8>, extension: Option<&str>) -> Result<Transcript> {
+        let samples = decode_audio(bytes, extension)?;
+        let duration = samples.len() as f64 / SAMPLE_RATE as f64;
+        let mut transcript = Transcript {
+            duration,
+            ..Default::default()
+        };
+
+        let mut seek = 0;
+        let mut language = None;
+        while seek < samples.len() {
+            let offset = seek as f64 / SAMPLE_RATE as f64;
+            let window = &samples[seek..(seek + WINDOW_SAMPLES).min(samples.len())];
+            let features = self.log_mel(window);
+            let input = Value::from_array(self.encoder.allocator(), &features)?;
+            let encoded = self.encoder.run([("input_features", &input)])?;
+            let hidden = &encoded[0];
+
+            // The language is detected from the first window and kept
+            if let (None, Some(languages)) = (language, self.tokens.languages) {
+                let detected = self.detect_language(hidden, languages)?;
+                transcript.language = self
+                    .tokenizer
+                    .id_to_token(detected)
+                    .map(|token| token.trim_start_matches("<|").trim_end_matches("|>").to_string());
+                language = Some(detected);
+            }
+            let mut prompt = vec![self.tokens.start_of_transcript];
+            prompt.extend(language);
+            prompt.extend(self.tokens.transcribe);
+
+            let tokens = self.decode(hidden, &prompt)?;
+            let (segments, unclosed, last_timestamp) = self.segments(&tokens[prompt.len()..], offset)?;
+            debug!("Transcribed {:.0}s-{:.0}s: {} segments", offset, offset + 30.0, segments.len());
+            transcript.segments.extend(segments);
+
+            // Continue from the last complete segment, or the next window
+            // when the model didn't close one. Text after the last complete
+            // segment is transcribed again from there, so it's only kept
+            // when nothing follows.
+            seek += match last_timestamp {
+                Some(end) if end > 0.0 && window.len() == WINDOW_SAMPLES => {
+                    (end * SAMPLE_RATE as f64) as usize
+                }
+                _ => {
+                    transcript.segments.extend(unclosed);
+                    WINDOW_SAMPLES
+                }
+            };
+        }
+
+        Ok(transcript)
+    }
+
+    fn detect_language(&self, hidden: &Value, (first, last): (u32, u32)) -> Result<u32> {
+        let logits = self.logits(hidden, &[self.tokens.start_of_transcript])?;
+        Ok((first..last)
+            .max_by(|a, b| logits[*a as usize].total_cmp(&logits[*b as usize]))
+            .unwrap_or(first))
+    }
+
+    /// Greedy decoding under Whisper's timestamp rules: text starts with a
+    /// timestamp, timestamps come in pairs and never go backwards.
+    fn decode(&self, hidden: &Value, prompt: &[u32]) -> Result<Vec<u32>> {
+        let tokens = &self.tokens;
+        let mut sequence = prompt.to_vec();
+
+        while sequence.len() - prompt.len() < MAX_TOKENS {
+            let mut logits = self.logits(hidden, &sequence)?;
+            let generated = &sequence[prompt.len()..];
+            let is_timestamp = |token: &u32| *token >= tokens.timestamp_begin;
+
+            // No special tokens other than end of text and timestamps
+            for token in tokens.start_of_transcript..tokens.timestamp_begin.min(logits.len() as u32) {
+                logits[token as usize] = f32::NEG_INFINITY;
+            }
+            let last = generated.last();
+            let penultimate = generated.len().checked_sub(2).map(|i| &generated[i]);
+            match (last.map(is_timestamp), penultimate.map(is_timestamp)) {
+                // The first token is a timestamp
+                (None, _) => suppress(&mut logits, 0..tokens.timestamp_begin as usize),
+                // A closed pair, or the opening timestamp: text follows
+                (Some(true), Some(true)) | (Some(true), None) => {
+                    suppress(&mut logits, tokens.timestamp_begin as usize..usize::MAX)
+                }
+                // An open segment ends with a timestamp (or the end)
+                (Some(true), Some(false)) => {
+                    let end_of_text = logits[tokens.end_of_text as usize];
+                    suppress(&mut logits, 0..tokens.timestamp_begin as usize);
+                    logits[tokens.end_of_text as usize] = end_of_text;
+                }
+                _ => {}
+            }
+            if let Some(latest) = generated.iter().rev().find(|token| is_timestamp(token)) {
+                suppress(&mut logits, tokens.timestamp_begin as usize..*latest as usize);
+            }
+
+            let next = logits
+                .iter()
+                .enumerate()
+                .max_by(|(_, a), (_, b)| a.total_cmp(b))
+                .map(|(token, _)| token as u32)
+                .unwrap_or(tokens.end_of_text);
+            if next == tokens.end_of_text {
+                break;
+            }
+            sequence.push(next);
+        }
+
+        Ok(sequence)
+    }
+
+    /// Logits for the token after `sequence`.
+    fn logits(&self, hidden: &Value, sequence: &[u32]) -> Result<Vec<f32>> {
+        let ids = Array2::from_shape_vec((1, sequence.len()), sequence.iter().map(|&token| token as i64).collect())?;
+        let input_ids = Value::from_array(self.decoder.allocator(), &ids)?;
+        let outputs = self.decoder.run([("input_ids", &input_ids), ("encoder_hidden_states", hidden)])?;
+        let shape = outputs[0].shape();
+        let logits = outputs[0].try_extract::<f32>()?;
+        if shape.len() != 3 {
+            return Err(anyhow::anyhow!("Expected 3D Whisper decoder output, got {:?}", shape));
+        }
+        let vocab = shape[2] as usize;
+        let last = (shape[1] as usize - 1) * vocab;
+        Ok(logits[last..last + vocab].to_vec())
+    }
+
+    /// Splits decoded tokens at timestamp pairs. Returns the closed
+    /// segments, the text left open at the end of the window, and the last
+    /// closing timestamp, relative to the window.
+    fn segments(&self, tokens: &[u32], offset: f64) -> Result<(Vec<Segment>, Option<Segment>, Option<f64>)> {
+        let mut segments = Vec::new();
+        let mut start = None;
+        let mut text: Vec<u32> = Vec::new();
+        let mut last_timestamp = None;
+
+        for &token in tokens {
+            if token < self.tokens.timestamp_begin {
+                text.push(token);
+                continue;
+            }
+            let time = (token - self.tokens.timestamp_begin) as f64 * TIMESTAMP_STEP;
+            match start {
+                Some(started) if !text.is_empty() => {
+                    let words = self.text(&text)?;
+                    if !words.trim().is_empty() {
+                        segments.push(Segment {
+                            start: offset + started,
+                            end: offset + time,
+                            text: words.trim().to_string(),
+                        });
+                    }
+                    text.clear();
+                    start = None;
+                    last_timestamp = Some(time);
+                }
+                _ => start = Some(time),
+            }
+        }
+
+        // Text cut off by the end of the window runs to its end
+        let mut unclosed = None;
+        if let (Some(started), false) = (start, text.is_empty()) {
+            let words = self.text(&text)?;
+            if !words.trim().is_empty() {
+                unclosed = Some(Segment {
+                    start: offset + started,
+                    end: offset + 30.0,
+                    text: words.trim().to_string(),
+                });
+            }
+        }
+        Ok((segments, unclosed, last_timestamp))
+    }
+
+    fn text(&self, tokens: &[u32]) -> Result<String> {
+        self.tokenizer
+            .decode(tokens, true)
+            .map_err(|e| anyhow::anyhow!("Failed to decode transcript: {}", e))
+    }
+
+    /// Whisper's log-mel spectrogram of up to 30 seconds of audio, zero
+    /// padded to a full window: `[1, 80, 3000]`.
+    fn log_mel(&self, samples: &[f32]) -> Array3<f32> {
+        let mut padded = vec![0.0; WINDOW_SAMPLES];
+        padded[..samples.len()].copy_from_slice(samples);
+
+        // Centred frames: reflect-pad by half a window on each side
+        let half = N_FFT / 2;
+        let mut signal = Vec::with_capacity(WINDOW_SAMPLES + N_FFT);
+        signal.extend((1..=half).rev().map(|i| padded[i]));
+        signal.extend_from_slice(&padded);
+        signal.extend((1..=half).map(|i| padded[WINDOW_SAMPLES - 1 - i]));
+
+        let hann: Vec<f32> = (0..N_FFT)
+            .map(|n| 0.5 - 0.5 * (2.0 * std::f32::consts::PI * n as f32 / N_FFT as f32).cos())
+            .collect();
+        let fft = FftPlanner::<f32>::new().plan_fft_forward(N_FFT);
+        let bins = N_FFT / 2 + 1;
+
+        let mut mel = Array3::<f32>::zeros((1, N_MELS, WINDOW_FRAMES));
+        let mut buffer = vec![Complex::new(0.0, 0.0); N_FFT];
+        let mut power = vec![0.0; bins];
+        for frame in 0..WINDOW_FRAMES {
+            let start = frame * HOP;
+            for (n, value) in buffer.iter_mut().enumerate() {
+                *value = Complex::new(signal[start + n] * hann[n], 0.0);
+            }
+            fft.process(&mut buffer);
+            for (bin, value) in power.iter_mut().enumerate() {
+                *value = buffer[bin].norm_sqr();
+            }
+            for band in 0..N_MELS {
+                let energy: f32 = (0..bins).map(|bin| self.mel_filters[[band, bin]] * power[bin]).sum();
+                mel[[0, band, frame]] = energy.max(1e-10).log10();
+            }
+        }
+
+        let max = mel.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+        mel.mapv_inplace(|value| (value.max(max - 8.0) + 4.0) / 4.0);
+        mel
+    }
+}
+
+fn suppress(logits: &mut [f32], range: std::ops::Range<usize>) {
+    let end = range.end.min(logits.len());
+    for logit in &mut logits[range.start.min(end)..end] {
+        *logit = f32::NEG_INFINITY;
+    }
+}
+
+/// Slaney-style mel filterbank (librosa's default, which Whisper uses).
+fn mel_filters() -> Array2<f32> {
+    let hz_to_mel = |hz: f64| {
+        if hz < 1000.0 {
+            3.0 * hz / 200.0
+        } else {
+            15.0 + (hz / 1000.0).ln() / (6.4f64.ln() / 27.0)
+        }
+    };
+    let mel_to_hz = |mel: f64| {
+        if mel < 15.0 {
+            200.0 * mel / 3.0
+        } else {
+            1000.0 * ((mel - 15.0) * 6.4f64.ln() / 27.0).exp()
+        }
+    };
+
+    let bins = N_FFT / 2 + 1;
+    let top = hz_to_mel(SAMPLE_RATE as f64 / 2.0);
+    let points: Vec<f64> = (0..N_MELS + 2)
+        .map(|i| mel_to_hz(top * i as f64 / (N_MELS + 1) as f64))
+        .collect();
+
+    let mut filters = Array2::<f32>::zeros((N_MELS, bins));
+    for band in 0..N_MELS {
+        let (lower, centre, upper) = (points[band], points[band + 1], points[band + 2]);
+        let norm = 2.0 / (upper - lower);
+        for bin in 0..bins {
+            let hz = bin as f64 * SAMPLE_RATE as f64 / N_FFT as f64;
+            let rising = (hz - lower) / (centre - lower);
+            let falling = (upper - hz) / (upper - centre);
+            filters[[band, bin]] = (rising.min(falling).max(0.0) * norm) as f32;
+        }
+    }
+    filters
+}
+
+/// Decodes the first audio track to 16 kHz mono samples.
+fn decode_audio(bytes: Vec<u8>, extension: Option<&str>) -> Result<Vec<f32>> {
+    let source = MediaSourceStream::new(Box::new(std::io::Cursor::new(bytes)), Default::default());
+    let mut hint = Hint::new();
+    if let Some(extension) = extension {
+        hint.with_extension(extension);
+    }
+    let probed = symphonia::default::get_probe().format(
+        &hint,
+        source,
+        &FormatOptions::default(),
+        &MetadataOptions::default(),
+    )?;
+    let mut format = probed.format;
+    let track = format
+        .default_track()
+        .ok_or_else(|| anyhow::anyhow!("No audio track"))?;
+    let track_id = track.id;
+    let sample_rate = track
+        .codec_params
+        .sample_rate
+        .ok_or_else(|| anyhow::anyhow!("Audio track has no sample rate"))? as usize;
+    let mut decoder = symphonia::default::get_codecs().make(&track.codec_params, &DecoderOptions::default())?;
+
+    let mut mono = Vec::new();
+    loop {
+        let packet = match format.next_packet() {
+            Ok(packet) => packet,
+            Err(AudioError::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e.into()),
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+        let decoded = match decoder.decode(&packet) {
+            Ok(decoded) => decoded,
+            // A damaged packet loses a few milliseconds, not the recording
+            Err(AudioError::DecodeError(_)) => continue,
+            Err(e) => return Err(e.into()),
+        };
+        let spec = *decoded.spec();
+        let channels = spec.channels.count().max(1);
+        let mut buffer = SampleBuffer::<f32>::new(decoded.capacity() as u64, spec);
+        buffer.copy_interleaved_ref(decoded);
+        mono.extend(
+            buffer
+                .samples()
+                .chunks(channels)
+                .map(|frame| frame.iter().sum::<f32>() / channels as f32),
+        );
+    }
+
+    Ok(resample(&mono, sample_rate))
+}
+
+/// Linear resampling to 16 kHz, plenty for speech.
+fn resample(samples: &[f32], rate: usize) -> Vec<f32> {
+    if rate == SAMPLE_RATE || samples.is_empty() {
+        return samples.to_vec();
+    }
+    let step = rate as f64 / SAMPLE_RATE as f64;
+    let length = (samples.len() as f64 / step) as usize;
+    (0..length)
+        .map(|i| {
+            let position = i as f64 * step;
+            let index = position as usize;
+            let fraction = (position - index as f64) as f32;
+            let next = samples.get(index + 1).copied().unwrap_or(samples[index]);
+            samples[index] * (1.0 - fraction) + next * fraction
+        })
+        .collect()
+}
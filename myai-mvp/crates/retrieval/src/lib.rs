@@ -1,11 +1,12 @@
 use anyhow::Result;
-use chrono::Utc;
+use chrono::{DateTime, NaiveDate, Utc};
 use std::collections::HashMap;
+use std::path::Path;
 use std::time::Instant;
 use tracing::{info, warn};
 use types::{
-    AppConfig, Chunk, QueryFilters, QueryRequest, QueryResponse, ReasoningStage, ReasoningTrace,
-    SearchHit,
+    AppConfig, Chunk, ContextMode, EntityKind, ParentChunk, QueryFilters, QueryRequest, QueryResponse,
+    ReasoningStage, ReasoningTrace, SearchHit,
 };
 
 use models::ModelManager;
@@ -26,6 +27,11 @@ impl HybridIndex {
         })
     }
     
+    /// The models searches run on, for ingest steps that need them too.
+    pub fn models(&self) -> &ModelManager {
+        &self.models
+    }
+    
     pub async fn add_document(&self, doc: &types::Document) -> Result<()> {
         self.storage.save_document(doc).await
     }
@@ -43,6 +49,56 @@ impl HybridIndex {
         self.storage.upsert_chunk(&chunk_with_embedding).await
     }
     
+    /// Stores a chunk for keyword search only, keeping it out of the vector
+    /// index.
+    pub async fn add_chunk_unembedded(&self, chunk: &Chunk) -> Result<()> {
+        self.storage.upsert_chunk(chunk).await
+    }
+    
+    pub async fn get_document_by_path(&self, path: &str) -> Result<Option<types::Document>> {
+        self.storage.get_document_by_path(path).await
+    }
+    
+    /// Documents stored for a file, including each message of an mbox.
+    pub async fn get_documents_by_file(&self, path: &str) -> Result<Vec<types::Document>> {
+        self.storage.get_documents_by_file(path).await
+    }
+    
+    /// Indexed files at or below `root`.
+    pub async fn get_file_paths_under(&self, root: &str) -> Result<Vec<String>> {
+        self.storage.get_file_paths_under(root).await
+    }
+    
+    pub async fn get_chunks_by_doc(&self, doc_id: &str) -> Result<Vec<Chunk>> {
+        self.storage.get_chunks_by_doc(doc_id).await
+    }
+    
+    /// Stores the parent sections of a document in place of its old ones.
+    pub async fn save_parents(&self, doc_id: &str, parents: &[ParentChunk]) -> Result<()> {
+        self.storage.save_parents(doc_id, parents).await
+    }
+    
+    /// Links a stored chunk to a new parent and position without re-embedding it.
+    pub async fn place_chunk(&self, chunk_id: &str, parent_id: Option<&str>, position: u32) -> Result<()> {
+        self.storage.place_chunk(chunk_id, parent_id, position).await
+    }
+    
+    pub async fn remove_chunks(&self, chunk_ids: &[String]) -> Result<()> {
+        self.storage.delete_chunks(chunk_ids).await
+    }
+    
+    /// Removes the document at `path` (or below it, for a directory) from the index.
+    pub async fn remove_path(&self, path: &str) -> Result<usize> {
+        self.storage.delete_documents_by_path(path).await
+    }
+    
+    /// Points documents at a new path without re-embedding their chunks.
+    /// Anything already indexed at the destination is replaced.
+    pub async fn rename_path(&self, from: &str, to: &str) -> Result<usize> {
+        self.storage.delete_documents_by_path(to).await?;
+        self.storage.rename_path(from, to).await
+    }
+    
     pub async fn search(
         &self,
         request: &QueryRequest,
@@ -52,81 +108,117 @@ impl HybridIndex {
         
         info!("Starting hybrid search for query: {}", request.query);
         
-        // Step 1: BM25 search
-        let bm25_start = Instant::now();
-        let bm25_results = self
-            .storage
-            .search_bm25(&request.query, self.config.retrieval.rerank_top)
-            .await?;
-        let bm25_elapsed = bm25_start.elapsed().as_millis() as u64;
-        
-        info!("BM25 found {} results in {}ms", bm25_results.len(), bm25_elapsed);
-        
-        // Step 2: ANN search
-        let ann_start = Instant::now();
+        let embed_start = Instant::now();
         let query_embedding = self
             .models
             .embedder
             .embed(&[request.query.clone()])
             .await?;
-        
-        let ann_results = if let Some(embedding) = query_embedding.first() {
-            self.storage
-                .search_ann(embedding, self.config.retrieval.rerank_top)
+        let embed_elapsed = embed_start.elapsed().as_millis() as u64;
+        
+        // Filters and date bounds are checked on stored chunks, after BM25 and
+        // ANN have cut their lists. With any set, both searches are widened
+        // until enough candidates pass or the whole index has been searched
+        let restricted = request.filters.is_some() || request.date_from.is_some() || request.date_to.is_some();
+        let total_chunks = if restricted { self.storage.get_stats().await?.1 as usize } else { 0 };
+        let rerank_top = self.config.retrieval.rerank_top;
+        let mut limit = rerank_top;
+        let (mut bm25_elapsed, mut ann_elapsed, mut hybrid_elapsed) = (0, embed_elapsed, 0);
+        let mut filter_elapsed = 0;
+        
+        let chunks = loop {
+            // Step 1: BM25 search
+            let bm25_start = Instant::now();
+            let bm25_results = self
+                .storage
+                .search_bm25(&request.query, limit, request.language.as_deref())
+                .await?;
+            bm25_elapsed += bm25_start.elapsed().as_millis() as u64;
+            
+            info!("BM25 found {} results in {}ms", bm25_results.len(), bm25_elapsed);
+            
+            // Step 2: ANN search
+            let ann_start = Instant::now();
+            let ann_results = if let Some(embedding) = query_embedding.first() {
+                self.storage.search_ann(embedding, limit).await?
+            } else {
+                vec![]
+            };
+            ann_elapsed += ann_start.elapsed().as_millis() as u64;
+            
+            info!("ANN found {} results in {}ms", ann_results.len(), ann_elapsed);
+            
+            // Step 3: Hybrid union and scoring
+            let hybrid_start = Instant::now();
+            let mut combined_results = HashMap::new();
+            
+            // Add BM25 results
+            for (chunk_id, bm25_score) in bm25_results {
+                combined_results.insert(chunk_id, (bm25_score, 0.0));
+            }
+            
+            // Add ANN results and combine scores
+            for (chunk_id, ann_score) in ann_results {
+                let entry = combined_results.entry(chunk_id).or_insert((0.0, 0.0));
+                entry.1 = ann_score;
+            }
+            
+            // Calculate hybrid scores
+            let mut hybrid_results: Vec<(String, f32)> = combined_results
+                .into_iter()
+                .map(|(chunk_id, (bm25_score, ann_score))| {
+                    let hybrid_score = self.config.retrieval.alpha * bm25_score
+                        + self.config.retrieval.beta * ann_score;
+                    (chunk_id, hybrid_score)
+                })
+                .collect();
+            
+            // Sort by hybrid score
+            hybrid_results.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
+            
+            // Take top candidates for reranking; filtered searches keep them
+            // all until the filters have run
+            let rerank_candidates: Vec<String> = hybrid_results
+                .iter()
+                .take(if restricted { hybrid_results.len() } else { rerank_top })
+                .map(|(id, _)| id.clone())
+                .collect();
+            
+            hybrid_elapsed += hybrid_start.elapsed().as_millis() as u64;
+            
+            info!(
+                "Hybrid union found {} candidates in {}ms",
+                rerank_candidates.len(),
+                hybrid_elapsed
+            );
+            
+            let filter_start = Instant::now();
+            let rank: HashMap<&str, usize> = rerank_candidates
+                .iter()
+                .enumerate()
+                .map(|(rank, id)| (id.as_str(), rank))
+                .collect();
+            let mut chunks: Vec<Chunk> = self
+                .storage
+                .get_chunks_by_ids(&rerank_candidates)
                 .await?
-        } else {
-            vec![]
+                .into_iter()
+                .filter(|chunk| within_dates(chunk, request))
+                .filter(|chunk| matches_filters(chunk, request.filters.as_ref()))
+                .collect();
+            chunks.sort_by_key(|chunk| rank.get(chunk.id.as_str()).copied().unwrap_or(usize::MAX));
+            filter_elapsed += filter_start.elapsed().as_millis() as u64;
+            
+            if !restricted || chunks.len() >= rerank_top || limit >= total_chunks {
+                chunks.truncate(rerank_top);
+                break chunks;
+            }
+            limit = (limit * 4).min(total_chunks);
+            info!("{} candidates pass the filters; searching the top {}", chunks.len(), limit);
         };
-        let ann_elapsed = ann_start.elapsed().as_millis() as u64;
-        
-        info!("ANN found {} results in {}ms", ann_results.len(), ann_elapsed);
-        
-        // Step 3: Hybrid union and scoring
-        let hybrid_start = Instant::now();
-        let mut combined_results = HashMap::new();
-        
-        // Add BM25 results
-        for (chunk_id, bm25_score) in bm25_results {
-            combined_results.insert(chunk_id, (bm25_score, 0.0));
-        }
-        
-        // Add ANN results and combine scores
-        for (chunk_id, ann_score) in ann_results {
-            let entry = combined_results.entry(chunk_id).or_insert((0.0, 0.0));
-            entry.1 = ann_score;
-        }
-        
-        // Calculate hybrid scores
-        let mut hybrid_results: Vec<(String, f32)> = combined_results
-            .into_iter()
-            .map(|(chunk_id, (bm25_score, ann_score))| {
-                let hybrid_score = self.config.retrieval.alpha * bm25_score
-                    + self.config.retrieval.beta * ann_score;
-                (chunk_id, hybrid_score)
-            })
-            .collect();
-        
-        // Sort by hybrid score
-        hybrid_results.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
-        
-        // Take top candidates for reranking
-        let rerank_candidates: Vec<String> = hybrid_results
-            .iter()
-            .take(self.config.retrieval.rerank_top)
-            .map(|(id, _)| id.clone())
-            .collect();
-        
-        let hybrid_elapsed = hybrid_start.elapsed().as_millis() as u64;
-        
-        info!(
-            "Hybrid union found {} candidates in {}ms",
-            rerank_candidates.len(),
-            hybrid_elapsed
-        );
         
         // Step 4: Reranking
         let rerank_start = Instant::now();
-        let chunks = self.storage.get_chunks_by_ids(&rerank_candidates).await?;
         
         let mut rerank_candidates_with_text = Vec::new();
         for chunk in &chunks {
@@ -142,7 +234,7 @@ impl HybridIndex {
             .rerank(&request.query, &rerank_candidates_with_text)
             .await?;
         
-        let rerank_elapsed = rerank_start.elapsed().as_millis() as u64;
+        let rerank_elapsed = rerank_start.elapsed().as_millis() as u64 + filter_elapsed;
         
         info!("Reranking completed in {}ms", rerank_elapsed);
         
@@ -164,6 +256,7 @@ impl HybridIndex {
                     score: rerank_score,
                     metadata: serde_json::to_value(&chunk.metadata)?,
                     created_at: chunk.created_at,
+                    context: None,
                 };
                 
                 final_results.push(search_hit);
@@ -173,6 +266,7 @@ impl HybridIndex {
         // Sort by rerank score and take final top results
         final_results.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap());
         final_results.truncate(request.k as usize);
+        self.expand_context(&mut final_results, &chunks, request).await?;
         
         let total_elapsed = start_time.elapsed().as_millis() as u64;
         
@@ -212,17 +306,65 @@ impl HybridIndex {
         Ok((final_results, reasoning))
     }
     
+    /// Fills in the text around each hit asked for by `request.context`: its
+    /// parent section, or the chunks on either side of it. Hits indexed before
+    /// parents and positions were kept get their own text.
+    async fn expand_context(&self, hits: &mut [SearchHit], chunks: &[Chunk], request: &QueryRequest) -> Result<()> {
+        let chunks: HashMap<&str, &Chunk> = chunks.iter().map(|chunk| (chunk.id.as_str(), chunk)).collect();
+        match request.context {
+            ContextMode::Chunk => {}
+            ContextMode::Parent => {
+                let parent_ids: Vec<String> = hits
+                    .iter()
+                    .filter_map(|hit| chunks.get(hit.chunk_id.as_str())?.parent_id.clone())
+                    .collect();
+                let parents: HashMap<String, String> = self
+                    .storage
+                    .get_parents_by_ids(&parent_ids)
+                    .await?
+                    .into_iter()
+                    .map(|parent| (parent.id, parent.text))
+                    .collect();
+                for hit in hits.iter_mut() {
+                    let Some(chunk) = chunks.get(hit.chunk_id.as_str()) else { continue };
+                    let parent = chunk.parent_id.as_ref().and_then(|id| parents.get(id));
+                    hit.context = Some(parent.unwrap_or(&chunk.text).clone());
+                }
+            }
+            ContextMode::Window => {
+                let window = request.context_window;
+                for hit in hits.iter_mut() {
+                    let Some(chunk) = chunks.get(hit.chunk_id.as_str()) else { continue };
+                    let neighbours = self
+                        .storage
+                        .get_chunk_range(
+                            &chunk.doc_id,
+                            chunk.position.saturating_sub(window),
+                            chunk.position.saturating_add(window),
+                        )
+                        .await?;
+                    // Chunks indexed before positions were kept all sit at 0
+                    hit.context = if neighbours.len() > 2 * window as usize + 1 {
+                        Some(chunk.text.clone())
+                    } else {
+                        Some(join_overlapping(&neighbours))
+                    };
+                }
+            }
+        }
+        Ok(())
+    }
+    
     fn generate_snippet(&self, text: &str, query: &str) -> String {
         // Simple snippet generation - find query terms and create a window around them
         let query_terms: Vec<&str> = query.split_whitespace().collect();
-        let text_lower = text.to_lowercase();
         
         // Find the first occurrence of any query term
         let mut best_start = 0;
         let mut best_score = 0;
         
         for term in &query_terms {
-            if let Some(pos) = text_lower.find(&term.to_lowercase()) {
+            if let Some(pos) = find_ignore_case(text, term) {
                 let score = term.len();
                 if score > best_score {
                     best_score = score;
@@ -231,10 +373,10 @@ impl HybridIndex {
             }
         }
         
-        // Create a window around the best match
+        // Create a window around the best match, without cutting a character
         let window_size = 200;
-        let start = best_start.saturating_sub(window_size / 2);
-        let end = (best_start + window_size).min(text.len());
+        let start = text.floor_char_boundary(best_start.saturating_sub(window_size / 2));
+        let end = text.ceil_char_boundary((best_start + window_size).min(text.len()));
         
         let snippet = &text[start..end];
         
@@ -251,3 +393,152 @@ impl HybridIndex {
         result
     }
 }
+
+/// Byte offset in `text` of the first occurrence of `term`, ignoring case.
+/// Lowercasing can change a text's length, so the match is found in `text`
+/// itself rather than in a lowercased copy.
+fn find_ignore_case(text: &str, term: &str) -> Option<usize> {
+    let term: Vec<char> = term.chars().flat_map(char::to_lowercase).collect();
+    if term.is_empty() {
+        return None;
+    }
+    text.char_indices()
+        .map(|(index, _)| index)
+        .find(|&index| {
+            let mut rest = text[index..].chars().flat_map(char::to_lowercase);
+            term.iter().all(|c| rest.next() == Some(*c))
+        })
+}
+
+/// Chunk texts joined in order, with the words consecutive chunks share
+/// through their overlap kept once. Chunks that don't overlap are joined with
+/// a space within a parent section and go on separate lines across parents.
+fn join_overlapping(chunks: &[Chunk]) -> String {
+    let mut joined = String::new();
+    let mut previous: Option<&Chunk> = None;
+    for chunk in chunks {
+        let text = chunk.text.as_str();
+        // Longest run of whole words starting this chunk and ending the text
+        // so far; an overlap may also start at punctuation, as in `. Next`
+        let starts_word = text.chars().next().is_some_and(char::is_alphanumeric);
+        let shared = (1..=text.len().min(joined.len()))
+            .rev()
+            .filter(|&len| text.is_char_boundary(len))
+            .find(|&len| {
+                joined.ends_with(&text[..len])
+                    && text[len..].chars().next().is_none_or(char::is_whitespace)
+                    && (!starts_word || joined[..joined.len() - len].chars().next_back().is_none_or(char::is_whitespace))
+            })
+            .unwrap_or(0);
+        if shared == 0 {
+            if let Some(previous) = previous {
+                let same_parent = previous.parent_id.is_some() && previous.parent_id == chunk.parent_id;
+                joined.push(if same_parent { ' ' } else { '\n' });
+            }
+        }
+        joined.push_str(&text[shared..]);
+        previous = Some(chunk);
+    }
+    joined
+}
+
+/// Whether a chunk's `created_at` lies within the request's `dateFrom` and
+/// `dateTo`. Either bound may be a date (`2024-01-31`, taken as the whole
+/// day) or an RFC 3339 timestamp; bounds that don't parse are ignored.
+fn within_dates(chunk: &Chunk, request: &QueryRequest) -> bool {
+    let from = request.date_from.as_deref().and_then(|date| parse_bound(date, false));
+    let to = request.date_to.as_deref().and_then(|date| parse_bound(date, true));
+    from.is_none_or(|from| chunk.created_at >= from) && to.is_none_or(|to| chunk.created_at <= to)
+}
+
+fn parse_bound(date: &str, end_of_day: bool) -> Option<DateTime<Utc>> {
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(date.trim()) {
+        return Some(datetime.with_timezone(&Utc));
+    }
+    let day = NaiveDate::parse_from_str(date.trim(), "%Y-%m-%d").ok()?;
+    let time = if end_of_day { day.and_hms_opt(23, 59, 59)? } else { day.and_hms_opt(0, 0, 0)? };
+    Some(time.and_utc())
+}
+
+/// Whether a chunk passes the request's metadata filters. A `people`,
+/// `organizations` or `places` entry matches case-insensitively against any
+/// name in the chunk's metadata list of that kind, so "alice" finds both
+/// "Alice Smith" and "alice@example.com".
+/// `tags` match a note's tags or their nested children, and `linksTo` keeps
+/// notes whose links or embeds point at that path or note name.
+fn matches_filters(chunk: &Chunk, filters: Option<&QueryFilters>) -> bool {
+    let Some(filters) = filters else {
+        return true;
+    };
+    
+    for (wanted, kind) in [
+        (&filters.people, EntityKind::Person),
+        (&filters.organizations, EntityKind::Organization),
+        (&filters.places, EntityKind::Place),
+    ] {
+        let Some(wanted) = wanted.as_ref().filter(|wanted| !wanted.is_empty()) else {
+            continue;
+        };
+        let known = string_list(chunk, kind.metadata_key());
+        let found = wanted
+            .iter()
+            .map(|name| name.trim().to_lowercase())
+            .any(|name| known.iter().any(|known| known.contains(&name)));
+        if !found {
+            return false;
+        }
+    }
+    
+    if let Some(tags) = filters.tags.as_ref().filter(|tags| !tags.is_empty()) {
+        let known = string_list(chunk, "tags");
+        let found = tags
+            .iter()
+            .map(|tag| tag.trim().trim_start_matches('#').to_lowercase())
+            .any(|tag| {
+                known
+                    .iter()
+                    .any(|known| *known == tag || known.starts_with(&format!("{}/", tag)))
+            });
+        if !found {
+            return false;
+        }
+    }
+    
+    if let Some(note) = filters.links_to.as_deref().map(str::trim).filter(|note| !note.is_empty()) {
+        let note = note.to_lowercase();
+        let name = note.trim_end_matches(".md");
+        let logseq_name = name.replace('/', "___");
+        let found = string_list(chunk, "links")
+            .into_iter()
+            .chain(string_list(chunk, "embeds"))
+            .filter(|target| !target.contains("://"))
+            .any(|target| {
+                let stem = Path::new(&target)
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                target == note || stem == name || stem == logseq_name
+            });
+        if !found {
+            return false;
+        }
+    }
+    
+    true
+}
+
+/// A list of strings from chunk metadata, lowercased.
+fn string_list(chunk: &Chunk, key: &str) -> Vec<String> {
+    chunk
+        .metadata
+        .get(key)
+        .and_then(|value| value.as_array())
+        .map(|items| {
+            items
+                .iter()
+                .filter_map(|item| item.as_str())
+                .map(str::to_lowercase)
+                .collect()
+        })
+        .unwrap_or_default()
+}
@@ -1,5 +1,5 @@
 use axum::{
-    extract::{Multipart, Path, State},
+    extract::{Multipart, Path, Query, State},
     http::{HeaderMap, StatusCode},
     response::{sse::Event, Sse},
     routing::{get, post},
@@ -12,12 +12,15 @@ use std::time::Duration;
 use tokio::sync::broadcast;
 use tower_http::cors::{Any, CorsLayer};
 use tracing::{error, info};
+use serde::Deserialize;
 use types::{
-    ApiError, IngestTextRequest, QueryRequest, QueryResponse, StatusResponse,
+    ApiError, Backlink, BacklinksResponse, ContextMode, EntityKind, IngestTextRequest, Mention,
+    MentionsResponse, QueryRequest, QueryResponse, StatusResponse,
 };
 use utoipa::OpenApi;
 use utoipa_swagger_ui::SwaggerUi;
 
+use ingest::IngestPipeline;
 use models::ModelManager;
 use retrieval::HybridIndex;
 use storage::StorageManager;
@@ -27,6 +30,9 @@ pub struct AppState {
     pub index: Arc<HybridIndex>,
     pub models: Arc<ModelManager>,
     pub storage: Arc<StorageManager>,
+    /// Built once from the loaded config, so uploads follow its privacy
+    /// and chunking rules
+    pub pipeline: Arc<IngestPipeline>,
     pub progress_tx: broadcast::Sender<String>,
 }
 
@@ -37,9 +43,12 @@ pub struct AppState {
         ingest_file,
         ingest_text,
         status,
+        backlinks,
+        mentions,
     ),
     components(
-        schemas(QueryRequest, QueryResponse, IngestTextRequest, StatusResponse)
+        schemas(QueryRequest, ContextMode, QueryResponse, IngestTextRequest, StatusResponse, BacklinksResponse, Backlink,
+            MentionsResponse, Mention, EntityKind)
     ),
     tags(
         (name = "search", description = "Search API"),
@@ -60,6 +69,8 @@ pub fn create_app(state: AppState) -> Router {
         .route("/api/ingest/file", post(ingest_file))
         .route("/api/ingest/text", post(ingest_text))
         .route("/api/status", get(status))
+        .route("/api/backlinks", get(backlinks))
+        .route("/api/mentions", get(mentions))
         .route("/ws/progress", get(progress_websocket))
         .merge(SwaggerUi::new("/docs").url("/api-docs/openapi.json", ApiDoc::openapi()))
         .layer(cors)
@@ -126,21 +137,29 @@ async fn ingest_file(
         let data = field.bytes().await
             .map_err(|e| ApiError::bad_request(format!("Failed to read file: {}", e)))?;
         
-        // Save to temporary file
-        let temp_path = std::env::temp_dir().join(&filename);
-        tokio::fs::write(&temp_path, &data).await
-            .map_err(|e| ApiError::internal(format!("Failed to save file: {}", e)))?;
+        // Only the name is kept; a client could send `../../etc/passwd`
+        let filename = std::path::Path::new(&filename)
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .ok_or_else(|| ApiError::bad_request("Invalid filename"))?;
         
-        // Process the file
-        let result = ingest::IngestPipeline::new(types::AppConfig::default())
-            .map_err(|e| ApiError::internal(format!("Failed to create ingest pipeline: {}", e)))?
-            .ingest_path(&temp_path).await
-            .map_err(|e| ApiError::internal(format!("Failed to ingest file: {}", e)))?;
+        // Save to a directory of its own: documents are keyed by path, so
+        // uploads sharing a name must not replace each other
+        let temp_dir = std::env::temp_dir().join(format!("myai-upload-{}", uuid::Uuid::new_v4()));
+        let temp_path = temp_dir.join(&filename);
+        tokio::fs::create_dir_all(&temp_dir).await
+            .map_err(|e| ApiError::internal(format!("Failed to save file: {}", e)))?;
+        let result = match tokio::fs::write(&temp_path, &data).await {
+            // Process the file
+            Ok(()) => state.pipeline.ingest_path(&temp_path).await
+                .map_err(|e| ApiError::internal(format!("Failed to ingest file: {}", e))),
+            Err(e) => Err(ApiError::internal(format!("Failed to save file: {}", e))),
+        };
         
         // Clean up temp file
-        let _ = tokio::fs::remove_file(&temp_path).await;
+        let _ = tokio::fs::remove_dir_all(&temp_dir).await;
         
-        return Ok(Json(result));
+        return result.map(Json);
     }
     
     Err(ApiError::bad_request("No file provided"))
@@ -163,9 +182,7 @@ async fn ingest_text(
 ) -> Result<Json<types::IngestResult>, ApiError> {
     info!("Processing text ingest: {}", request.title.as_deref().unwrap_or("Untitled"));
     
-    let result = ingest::IngestPipeline::new(types::AppConfig::default())
-        .map_err(|e| ApiError::internal(format!("Failed to create ingest pipeline: {}", e)))?
-        .ingest_text(&request.text, request.title, request.source).await
+    let result = state.pipeline.ingest_text(&request.text, request.title, request.source).await
         .map_err(|e| ApiError::internal(format!("Failed to ingest text: {}", e)))?;
     
     Ok(Json(result))
@@ -196,6 +213,100 @@ async fn status(
     Ok(Json(response))
 }
 
+#[derive(Deserialize)]
+struct BacklinksParams {
+    note: String,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/backlinks",
+    params(
+        ("note" = String, Query, description = "Path or name of the note, e.g. `Project Alpha`")
+    ),
+    responses(
+        (status = 200, description = "Documents linking to the note", body = BacklinksResponse),
+        (status = 400, description = "Bad request"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "search"
+)]
+async fn backlinks(
+    State(state): State<AppState>,
+    Query(params): Query<BacklinksParams>,
+) -> Result<Json<BacklinksResponse>, ApiError> {
+    if params.note.trim().is_empty() {
+        return Err(ApiError::bad_request("No note given"));
+    }
+    
+    let docs = state.storage.backlinks(&params.note).await
+        .map_err(|e| ApiError::internal(format!("Failed to find backlinks: {}", e)))?;
+    
+    let backlinks = docs
+        .into_iter()
+        .map(|doc| Backlink {
+            doc_id: doc.id,
+            title: doc.title,
+            path: doc.path,
+            modified_at: doc.modified_at,
+        })
+        .collect();
+    
+    Ok(Json(BacklinksResponse {
+        note: params.note,
+        backlinks,
+    }))
+}
+
+#[derive(Deserialize)]
+struct MentionsParams {
+    name: String,
+    kind: Option<EntityKind>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/mentions",
+    params(
+        ("name" = String, Query, description = "Name, address or date to look for, e.g. `Acme Corp`"),
+        ("kind" = Option<EntityKind>, Query, description = "Only entities of this kind")
+    ),
+    responses(
+        (status = 200, description = "Documents mentioning the entity", body = MentionsResponse),
+        (status = 400, description = "Bad request"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "search"
+)]
+async fn mentions(
+    State(state): State<AppState>,
+    Query(params): Query<MentionsParams>,
+) -> Result<Json<MentionsResponse>, ApiError> {
+    if params.name.trim().is_empty() {
+        return Err(ApiError::bad_request("No name given"));
+    }
+    
+    let docs = state.storage.mentions(&params.name, params.kind).await
+        .map_err(|e| ApiError::internal(format!("Failed to find mentions: {}", e)))?;
+    
+    let documents = docs
+        .into_iter()
+        .map(|(doc, chunks)| Mention {
+            doc_id: doc.id,
+            title: doc.title,
+            path: doc.path,
+            modified_at: doc.modified_at,
+            chunks,
+        })
+        .collect();
+    
+    Ok(Json(MentionsResponse {
+        name: params.name,
+        kind: params.kind,
+        documents,
+    }))
+}
+
 async fn progress_websocket(
     State(state): State<AppState>,
 ) -> impl Stream<Item = Result<Event, Infallible>> {
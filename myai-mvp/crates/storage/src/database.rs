@@ -6,7 +6,7 @@ use std::collections::HashMap;
 use std::path::Path;
 use tokio::sync::Mutex;
 use tracing::info;
-use types::{Chunk, Document};
+use types::{Chunk, Document, EntityKind, ParentChunk};
 
 pub struct Database {
     conn: Mutex<Connection>,
@@ -28,7 +28,10 @@ impl Database {
                 title TEXT NOT NULL,
                 modified_at INTEGER NOT NULL,
                 source TEXT NOT NULL,
-                mime TEXT NOT NULL
+                mime TEXT NOT NULL,
+                size INTEGER NOT NULL DEFAULT 0,
+                content_hash TEXT NOT NULL DEFAULT '',
+                meta TEXT NOT NULL DEFAULT '{}'
             );
             
             CREATE TABLE IF NOT EXISTS chunks (
@@ -38,24 +41,61 @@ impl Database {
                 ts INTEGER NOT NULL,
                 meta TEXT NOT NULL,
                 vec BLOB,
+                parent_id TEXT,
+                position INTEGER NOT NULL DEFAULT 0,
                 FOREIGN KEY (doc_id) REFERENCES documents (id)
             );
             
             CREATE INDEX IF NOT EXISTS idx_chunks_doc_id ON chunks (doc_id);
             CREATE INDEX IF NOT EXISTS idx_chunks_ts ON chunks (ts);
+            
+            CREATE TABLE IF NOT EXISTS parents (
+                id TEXT PRIMARY KEY,
+                doc_id TEXT NOT NULL,
+                text TEXT NOT NULL,
+                FOREIGN KEY (doc_id) REFERENCES documents (id)
+            );
+            
+            CREATE INDEX IF NOT EXISTS idx_parents_doc_id ON parents (doc_id);
+            
+            CREATE TABLE IF NOT EXISTS links (
+                source_id TEXT NOT NULL,
+                target TEXT NOT NULL,
+                embed INTEGER NOT NULL DEFAULT 0,
+                PRIMARY KEY (source_id, target, embed)
+            );
+            
+            CREATE INDEX IF NOT EXISTS idx_links_target ON links (target);
+            
+            CREATE TABLE IF NOT EXISTS entities (
+                chunk_id TEXT NOT NULL,
+                doc_id TEXT NOT NULL,
+                kind TEXT NOT NULL,
+                name TEXT NOT NULL COLLATE NOCASE,
+                PRIMARY KEY (chunk_id, kind, name)
+            );
+            
+            CREATE INDEX IF NOT EXISTS idx_entities_name ON entities (name, kind);
+            CREATE INDEX IF NOT EXISTS idx_entities_doc_id ON entities (doc_id);
             "#,
         )?;
         
+        migrate(&conn)?;
+        
         info!("Database initialized successfully");
         Ok(Self {
             conn: Mutex::new(conn),
         })
     }
     
+    /// Saves a document along with the notes it links to (its `links` and
+    /// `embeds` metadata), replacing the links it had before.
     pub async fn save_document(&self, doc: &Document) -> Result<()> {
-        let conn = self.conn.lock().await;
-        conn.execute(
-            "INSERT OR REPLACE INTO documents (id, path, title, modified_at, source, mime) VALUES (?, ?, ?, ?, ?, ?)",
+        let mut conn = self.conn.lock().await;
+        let tx = conn.transaction()?;
+        let meta_json = serde_json::to_string(&doc.metadata)?;
+        tx.execute(
+            "INSERT OR REPLACE INTO documents (id, path, title, modified_at, source, mime, size, content_hash, meta) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
             (
                 &doc.id,
                 &doc.path,
@@ -63,13 +103,85 @@ impl Database {
                 doc.modified_at.timestamp(),
                 &doc.source,
                 &doc.mime,
+                doc.size as i64,
+                &doc.content_hash,
+                &meta_json,
             ),
         )?;
+        
+        tx.execute("DELETE FROM links WHERE source_id = ?", [&doc.id])?;
+        for (key, embed) in [("links", false), ("embeds", true)] {
+            let targets = doc.metadata.get(key).and_then(Value::as_array);
+            // Web pages keep their outbound URLs as links too; only files count here
+            for target in targets.into_iter().flatten().filter_map(Value::as_str).filter(|target| !target.contains("://")) {
+                tx.execute(
+                    "INSERT OR IGNORE INTO links (source_id, target, embed) VALUES (?, ?, ?)",
+                    (&doc.id, target, embed),
+                )?;
+            }
+        }
+        
+        tx.commit()?;
         Ok(())
     }
     
-    pub async fn upsert_chunk(&self, chunk: &Chunk) -> Result<()> {
+    /// Documents linking to or embedding `note`: a path, or a note name
+    /// (`Alpha`, `project/alpha`) matched against the file name of link
+    /// targets regardless of case.
+    pub async fn backlinks(&self, note: &str) -> Result<Vec<Document>> {
+        let conn = self.conn.lock().await;
+        let sep = std::path::MAIN_SEPARATOR;
+        let name = note.trim().trim_end_matches(".md");
+        // Logseq stores namespaced pages as project___alpha.md
+        let suffixes = [
+            format!("{}{}.md", sep, name),
+            format!("{}{}.md", sep, name.replace('/', "___")),
+        ];
+        let mut stmt = conn.prepare(
+            "SELECT * FROM documents WHERE id IN (
+                SELECT source_id FROM links
+                WHERE target = ?1
+                   OR substr(target, -length(?2)) = ?2 COLLATE NOCASE
+                   OR substr(target, -length(?3)) = ?3 COLLATE NOCASE
+            ) ORDER BY modified_at DESC"
+        )?;
+        let mut rows = stmt.query((note, &suffixes[0], &suffixes[1]))?;
+        let mut docs = Vec::new();
+        
+        while let Some(row) = rows.next()? {
+            docs.push(self.row_to_document(row)?);
+        }
+        
+        Ok(docs)
+    }
+    
+    /// Documents mentioning the entity `name` (in any case), of one kind or
+    /// any, with how many of their chunks mention it. Most mentions first.
+    pub async fn mentions(&self, name: &str, kind: Option<EntityKind>) -> Result<Vec<(Document, u32)>> {
         let conn = self.conn.lock().await;
+        let mut stmt = conn.prepare(
+            "SELECT documents.*, COUNT(DISTINCT entities.chunk_id) AS mentions
+             FROM entities JOIN documents ON documents.id = entities.doc_id
+             WHERE entities.name = ?1 AND (?2 IS NULL OR entities.kind = ?2)
+             GROUP BY documents.id
+             ORDER BY mentions DESC, documents.modified_at DESC"
+        )?;
+        let mut rows = stmt.query((name.trim(), kind.map(|kind| kind.name())))?;
+        let mut docs = Vec::new();
+        
+        while let Some(row) = rows.next()? {
+            docs.push((self.row_to_document(row)?, row.get(9)?));
+        }
+        
+        Ok(docs)
+    }
+    
+    /// Saves a chunk along with the entities it mentions (its `people`,
+    /// `organizations`, `places` and `dates` metadata), replacing the ones
+    /// it had before.
+    pub async fn upsert_chunk(&self, chunk: &Chunk) -> Result<()> {
+        let mut conn = self.conn.lock().await;
+        let tx = conn.transaction()?;
         
         let meta_json = serde_json::to_string(&chunk.metadata)?;
         let vec_blob = chunk.embedding.as_ref().map(|v| {
@@ -77,8 +189,8 @@ impl Database {
             bytes
         });
         
-        conn.execute(
-            "INSERT OR REPLACE INTO chunks (id, doc_id, text, ts, meta, vec) VALUES (?, ?, ?, ?, ?, ?)",
+        tx.execute(
+            "INSERT OR REPLACE INTO chunks (id, doc_id, text, ts, meta, vec, parent_id, position) VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
             (
                 &chunk.id,
                 &chunk.doc_id,
@@ -86,11 +198,88 @@ impl Database {
                 chunk.created_at.timestamp(),
                 &meta_json,
                 vec_blob.as_deref(),
+                &chunk.parent_id,
+                chunk.position,
             ),
         )?;
+        
+        tx.execute("DELETE FROM entities WHERE chunk_id = ?", [&chunk.id])?;
+        for kind in EntityKind::ALL {
+            let names = chunk.metadata.get(kind.metadata_key()).and_then(Value::as_array);
+            for name in names.into_iter().flatten().filter_map(Value::as_str) {
+                tx.execute(
+                    "INSERT OR IGNORE INTO entities (chunk_id, doc_id, kind, name) VALUES (?, ?, ?, ?)",
+                    (&chunk.id, &chunk.doc_id, kind.name(), name),
+                )?;
+            }
+        }
+        
+        tx.commit()?;
+        Ok(())
+    }
+    
+    /// Moves a stored chunk to a new parent and position, for a chunk kept
+    /// as it was while the text around it changed.
+    pub async fn place_chunk(&self, chunk_id: &str, parent_id: Option<&str>, position: u32) -> Result<()> {
+        let conn = self.conn.lock().await;
+        conn.execute(
+            "UPDATE chunks SET parent_id = ?, position = ? WHERE id = ?",
+            (parent_id, position, chunk_id),
+        )?;
         Ok(())
     }
     
+    /// Replaces the parent sections stored for a document.
+    pub async fn save_parents(&self, doc_id: &str, parents: &[ParentChunk]) -> Result<()> {
+        let mut conn = self.conn.lock().await;
+        let tx = conn.transaction()?;
+        tx.execute("DELETE FROM parents WHERE doc_id = ?", [doc_id])?;
+        for parent in parents {
+            tx.execute(
+                "INSERT OR REPLACE INTO parents (id, doc_id, text) VALUES (?, ?, ?)",
+                (&parent.id, &parent.doc_id, &parent.text),
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+    
+    pub async fn get_parents_by_ids(&self, parent_ids: &[String]) -> Result<Vec<ParentChunk>> {
+        if parent_ids.is_empty() {
+            return Ok(vec![]);
+        }
+        
+        let conn = self.conn.lock().await;
+        let placeholders = parent_ids.iter().map(|_| "?").collect::<Vec<_>>().join(",");
+        let query = format!("SELECT id, doc_id, text FROM parents WHERE id IN ({})", placeholders);
+        
+        let mut stmt = conn.prepare(&query)?;
+        let rows = stmt.query_map(rusqlite::params_from_iter(parent_ids), |row| {
+            Ok(ParentChunk {
+                id: row.get(0)?,
+                doc_id: row.get(1)?,
+                text: row.get(2)?,
+            })
+        })?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+    
+    /// Chunks of a document from position `from` to `to` inclusive, in order.
+    pub async fn get_chunk_range(&self, doc_id: &str, from: u32, to: u32) -> Result<Vec<Chunk>> {
+        let conn = self.conn.lock().await;
+        let mut stmt = conn.prepare(
+            "SELECT * FROM chunks WHERE doc_id = ? AND position BETWEEN ? AND ? ORDER BY position"
+        )?;
+        let mut rows = stmt.query((doc_id, from, to))?;
+        
+        let mut chunks = Vec::new();
+        while let Some(row) = rows.next()? {
+            chunks.push(self.row_to_chunk(row)?);
+        }
+        
+        Ok(chunks)
+    }
+    
     pub async fn get_chunks_by_ids(&self, chunk_ids: &[String]) -> Result<Vec<Chunk>> {
         if chunk_ids.is_empty() {
             return Ok(vec![]);
@@ -111,6 +300,78 @@ impl Database {
         Ok(chunks)
     }
     
+    pub async fn get_chunks_by_doc(&self, doc_id: &str) -> Result<Vec<Chunk>> {
+        let conn = self.conn.lock().await;
+        let mut stmt = conn.prepare("SELECT * FROM chunks WHERE doc_id = ?")?;
+        let mut rows = stmt.query([doc_id])?;
+        
+        let mut chunks = Vec::new();
+        while let Some(row) = rows.next()? {
+            chunks.push(self.row_to_chunk(row)?);
+        }
+        
+        Ok(chunks)
+    }
+    
+    pub async fn delete_chunks(&self, chunk_ids: &[String]) -> Result<()> {
+        let mut conn = self.conn.lock().await;
+        let tx = conn.transaction()?;
+        for chunk_id in chunk_ids {
+            tx.execute("DELETE FROM chunks WHERE id = ?", [chunk_id])?;
+            tx.execute("DELETE FROM entities WHERE chunk_id = ?", [chunk_id])?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+    
+    pub async fn get_document_by_path(&self, path: &str) -> Result<Option<Document>> {
+        let conn = self.conn.lock().await;
+        let mut stmt = conn.prepare("SELECT * FROM documents WHERE path = ? LIMIT 1")?;
+        let mut rows = stmt.query([path])?;
+        
+        match rows.next()? {
+            Some(row) => Ok(Some(self.row_to_document(row)?)),
+            None => Ok(None),
+        }
+    }
+    
+    /// The document at `path` plus any documents stored inside that file.
+    pub async fn get_documents_by_file(&self, path: &str) -> Result<Vec<Document>> {
+        let conn = self.conn.lock().await;
+        let mut stmt = conn.prepare(
+            "SELECT * FROM documents WHERE path = ?1 OR substr(path, 1, length(?2)) = ?2"
+        )?;
+        let mut rows = stmt.query((path, &part_prefix(path)))?;
+        let mut docs = Vec::new();
+        
+        while let Some(row) = rows.next()? {
+            docs.push(self.row_to_document(row)?);
+        }
+        
+        Ok(docs)
+    }
+    
+    /// Files with documents at or below `root`. Parts of an archive or
+    /// mailbox are listed once, as the file that holds them.
+    pub async fn get_file_paths_under(&self, root: &str) -> Result<Vec<String>> {
+        let conn = self.conn.lock().await;
+        let mut stmt = conn.prepare(
+            "SELECT path FROM documents WHERE path = ?1 OR substr(path, 1, length(?2)) = ?2"
+        )?;
+        let rows = stmt.query_map((root, &dir_prefix(root)), |row| row.get::<_, String>(0))?;
+        let mut paths = Vec::new();
+        
+        for row in rows {
+            let path = row?;
+            let file = path.split(types::PART_SEPARATOR).next().unwrap_or(&path).to_string();
+            if !paths.contains(&file) {
+                paths.push(file);
+            }
+        }
+        
+        Ok(paths)
+    }
+    
     pub async fn list_recent_docs(&self, limit: usize) -> Result<Vec<Document>> {
         let conn = self.conn.lock().await;
         let mut stmt = conn.prepare(
@@ -127,6 +388,87 @@ impl Database {
         Ok(docs)
     }
     
+    /// Deletes the document stored at `path`, or every document below it when
+    /// `path` is a directory or a file holding several documents (an mbox).
+    /// Returns the number of documents removed and the ids of their chunks.
+    pub async fn delete_documents_by_path(&self, path: &str) -> Result<(usize, Vec<String>)> {
+        let mut conn = self.conn.lock().await;
+        let tx = conn.transaction()?;
+        let prefix = dir_prefix(path);
+        let parts = part_prefix(path);
+        
+        let doc_ids: Vec<String> = {
+            let mut stmt = tx.prepare(
+                "SELECT id FROM documents WHERE path = ?1 OR substr(path, 1, length(?2)) = ?2 OR substr(path, 1, length(?3)) = ?3"
+            )?;
+            let rows = stmt.query_map((path, &prefix, &parts), |row| row.get(0))?;
+            rows.collect::<rusqlite::Result<_>>()?
+        };
+        
+        let mut chunk_ids = Vec::new();
+        for doc_id in &doc_ids {
+            let mut stmt = tx.prepare("SELECT id FROM chunks WHERE doc_id = ?")?;
+            let rows = stmt.query_map([doc_id], |row| row.get::<_, String>(0))?;
+            for id in rows {
+                chunk_ids.push(id?);
+            }
+            
+            tx.execute("DELETE FROM chunks WHERE doc_id = ?", [doc_id])?;
+            tx.execute("DELETE FROM parents WHERE doc_id = ?", [doc_id])?;
+            tx.execute("DELETE FROM links WHERE source_id = ?", [doc_id])?;
+            tx.execute("DELETE FROM entities WHERE doc_id = ?", [doc_id])?;
+            tx.execute("DELETE FROM documents WHERE id = ?", [doc_id])?;
+        }
+        
+        tx.commit()?;
+        Ok((doc_ids.len(), chunk_ids))
+    }
+    
+    /// Moves the document at `from` (or every document below it) to `to`
+    /// without touching its chunks, and points links to it at the new path.
+    /// A document titled by its file name is retitled with the new one.
+    /// Returns the number of documents updated.
+    pub async fn rename_path(&self, from: &str, to: &str) -> Result<usize> {
+        let mut conn = self.conn.lock().await;
+        let tx = conn.transaction()?;
+        
+        // A title taken from the file's own metadata stays; one that was
+        // just the file name follows it, in the chunks search hits are
+        // titled from too
+        let file_name = |path: &str| {
+            Path::new(path)
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_else(|| path.to_string())
+        };
+        tx.execute(
+            "UPDATE chunks SET meta = json_set(meta, '$.title', ?1)
+             WHERE doc_id IN (SELECT id FROM documents WHERE path = ?2 AND title = ?3)",
+            (file_name(to), from, file_name(from)),
+        )?;
+        tx.execute(
+            "UPDATE documents SET title = ?1 WHERE path = ?2 AND title = ?3",
+            (file_name(to), from, file_name(from)),
+        )?;
+        let mut updated = tx.execute("UPDATE documents SET path = ?1 WHERE path = ?2", (to, from))?;
+        
+        for prefix in [dir_prefix(from), part_prefix(from)] {
+            updated += tx.execute(
+                "UPDATE documents SET path = ?1 || substr(path, length(?2) + 1) WHERE substr(path, 1, length(?3)) = ?3",
+                (to, from, &prefix),
+            )?;
+        }
+        
+        tx.execute("UPDATE links SET target = ?1 WHERE target = ?2", (to, from))?;
+        tx.execute(
+            "UPDATE links SET target = ?1 || substr(target, length(?2) + 1) WHERE substr(target, 1, length(?3)) = ?3",
+            (to, from, &dir_prefix(from)),
+        )?;
+        
+        tx.commit()?;
+        Ok(updated)
+    }
+    
     pub async fn count_documents(&self) -> Result<u64> {
         let conn = self.conn.lock().await;
         let count: i64 = conn.query_row("SELECT COUNT(*) FROM documents", [], |row| row.get(0))?;
@@ -139,6 +481,22 @@ impl Database {
         Ok(count as u64)
     }
     
+    /// Ids and embeddings of every embedded chunk, for rebuilding the vector
+    /// index at startup.
+    pub async fn get_embeddings(&self) -> Result<Vec<(String, Vec<f32>)>> {
+        let conn = self.conn.lock().await;
+        let mut stmt = conn.prepare("SELECT id, vec FROM chunks WHERE vec IS NOT NULL")?;
+        let rows = stmt.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, Vec<u8>>(1)?)))?;
+        let mut embeddings = Vec::new();
+        
+        for row in rows {
+            let (id, bytes) = row?;
+            embeddings.push((id, vector_from_blob(&bytes)));
+        }
+        
+        Ok(embeddings)
+    }
+    
     fn row_to_chunk(&self, row: &Row) -> Result<Chunk> {
         let id: String = row.get(0)?;
         let doc_id: String = row.get(1)?;
@@ -146,14 +504,11 @@ impl Database {
         let ts: i64 = row.get(3)?;
         let meta_json: String = row.get(4)?;
         let vec_blob: Option<Vec<u8>> = row.get(5)?;
+        let parent_id: Option<String> = row.get(6)?;
+        let position: u32 = row.get(7)?;
         
         let metadata: HashMap<String, Value> = serde_json::from_str(&meta_json)?;
-        let embedding = vec_blob.map(|bytes| {
-            bytes
-                .chunks_exact(4)
-                .map(|chunk| f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
-                .collect()
-        });
+        let embedding = vec_blob.map(|bytes| vector_from_blob(&bytes));
         
         Ok(Chunk {
             id,
@@ -162,6 +517,8 @@ impl Database {
             embedding,
             metadata,
             created_at: DateTime::from_timestamp(ts, 0).unwrap_or_else(|| Utc::now()),
+            parent_id,
+            position,
         })
     }
     
@@ -172,6 +529,11 @@ impl Database {
         let modified_at: i64 = row.get(3)?;
         let source: String = row.get(4)?;
         let mime: String = row.get(5)?;
+        let size: i64 = row.get(6)?;
+        let content_hash: String = row.get(7)?;
+        let meta_json: String = row.get(8)?;
+        
+        let metadata: HashMap<String, Value> = serde_json::from_str(&meta_json)?;
         
         Ok(Document {
             id,
@@ -180,6 +542,51 @@ impl Database {
             modified_at: DateTime::from_timestamp(modified_at, 0).unwrap_or_else(|| Utc::now()),
             source,
             mime,
+            size: size as u64,
+            content_hash,
+            metadata,
         })
     }
 }
+
+/// Brings databases created by older versions up to the current schema.
+fn migrate(conn: &Connection) -> Result<()> {
+    add_column(conn, "documents", "size", "INTEGER NOT NULL DEFAULT 0")?;
+    add_column(conn, "documents", "content_hash", "TEXT NOT NULL DEFAULT ''")?;
+    add_column(conn, "documents", "meta", "TEXT NOT NULL DEFAULT '{}'")?;
+    add_column(conn, "chunks", "parent_id", "TEXT")?;
+    add_column(conn, "chunks", "position", "INTEGER NOT NULL DEFAULT 0")?;
+    
+    conn.execute_batch(
+        "CREATE INDEX IF NOT EXISTS idx_documents_path ON documents (path);
+         CREATE INDEX IF NOT EXISTS idx_chunks_position ON chunks (doc_id, position);"
+    )?;
+    Ok(())
+}
+
+fn add_column(conn: &Connection, table: &str, column: &str, definition: &str) -> Result<()> {
+    let exists = conn
+        .prepare(&format!("SELECT 1 FROM pragma_table_info('{}') WHERE name = ?", table))?
+        .exists([column])?;
+    
+    if !exists {
+        info!("Adding column {}.{}", table, column);
+        conn.execute(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition), [])?;
+    }
+    Ok(())
+}
+
+fn vector_from_blob(bytes: &[u8]) -> Vec<f32> {
+    bytes
+        .chunks_exact(4)
+        .map(|chunk| f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+        .collect()
+}
+
+fn dir_prefix(path: &str) -> String {
+    format!("{}{}", path.trim_end_matches(std::path::MAIN_SEPARATOR), std::path::MAIN_SEPARATOR)
+}
+
+fn part_prefix(path: &str) -> String {
+    format!("{}{}", path, types::PART_SEPARATOR)
+}
@@ -1,10 +1,18 @@
 use anyhow::Result;
 use hnsw_rs::{Hnsw, Searcher};
+use std::future::Future;
 use std::path::Path;
 use std::sync::Arc;
 use tokio::sync::Mutex;
 use tracing::info;
 
+/// MiniLM-L6-v2 dimension
+const DIMENSION: usize = 384;
+
+/// Removed points are rebuilt away once there are at least this many and
+/// they outnumber the live ones
+const MIN_REBUILD_TOMBSTONES: usize = 1000;
+
 pub struct HnswStore {
     hnsw: Arc<Mutex<Hnsw<f32, u32>>>,
     searcher: Arc<Mutex<Searcher<f32, u32>>>,
@@ -18,32 +26,35 @@ impl HnswStore {
         let hnsw_path = Path::new(data_dir).join("hnsw");
         info!("Initializing HNSW index at {:?}", hnsw_path);
         
+        let hnsw = Self::empty_graph();
+        let searcher = Searcher::default();
+        
+        info!("HNSW index initialized successfully (dim: {})", DIMENSION);
+        
+        Ok(Self {
+            hnsw: Arc::new(Mutex::new(hnsw)),
+            searcher: Arc::new(Mutex::new(searcher)),
+            id_to_index: Arc::new(Mutex::new(std::collections::HashMap::new())),
+            index_to_id: Arc::new(Mutex::new(std::collections::HashMap::new())),
+            next_index: Arc::new(Mutex::new(0)),
+        })
+    }
+    
+    fn empty_graph() -> Hnsw<f32, u32> {
         // HNSW parameters
-        let dim = 384; // MiniLM-L6-v2 dimension
+        let dim = DIMENSION;
         let max_nb_connection = 16;
         let nb_layer = 16;
         let ef_c = 100;
         let ef_s = 50;
         
-        let hnsw = Hnsw::<f32, u32>::new(
+        Hnsw::<f32, u32>::new(
             max_nb_connection,
             nb_layer,
             dim,
             ef_c,
             ef_s,
-        );
-        
-        let searcher = Searcher::default();
-        
-        info!("HNSW index initialized successfully (dim: {})", dim);
-        
-        Ok(Self {
-            hnsw: Arc::new(Mutex::new(hnsw)),
-            searcher: Arc::new(Mutex::new(searcher)),
-            id_to_index: Arc::new(Mutex::new(std::collections::HashMap::new())),
-            index_to_id: Arc::new(Mutex::new(std::collections::HashMap::new())),
-            next_index: Arc::new(Mutex::new(0)),
-        })
+        )
     }
     
     pub async fn add_vector(&self, id: &str, embedding: &[f32]) -> Result<()> {
@@ -69,12 +80,72 @@ impl HnswStore {
         Ok(())
     }
     
+    /// Drops a vector from search results. HNSW graphs don't support removal,
+    /// so the point stays in the graph as a tombstone until the index is
+    /// rebuilt.
+    pub async fn remove_vector(&self, id: &str) -> Result<()> {
+        let mut id_to_index = self.id_to_index.lock().await;
+        let mut index_to_id = self.index_to_id.lock().await;
+        
+        if let Some(index) = id_to_index.remove(id) {
+            index_to_id.remove(&index);
+        }
+        
+        Ok(())
+    }
+    
+    /// Whether enough removed points have piled up that a rebuild is due.
+    pub async fn needs_rebuild(&self) -> bool {
+        let index_to_id = self.index_to_id.lock().await;
+        let next_index = self.next_index.lock().await;
+        let tombstones = *next_index as usize - index_to_id.len();
+        
+        tombstones >= MIN_REBUILD_TOMBSTONES && tombstones > index_to_id.len()
+    }
+    
+    /// Replaces the graph with one holding only the vectors `load` returns.
+    /// The index stays locked while they load, so vectors added or removed
+    /// meanwhile are applied to the new graph afterwards.
+    pub async fn rebuild<F>(&self, load: F) -> Result<usize>
+    where
+        F: Future<Output = Result<Vec<(String, Vec<f32>)>>>,
+    {
+        let mut hnsw = self.hnsw.lock().await;
+        let mut id_to_index = self.id_to_index.lock().await;
+        let mut index_to_id = self.index_to_id.lock().await;
+        let mut next_index = self.next_index.lock().await;
+        
+        let vectors = load.await?;
+        let removed = *next_index as usize - index_to_id.len();
+        
+        *hnsw = Self::empty_graph();
+        id_to_index.clear();
+        index_to_id.clear();
+        *next_index = 0;
+        
+        for (id, embedding) in &vectors {
+            let index = *next_index;
+            hnsw.insert_vector(embedding, index)?;
+            
+            id_to_index.insert(id.clone(), index);
+            index_to_id.insert(index, id.clone());
+            *next_index += 1;
+        }
+        
+        info!("Rebuilt the vector index with {} vectors, dropping {} removed ones", vectors.len(), removed);
+        Ok(vectors.len())
+    }
+    
     pub async fn search(&self, query_embedding: &[f32], limit: usize) -> Result<Vec<(String, f32)>> {
         let hnsw = self.hnsw.lock().await;
         let searcher = self.searcher.lock().await;
         let index_to_id = self.index_to_id.lock().await;
+        let next_index = self.next_index.lock().await;
         
-        let search_result = searcher.search(&hnsw, query_embedding, limit, None)?;
+        // Removed points are still in the graph and can take any of the
+        // nearest slots, so fetch enough extra to fill `limit` with live ones
+        let tombstones = *next_index as usize - index_to_id.len();
+        let search_result = searcher.search(&hnsw, query_embedding, limit + tombstones, None)?;
         
         let mut results = Vec::new();
         for (index, distance) in search_result {
@@ -84,6 +155,7 @@ impl HnswStore {
                 results.push((id.clone(), similarity));
             }
         }
+        results.truncate(limit);
         
         Ok(results)
     }
@@ -12,12 +12,13 @@ use tantivy::{
     Document, Index, IndexReader, IndexWriter, Term,
 };
 use tracing::{info, warn};
-use types::{Chunk, Document as DocType};
+use types::{Chunk, Document as DocType, EntityKind, ParentChunk};
 use uuid::Uuid;
 
 pub mod database;
 pub mod tantivy_store;
 pub mod hnsw_store;
+pub mod tokenizer;
 
 pub use database::Database;
 pub use tantivy_store::TantivyStore;
@@ -38,6 +39,14 @@ impl StorageManager {
         let tantivy = TantivyStore::new(data_dir).await?;
         let hnsw = HnswStore::new(data_dir).await?;
         
+        // The vector index lives in memory only, so refill it from the stored
+        // embeddings; re-ingesting skips unchanged files and wouldn't
+        let embeddings = database.get_embeddings().await?;
+        for (chunk_id, embedding) in &embeddings {
+            hnsw.add_vector(chunk_id, embedding).await?;
+        }
+        info!("Loaded {} vectors into the vector index", embeddings.len());
+        
         info!("Storage manager initialized successfully");
         Ok(Self {
             database,
@@ -62,16 +71,87 @@ impl StorageManager {
         Ok(())
     }
     
+    /// Removes the document at `path`, or all documents below a directory,
+    /// from every store. Returns the number of documents removed.
+    pub async fn delete_documents_by_path(&self, path: &str) -> Result<usize> {
+        let (removed, chunk_ids) = self.database.delete_documents_by_path(path).await?;
+        self.delete_indexed_chunks(&chunk_ids).await?;
+        Ok(removed)
+    }
+    
+    pub async fn delete_chunks(&self, chunk_ids: &[String]) -> Result<()> {
+        self.database.delete_chunks(chunk_ids).await?;
+        self.delete_indexed_chunks(chunk_ids).await
+    }
+    
+    async fn delete_indexed_chunks(&self, chunk_ids: &[String]) -> Result<()> {
+        self.tantivy.delete_chunks(chunk_ids).await?;
+        for chunk_id in chunk_ids {
+            self.hnsw.remove_vector(chunk_id).await?;
+        }
+        
+        // Removed vectors stay in the graph and slow every search down, so
+        // rebuild from the stored embeddings once they outnumber live ones
+        if self.hnsw.needs_rebuild().await {
+            self.hnsw.rebuild(self.database.get_embeddings()).await?;
+        }
+        Ok(())
+    }
+    
+    pub async fn get_document_by_path(&self, path: &str) -> Result<Option<DocType>> {
+        self.database.get_document_by_path(path).await
+    }
+    
+    pub async fn get_documents_by_file(&self, path: &str) -> Result<Vec<DocType>> {
+        self.database.get_documents_by_file(path).await
+    }
+    
+    pub async fn get_file_paths_under(&self, root: &str) -> Result<Vec<String>> {
+        self.database.get_file_paths_under(root).await
+    }
+    
+    pub async fn get_chunks_by_doc(&self, doc_id: &str) -> Result<Vec<Chunk>> {
+        self.database.get_chunks_by_doc(doc_id).await
+    }
+    
+    pub async fn rename_path(&self, from: &str, to: &str) -> Result<usize> {
+        self.database.rename_path(from, to).await
+    }
+    
+    pub async fn backlinks(&self, note: &str) -> Result<Vec<DocType>> {
+        self.database.backlinks(note).await
+    }
+    
+    pub async fn mentions(&self, name: &str, kind: Option<EntityKind>) -> Result<Vec<(DocType, u32)>> {
+        self.database.mentions(name, kind).await
+    }
+    
     pub async fn get_chunks_by_ids(&self, chunk_ids: &[String]) -> Result<Vec<Chunk>> {
         self.database.get_chunks_by_ids(chunk_ids).await
     }
     
+    pub async fn place_chunk(&self, chunk_id: &str, parent_id: Option<&str>, position: u32) -> Result<()> {
+        self.database.place_chunk(chunk_id, parent_id, position).await
+    }
+    
+    pub async fn save_parents(&self, doc_id: &str, parents: &[ParentChunk]) -> Result<()> {
+        self.database.save_parents(doc_id, parents).await
+    }
+    
+    pub async fn get_parents_by_ids(&self, parent_ids: &[String]) -> Result<Vec<ParentChunk>> {
+        self.database.get_parents_by_ids(parent_ids).await
+    }
+    
+    pub async fn get_chunk_range(&self, doc_id: &str, from: u32, to: u32) -> Result<Vec<Chunk>> {
+        self.database.get_chunk_range(doc_id, from, to).await
+    }
+    
     pub async fn list_recent_docs(&self, limit: usize) -> Result<Vec<DocType>> {
         self.database.list_recent_docs(limit).await
     }
     
-    pub async fn search_bm25(&self, query: &str, limit: usize) -> Result<Vec<(String, f32)>> {
-        self.tantivy.search(query, limit).await
+    pub async fn search_bm25(&self, query: &str, limit: usize, language: Option<&str>) -> Result<Vec<(String, f32)>> {
+        self.tantivy.search(query, limit, language).await
     }
     
     pub async fn search_ann(&self, query_embedding: &[f32], limit: usize) -> Result<Vec<(String, f32)>> {
@@ -1,23 +1,32 @@
 use anyhow::Result;
+use std::collections::HashMap;
 use std::path::Path;
 use std::sync::Arc;
 use tantivy::{
     collector::TopDocs,
     doc,
     query::{QueryParser, TermQuery},
-    schema::{Field, Schema, STORED, TEXT},
+    schema::{Field, FieldType, IndexRecordOption, Schema, TextFieldIndexing, TextOptions, STORED, STRING},
+    tokenizer::TokenizerManager,
     Document, Index, IndexReader, IndexWriter, Term,
 };
 use tokio::sync::Mutex;
-use tracing::info;
+use tracing::{info, warn};
 use types::Chunk;
 
+use crate::tokenizer::{self, IDENTIFIER_TOKENIZER};
+
+/// Text of chunks without a detected language, or in one without its own
+/// analyzer, goes to `text`; the rest to `text_<analyzer>`, e.g. `text_de`.
 pub struct TantivyStore {
     index: Arc<Index>,
     writer: Mutex<IndexWriter>,
     reader: IndexReader,
     text_field: Field,
+    /// Per-language text fields by analyzer name
+    language_fields: HashMap<&'static str, Field>,
     id_field: Field,
+    query_tokenizers: TokenizerManager,
 }
 
 impl TantivyStore {
@@ -26,8 +35,20 @@ impl TantivyStore {
         info!("Opening Tantivy index at {:?}", index_path);
         
         let mut schema_builder = Schema::builder();
-        let text_field = schema_builder.add_text_field("text", TEXT | STORED);
-        let id_field = schema_builder.add_text_field("id", STORED);
+        let text_indexing = TextFieldIndexing::default()
+            .set_tokenizer(IDENTIFIER_TOKENIZER)
+            .set_index_option(IndexRecordOption::WithFreqsAndPositions);
+        let text_options = TextOptions::default()
+            .set_indexing_options(text_indexing)
+            .set_stored();
+        schema_builder.add_text_field("text", text_options);
+        for name in tokenizer::language_analyzers() {
+            let indexing = TextFieldIndexing::default()
+                .set_tokenizer(&tokenizer::tokenizer_name(name))
+                .set_index_option(IndexRecordOption::WithFreqsAndPositions);
+            schema_builder.add_text_field(&language_field(name), TextOptions::default().set_indexing_options(indexing));
+        }
+        schema_builder.add_text_field("id", STRING | STORED);
         let schema = schema_builder.build();
         
         let index = if index_path.exists() {
@@ -35,6 +56,30 @@ impl TantivyStore {
         } else {
             Index::create_in_dir(&index_path, schema)?
         };
+        tokenizer::register_index_tokenizer(index.tokenizers());
+        
+        // Fields are looked up by name: an index created by an older version
+        // numbers them differently
+        let schema = index.schema();
+        let text_field = schema.get_field("text")?;
+        let id_field = schema.get_field("id")?;
+        
+        // Indexes created before identifier splitting keep their tokenizer
+        // until the data directory is rebuilt
+        if let FieldType::Str(options) = schema.get_field_entry(text_field).field_type() {
+            let current = options.get_indexing_options().map(|indexing| indexing.tokenizer());
+            if current != Some(IDENTIFIER_TOKENIZER) {
+                warn!("BM25 index uses the {:?} tokenizer; re-create it to split camelCase and snake_case identifiers", current);
+            }
+        }
+        
+        // Indexes created before per-language fields index all text as `text`
+        let language_fields: HashMap<&'static str, Field> = tokenizer::language_analyzers()
+            .filter_map(|name| Some((name, schema.get_field(&language_field(name)).ok()?)))
+            .collect();
+        if language_fields.is_empty() {
+            warn!("BM25 index has no per-language fields; re-create it to stem and search non-English text by language");
+        }
         
         let writer = index.writer(50_000_000)?; // 50MB buffer
         let reader = index.reader()?;
@@ -46,15 +91,23 @@ impl TantivyStore {
             writer: Mutex::new(writer),
             reader,
             text_field,
+            language_fields,
             id_field,
+            query_tokenizers: tokenizer::query_tokenizers(),
         })
     }
     
     pub async fn index_chunk(&self, chunk: &Chunk) -> Result<()> {
         let mut writer = self.writer.lock().await;
         
+        let language = chunk.metadata.get("language").and_then(|value| value.as_str());
+        let text_field = language
+            .and_then(tokenizer::analyzer_for_language)
+            .and_then(|analyzer| self.language_fields.get(analyzer))
+            .copied()
+            .unwrap_or(self.text_field);
         let doc = doc!(
-            self.text_field => chunk.text.clone(),
+            text_field => chunk.text.clone(),
             self.id_field => chunk.id.clone(),
         );
         
@@ -69,9 +122,34 @@ impl TantivyStore {
         Ok(())
     }
     
-    pub async fn search(&self, query: &str, limit: usize) -> Result<Vec<(String, f32)>> {
+    pub async fn delete_chunks(&self, chunk_ids: &[String]) -> Result<()> {
+        if chunk_ids.is_empty() {
+            return Ok(());
+        }
+        
+        let mut writer = self.writer.lock().await;
+        for chunk_id in chunk_ids {
+            writer.delete_term(Term::from_field_text(self.id_field, chunk_id));
+        }
+        writer.commit()?;
+        
+        Ok(())
+    }
+    
+    /// Searches text in `language` (an ISO 639-1 code) and text without a
+    /// language, or in every language when it is `None` or has no analyzer.
+    /// The query is analyzed the way each field's text was.
+    pub async fn search(&self, query: &str, limit: usize, language: Option<&str>) -> Result<Vec<(String, f32)>> {
         let searcher = self.reader.searcher();
-        let query_parser = QueryParser::for_index(&self.index, vec![self.text_field]);
+        let language_field = language
+            .and_then(tokenizer::analyzer_for_language)
+            .and_then(|analyzer| self.language_fields.get(analyzer));
+        let mut fields = vec![self.text_field];
+        match language_field {
+            Some(field) => fields.push(*field),
+            None => fields.extend(self.language_fields.values()),
+        }
+        let query_parser = QueryParser::new(self.index.schema(), fields, self.query_tokenizers.clone());
         let query = query_parser.parse_query(query)?;
         
         let top_docs = searcher.search(&query, &TopDocs::with_limit(limit))?;
@@ -87,3 +165,7 @@ impl TantivyStore {
         Ok(results)
     }
 }
+
+fn language_field(analyzer: &str) -> String {
+    format!("text_{}", analyzer)
+}
@@ -0,0 +1,296 @@
+use tantivy::tokenizer::{
+    Language, LowerCaser, RemoveLongFilter, Stemmer, StopWordFilter, TextAnalyzer, Token, TokenStream,
+    Tokenizer, TokenizerManager,
+};
+
+/// Name the identifier-aware analyzer is registered under.
+pub const IDENTIFIER_TOKENIZER: &str = "identifier";
+
+/// Analyzer for Chinese, Japanese and Korean, which aren't written with
+/// spaces between words
+pub const CJK_ANALYZER: &str = "cjk";
+
+/// Languages with a stemmer and stop words, by ISO 639-1 code. Each has its
+/// own analyzer, named by the code.
+const STEMMED_LANGUAGES: &[(&str, Language)] = &[
+    ("ar", Language::Arabic),
+    ("da", Language::Danish),
+    ("de", Language::German),
+    ("el", Language::Greek),
+    ("en", Language::English),
+    ("es", Language::Spanish),
+    ("fi", Language::Finnish),
+    ("fr", Language::French),
+    ("hu", Language::Hungarian),
+    ("it", Language::Italian),
+    ("nl", Language::Dutch),
+    ("no", Language::Norwegian),
+    ("pt", Language::Portuguese),
+    ("ro", Language::Romanian),
+    ("ru", Language::Russian),
+    ("sv", Language::Swedish),
+    ("ta", Language::Tamil),
+    ("tr", Language::Turkish),
+];
+
+/// Names of the per-language analyzers.
+pub fn language_analyzers() -> impl Iterator<Item = &'static str> {
+    STEMMED_LANGUAGES.iter().map(|(code, _)| *code).chain(std::iter::once(CJK_ANALYZER))
+}
+
+/// The analyzer for text in the language with this ISO 639-1 code, if
+/// there is one for it.
+pub fn analyzer_for_language(code: &str) -> Option<&'static str> {
+    match code {
+        "zh" | "ja" | "ko" => Some(CJK_ANALYZER),
+        _ => STEMMED_LANGUAGES.iter().map(|(name, _)| *name).find(|name| *name == code),
+    }
+}
+
+/// Name an analyzer is registered under, apart from tantivy's own.
+pub fn tokenizer_name(analyzer: &str) -> String {
+    format!("lang_{}", analyzer)
+}
+
+/// Splits text into words (runs of letters, digits and `_`) and, for
+/// identifiers like `parseHttpRequest` or `max_file_mb`, also emits their
+/// parts. The whole word shares its position with the first part and the
+/// parts follow at consecutive positions, so both `parseHttpRequest` and the
+/// phrase "http request" match.
+#[derive(Clone, Default)]
+pub struct IdentifierTokenizer {
+    /// Whether to emit the parts of identifiers, off for query text so a
+    /// searched identifier stays a single term
+    split: bool,
+}
+
+impl IdentifierTokenizer {
+    pub fn splitting() -> Self {
+        Self { split: true }
+    }
+
+    pub fn whole_words() -> Self {
+        Self { split: false }
+    }
+}
+
+impl Tokenizer for IdentifierTokenizer {
+    type TokenStream<'a> = IdentifierTokenStream;
+
+    fn token_stream<'a>(&'a mut self, text: &'a str) -> IdentifierTokenStream {
+        let mut tokens = Vec::new();
+        let mut position = 0;
+
+        for (start, word) in words(text) {
+            let parts = if self.split { identifier_parts(word) } else { Vec::new() };
+            tokens.push(token(text, start, start + word.len(), position));
+            if parts.len() > 1 {
+                for (i, (from, to)) in parts.into_iter().enumerate() {
+                    tokens.push(token(text, start + from, start + to, position + i));
+                }
+                position = tokens.last().map_or(position, |last| last.position) + 1;
+            } else {
+                position += 1;
+            }
+        }
+
+        IdentifierTokenStream { tokens, index: 0 }
+    }
+}
+
+pub struct IdentifierTokenStream {
+    tokens: Vec<Token>,
+    index: usize,
+}
+
+impl TokenStream for IdentifierTokenStream {
+    fn advance(&mut self) -> bool {
+        self.index += 1;
+        self.index <= self.tokens.len()
+    }
+
+    fn token(&self) -> &Token {
+        &self.tokens[self.index - 1]
+    }
+
+    fn token_mut(&mut self) -> &mut Token {
+        &mut self.tokens[self.index - 1]
+    }
+}
+
+/// Splits runs of Chinese, Japanese or Korean characters into overlapping
+/// pairs (`東京都` gives `東京`, `京都`), so words match without a dictionary
+/// to find their bounds. Other text is split into words as by
+/// `IdentifierTokenizer`.
+#[derive(Clone, Default)]
+pub struct CjkTokenizer;
+
+impl Tokenizer for CjkTokenizer {
+    type TokenStream<'a> = IdentifierTokenStream;
+
+    fn token_stream<'a>(&'a mut self, text: &'a str) -> IdentifierTokenStream {
+        let mut tokens = Vec::new();
+
+        // Words can mix scripts, as in `iPhone用`
+        for (start, word) in words(text) {
+            for (from, run, cjk) in script_runs(word) {
+                let from = start + from;
+                let chars: Vec<(usize, char)> = run.char_indices().collect();
+                if !cjk || chars.len() == 1 {
+                    tokens.push(token(text, from, from + run.len(), tokens.len()));
+                    continue;
+                }
+                for pair in chars.windows(2) {
+                    let to = pair[1].0 + pair[1].1.len_utf8();
+                    tokens.push(token(text, from + pair[0].0, from + to, tokens.len()));
+                }
+            }
+        }
+
+        IdentifierTokenStream { tokens, index: 0 }
+    }
+}
+
+/// Byte offset and text of each run of CJK or other characters in `word`,
+/// and whether it is CJK.
+fn script_runs(word: &str) -> Vec<(usize, &str, bool)> {
+    let mut runs = Vec::new();
+    let mut start = 0;
+    let mut cjk = word.chars().next().is_some_and(is_cjk);
+    for (i, c) in word.char_indices() {
+        if is_cjk(c) != cjk {
+            runs.push((start, &word[start..i], cjk));
+            start = i;
+            cjk = !cjk;
+        }
+    }
+    runs.push((start, &word[start..], cjk));
+    runs.retain(|(_, run, _)| !run.trim_matches('_').is_empty());
+    runs
+}
+
+/// Han, kana and Hangul.
+fn is_cjk(c: char) -> bool {
+    matches!(c,
+        '\u{3040}'..='\u{30ff}'       // Hiragana, Katakana
+        | '\u{3400}'..='\u{4dbf}'     // CJK Extension A
+        | '\u{4e00}'..='\u{9fff}'     // CJK Unified Ideographs
+        | '\u{ac00}'..='\u{d7af}'     // Hangul syllables
+        | '\u{1100}'..='\u{11ff}'     // Hangul Jamo
+        | '\u{f900}'..='\u{faff}'     // CJK Compatibility Ideographs
+        | '\u{ff66}'..='\u{ff9f}'     // Half-width Katakana
+        | '\u{20000}'..='\u{2ffff}'   // CJK Extensions B and later
+    )
+}
+
+/// Registers the splitting analyzer and the per-language analyzers for
+/// indexing on `manager`.
+pub fn register_index_tokenizer(manager: &TokenizerManager) {
+    manager.register(IDENTIFIER_TOKENIZER, analyzer(IdentifierTokenizer::splitting()));
+    for name in language_analyzers() {
+        manager.register(&tokenizer_name(name), language_analyzer(name, IdentifierTokenizer::splitting()));
+    }
+}
+
+/// Tokenizers for parsing queries: like the index's, but identifiers in the
+/// query are kept whole.
+pub fn query_tokenizers() -> TokenizerManager {
+    let manager = TokenizerManager::default();
+    manager.register(IDENTIFIER_TOKENIZER, analyzer(IdentifierTokenizer::whole_words()));
+    for name in language_analyzers() {
+        manager.register(&tokenizer_name(name), language_analyzer(name, IdentifierTokenizer::whole_words()));
+    }
+    manager
+}
+
+fn analyzer(tokenizer: IdentifierTokenizer) -> TextAnalyzer {
+    TextAnalyzer::builder(tokenizer)
+        .filter(RemoveLongFilter::limit(40))
+        .filter(LowerCaser)
+        .build()
+}
+
+/// Words lowercased, then stop words dropped and the rest stemmed, or CJK
+/// text as character pairs.
+fn language_analyzer(name: &str, tokenizer: IdentifierTokenizer) -> TextAnalyzer {
+    let Some(&(_, language)) = STEMMED_LANGUAGES.iter().find(|(code, _)| *code == name) else {
+        return TextAnalyzer::builder(CjkTokenizer)
+            .filter(RemoveLongFilter::limit(40))
+            .filter(LowerCaser)
+            .build();
+    };
+    let mut builder = TextAnalyzer::builder(tokenizer)
+        .filter(RemoveLongFilter::limit(40))
+        .filter(LowerCaser)
+        .dynamic();
+    if let Some(stop_words) = StopWordFilter::new(language) {
+        builder = builder.filter_dynamic(stop_words);
+    }
+    builder.filter_dynamic(Stemmer::new(language)).build()
+}
+
+fn token(text: &str, from: usize, to: usize, position: usize) -> Token {
+    Token {
+        offset_from: from,
+        offset_to: to,
+        position,
+        text: text[from..to].to_string(),
+        position_length: 1,
+    }
+}
+
+/// Byte offset and text of each run of letters, digits and underscores.
+fn words(text: &str) -> impl Iterator<Item = (usize, &str)> {
+    let mut start = None;
+    let mut words = Vec::new();
+    for (i, c) in text.char_indices().chain(std::iter::once((text.len(), ' '))) {
+        let in_word = c.is_alphanumeric() || c == '_';
+        match (in_word, start) {
+            (true, None) => start = Some(i),
+            (false, Some(from)) => {
+                let word = text[from..i].trim_matches('_');
+                if !word.is_empty() {
+                    let offset = from + text[from..i].find(word).unwrap_or(0);
+                    words.push((offset, word));
+                }
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    words.into_iter()
+}
+
+/// Byte ranges of the snake_case and camelCase parts of `word`:
+/// `parseHTTPRequest_v2` gives `parse`, `HTTP`, `Request`, `v2`.
+fn identifier_parts(word: &str) -> Vec<(usize, usize)> {
+    let chars: Vec<(usize, char)> = word.char_indices().collect();
+    let mut parts = Vec::new();
+    let mut start: Option<usize> = None;
+
+    for (i, &(offset, c)) in chars.iter().enumerate() {
+        if c == '_' {
+            if let Some(from) = start.take() {
+                parts.push((from, offset));
+            }
+            continue;
+        }
+        if let Some(from) = start {
+            let prev = chars[i - 1].1;
+            let next = chars.get(i + 1).map(|&(_, next)| next);
+            let lower_to_upper = (prev.is_lowercase() || prev.is_ascii_digit()) && c.is_uppercase();
+            // The last capital of an acronym starts the next word: HTTPRequest
+            let acronym_end = prev.is_uppercase() && c.is_uppercase() && next.is_some_and(char::is_lowercase);
+            if lower_to_upper || acronym_end {
+                parts.push((from, offset));
+                start = Some(offset);
+            }
+        } else {
+            start = Some(offset);
+        }
+    }
+    if let Some(from) = start {
+        parts.push((from, word.len()));
+    }
+    parts
+}
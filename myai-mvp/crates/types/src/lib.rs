@@ -1,7 +1,7 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use thiserror::Error;
 use utoipa::ToSchema;
 use uuid::Uuid;
@@ -48,18 +48,69 @@ pub struct QueryRequest {
     pub filters: Option<QueryFilters>,
     #[serde(default)]
     pub stream: bool,
+    /// Text returned with each hit besides its snippet
+    #[serde(default)]
+    pub context: ContextMode,
+    /// Chunks on either side of a hit included with `context: "window"`
+    #[serde(rename = "contextWindow", default = "default_context_window")]
+    pub context_window: u32,
+    /// ISO 639-1 code of the query's language (`de`); keyword search then
+    /// stems the query the way text in that language was stemmed. Without
+    /// it the query is matched against text in every language
+    #[serde(default)]
+    pub language: Option<String>,
 }
 
 fn default_k() -> u32 {
     10
 }
 
-#[derive(Debug, Serialize, Deserialize, ToSchema)]
+fn default_context_window() -> u32 {
+    1
+}
+
+/// How much of the document around a hit to return. Chunks are small so they
+/// match precisely; `parent` and `window` widen the hit for reading.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ContextMode {
+    /// Only the matching chunk
+    #[default]
+    Chunk,
+    /// The larger section the chunk was cut from
+    Parent,
+    /// The chunk with its neighbours, overlaps merged
+    Window,
+}
+
+impl std::str::FromStr for ContextMode {
+    type Err = String;
+    
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "chunk" => Ok(Self::Chunk),
+            "parent" => Ok(Self::Parent),
+            "window" => Ok(Self::Window),
+            _ => Err(format!("unknown context {:?}, expected chunk, parent or window", value)),
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
 pub struct QueryFilters {
     pub sources: Option<Vec<String>>,
     #[serde(rename = "mimeGroups")]
     pub mime_groups: Option<Vec<String>>,
     pub people: Option<Vec<String>>,
+    /// Chunks mentioning any of these organizations, e.g. `Acme`
+    pub organizations: Option<Vec<String>>,
+    /// Chunks mentioning any of these places
+    pub places: Option<Vec<String>>,
+    /// Notes carrying any of these tags; `project` also matches `project/alpha`
+    pub tags: Option<Vec<String>>,
+    /// Notes linking to or embedding this note, by path or name
+    #[serde(rename = "linksTo")]
+    pub links_to: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
@@ -82,6 +133,9 @@ pub struct SearchHit {
     pub metadata: Value,
     #[serde(rename = "createdAt")]
     pub created_at: DateTime<Utc>,
+    /// The parent section or neighbour window asked for with `context`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
@@ -104,8 +158,43 @@ pub struct IngestResult {
     pub doc_id: String,
     pub chunks: u32,
     pub skipped: u32,
+    /// The file matched what was already indexed and was not re-processed
+    #[serde(default)]
+    pub unchanged: bool,
     #[serde(rename = "tookMs")]
     pub took_ms: u64,
+    /// PII found in the file and what the privacy policy did about it
+    #[serde(default, skip_serializing_if = "PiiReport::is_empty")]
+    pub pii: PiiReport,
+}
+
+/// PII found while ingesting.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct PiiReport {
+    /// Matches by kind, e.g. `{"email": 3}`
+    pub found: BTreeMap<String, u32>,
+    /// Documents with at least one match
+    pub documents: u32,
+    /// Documents left out of the index by a `skip` policy
+    pub skipped: u32,
+    /// Chunks indexed for keyword search only under a `no-embed` policy
+    pub unembedded: u32,
+}
+
+impl PiiReport {
+    pub fn is_empty(&self) -> bool {
+        self.documents == 0
+    }
+    
+    /// Adds up the findings of another file.
+    pub fn merge(&mut self, other: &PiiReport) {
+        for (kind, count) in &other.found {
+            *self.found.entry(kind.clone()).or_default() += count;
+        }
+        self.documents += other.documents;
+        self.skipped += other.skipped;
+        self.unembedded += other.unembedded;
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
@@ -115,6 +204,89 @@ pub struct IngestTextRequest {
     pub source: Option<String>,
 }
 
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct BacklinksResponse {
+    pub note: String,
+    pub backlinks: Vec<Backlink>,
+}
+
+/// A document that links to or embeds the requested note.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct Backlink {
+    #[serde(rename = "docId")]
+    pub doc_id: String,
+    pub title: String,
+    pub path: String,
+    #[serde(rename = "modifiedAt")]
+    pub modified_at: DateTime<Utc>,
+}
+
+/// What a named entity found in a chunk is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum EntityKind {
+    Person,
+    Organization,
+    Place,
+    /// A calendar date, as `YYYY-MM-DD`
+    Date,
+}
+
+impl EntityKind {
+    pub const ALL: [EntityKind; 4] = [EntityKind::Person, EntityKind::Organization, EntityKind::Place, EntityKind::Date];
+    
+    /// Chunk metadata listing the entities of this kind.
+    pub fn metadata_key(self) -> &'static str {
+        match self {
+            EntityKind::Person => "people",
+            EntityKind::Organization => "organizations",
+            EntityKind::Place => "places",
+            EntityKind::Date => "dates",
+        }
+    }
+    
+    pub fn name(self) -> &'static str {
+        match self {
+            EntityKind::Person => "person",
+            EntityKind::Organization => "organization",
+            EntityKind::Place => "place",
+            EntityKind::Date => "date",
+        }
+    }
+}
+
+impl std::str::FromStr for EntityKind {
+    type Err = String;
+    
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        EntityKind::ALL
+            .into_iter()
+            .find(|kind| kind.name() == value)
+            .ok_or_else(|| format!("unknown entity kind {:?}, expected person, organization, place or date", value))
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct MentionsResponse {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kind: Option<EntityKind>,
+    pub documents: Vec<Mention>,
+}
+
+/// A document mentioning the requested entity.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct Mention {
+    #[serde(rename = "docId")]
+    pub doc_id: String,
+    pub title: String,
+    pub path: String,
+    #[serde(rename = "modifiedAt")]
+    pub modified_at: DateTime<Utc>,
+    /// Chunks of the document that mention it
+    pub chunks: u32,
+}
+
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct StatusResponse {
     pub version: String,
@@ -123,7 +295,7 @@ pub struct StatusResponse {
     pub uptime: u64,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AppConfig {
     pub paths: PathsConfig,
     pub api: ApiConfig,
@@ -132,7 +304,7 @@ pub struct AppConfig {
     pub ingest: IngestConfig,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PathsConfig {
     #[serde(rename = "dataDir")]
     pub data_dir: String,
@@ -142,14 +314,14 @@ pub struct PathsConfig {
     pub watch_paths: Vec<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ApiConfig {
     pub bind: String,
     #[serde(rename = "corsOrigins")]
     pub cors_origins: Vec<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RetrievalConfig {
     #[serde(rename = "bm25K1")]
     pub bm25_k1: f32,
@@ -164,7 +336,7 @@ pub struct RetrievalConfig {
     pub final_top: usize,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PrivacyConfig {
     #[serde(rename = "enableSqlcipher")]
     pub enable_sqlcipher: bool,
@@ -172,13 +344,251 @@ pub struct PrivacyConfig {
     pub max_file_mb: u64,
     #[serde(rename = "allowedMimeGroups")]
     pub allowed_mime_groups: Vec<String>,
+    /// What ingest does with documents containing PII
+    #[serde(default)]
+    pub pii: PiiConfig,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PiiConfig {
+    /// Policy for documents no rule matches
+    #[serde(default)]
+    pub action: PiiAction,
+    /// Kinds of PII to look for; all of them when empty
+    #[serde(default)]
+    pub kinds: Vec<PiiKind>,
+    /// Policies per source; the first matching rule applies
+    #[serde(default)]
+    pub rules: Vec<PiiRule>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Applies one policy to the documents matching any of `match`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PiiRule {
+    /// MIME types (`message/rfc822`), MIME groups (`email`) or globs matched
+    /// against the path and file name (`*.env`, `**/finance/**`)
+    #[serde(rename = "match")]
+    pub patterns: Vec<String>,
+    pub action: PiiAction,
+}
+
+/// What happens to a document that contains PII.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PiiAction {
+    /// Don't scan
+    Off,
+    /// Index as usual and list the kinds found in each chunk's `pii` metadata
+    #[default]
+    Tag,
+    /// Replace each match with its kind (`[EMAIL]`) in the stored text
+    Mask,
+    /// Keep chunks with PII out of the vector index, so they are only found
+    /// by keyword search
+    NoEmbed,
+    /// Leave the document out of the index
+    Skip,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PiiKind {
+    Email,
+    Phone,
+    /// Card numbers passing the Luhn check
+    CreditCard,
+    /// US Social Security and UK National Insurance numbers
+    NationalId,
+    /// Tokens of well-known services and `api_key = ...` style assignments
+    ApiKey,
+    /// PEM private key blocks
+    PrivateKey,
+}
+
+impl PiiKind {
+    pub const ALL: [PiiKind; 6] = [
+        PiiKind::Email,
+        PiiKind::Phone,
+        PiiKind::CreditCard,
+        PiiKind::NationalId,
+        PiiKind::ApiKey,
+        PiiKind::PrivateKey,
+    ];
+    
+    /// Name used in metadata and reports, as in the config.
+    pub fn name(self) -> &'static str {
+        match self {
+            PiiKind::Email => "email",
+            PiiKind::Phone => "phone",
+            PiiKind::CreditCard => "credit_card",
+            PiiKind::NationalId => "national_id",
+            PiiKind::ApiKey => "api_key",
+            PiiKind::PrivateKey => "private_key",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct IngestConfig {
+    /// Embedding model tokens per chunk, capped to what the model reads
     #[serde(rename = "chunkSize")]
     pub chunk_size: usize,
+    /// Tokens shared by consecutive chunks
     pub overlap: usize,
+    /// Glob patterns a file must match to be ingested from a directory (empty = all)
+    #[serde(default)]
+    pub include: Vec<String>,
+    /// Glob patterns for files and directories to leave out of directory ingestion
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    #[serde(rename = "followSymlinks", default)]
+    pub follow_symlinks: bool,
+    #[serde(rename = "includeHidden", default)]
+    pub include_hidden: bool,
+    /// Number of files processed concurrently during directory ingestion
+    #[serde(default = "default_workers")]
+    pub workers: usize,
+    /// Quiet period before a burst of changes to a watched file is re-ingested
+    #[serde(rename = "watchDebounceMs", default = "default_watch_debounce_ms")]
+    pub watch_debounce_ms: u64,
+    #[serde(default)]
+    pub semantic: SemanticChunkingConfig,
+    /// Tokens per parent section; chunks link to the one they start in
+    #[serde(rename = "parentSize", default = "default_parent_size")]
+    pub parent_size: usize,
+    /// How files are chunked, by MIME type or glob; the first matching rule
+    /// applies and other files get fixed-size chunks
+    #[serde(default)]
+    pub chunking: Vec<ChunkingRule>,
+    #[serde(default)]
+    pub entities: EntityConfig,
+}
+
+/// Finding people, organizations, places and dates in chunks.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EntityConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Names the NER model is less sure of than this are dropped
+    #[serde(rename = "minScore", default = "default_entity_min_score")]
+    pub min_score: f32,
+}
+
+impl Default for EntityConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            min_score: default_entity_min_score(),
+        }
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_entity_min_score() -> f32 {
+    0.6
+}
+
+/// Chunks the files matching any of `match` with one strategy.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChunkingRule {
+    /// MIME types (`text/csv`), MIME groups (`email`) or globs matched
+    /// against the path and file name (`*.log`, `**/chats/**`)
+    #[serde(rename = "match")]
+    pub patterns: Vec<String>,
+    pub strategy: ChunkingStrategyKind,
+    /// Tokens per chunk; defaults to `chunkSize`
+    #[serde(rename = "chunkSize", default)]
+    pub chunk_size: Option<usize>,
+    /// Tokens shared by consecutive `fixed` chunks; defaults to `overlap`
+    #[serde(default)]
+    pub overlap: Option<usize>,
+    /// Lines per `record` chunk
+    #[serde(default = "default_rows")]
+    pub rows: usize,
+}
+
+fn default_rows() -> usize {
+    1
+}
+
+/// Where chunks are cut. All but `fixed` keep whole units together, cutting
+/// a unit too long for a chunk into finer ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChunkingStrategyKind {
+    /// Runs of `chunkSize` tokens with `overlap`, ending at a sentence or word
+    Fixed,
+    /// As many whole sentences as fit
+    Sentence,
+    /// As many whole paragraphs (separated by blank lines) as fit
+    Paragraph,
+    /// One chunk per Markdown heading and the text below it
+    Markdown,
+    /// Blocks of code between blank lines, never cutting a line
+    Code,
+    /// `rows` lines per chunk, for logs, chats and table rows
+    Record,
+    /// By topic, with the settings in `[ingest.semantic]`
+    Semantic,
+}
+
+fn default_parent_size() -> usize {
+    1024
+}
+
+fn default_workers() -> usize {
+    4
+}
+
+fn default_watch_debounce_ms() -> u64 {
+    1000
+}
+
+/// Chunking by topic: sentences are embedded and a chunk ends where the
+/// next sentence is least like the ones before it. Applies to files matched
+/// by a `semantic` chunking rule, or listed in `mimeTypes`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SemanticChunkingConfig {
+    /// MIME types or groups (`text/markdown`, `email`) chunked by topic,
+    /// unless a `[[ingest.chunking]]` rule matches first
+    #[serde(rename = "mimeTypes", default)]
+    pub mime_types: Vec<String>,
+    /// A topic shift doesn't end a chunk shorter than this, in tokens
+    #[serde(rename = "minTokens", default = "default_semantic_min_tokens")]
+    pub min_tokens: usize,
+    /// Chunks end here even without a topic shift
+    #[serde(rename = "maxTokens", default = "default_semantic_max_tokens")]
+    pub max_tokens: usize,
+    /// A shift counts when the distance between neighbouring sentences is
+    /// above this percentile of all such distances in the section
+    #[serde(rename = "breakpointPercentile", default = "default_breakpoint_percentile")]
+    pub breakpoint_percentile: f32,
+}
+
+impl Default for SemanticChunkingConfig {
+    fn default() -> Self {
+        Self {
+            mime_types: vec![],
+            min_tokens: default_semantic_min_tokens(),
+            max_tokens: default_semantic_max_tokens(),
+            breakpoint_percentile: default_breakpoint_percentile(),
+        }
+    }
+}
+
+fn default_semantic_min_tokens() -> usize {
+    64
+}
+
+fn default_semantic_max_tokens() -> usize {
+    384
+}
+
+fn default_breakpoint_percentile() -> f32 {
+    90.0
 }
 
 impl Default for AppConfig {
@@ -205,11 +615,33 @@ impl Default for AppConfig {
             privacy: PrivacyConfig {
                 enable_sqlcipher: false,
                 max_file_mb: 500,
-                allowed_mime_groups: vec!["pdf".to_string(), "text".to_string()],
+                allowed_mime_groups: vec![
+                    "pdf".to_string(),
+                    "text".to_string(),
+                    "html".to_string(),
+                    "email".to_string(),
+                    "office".to_string(),
+                    "archive".to_string(),
+                    "browser".to_string(),
+                    "ebook".to_string(),
+                    "image".to_string(),
+                    "audio".to_string(),
+                ],
+                pii: PiiConfig::default(),
             },
             ingest: IngestConfig {
-                chunk_size: 800,
-                overlap: 120,
+                chunk_size: 256,
+                overlap: 32,
+                include: vec![],
+                exclude: vec![],
+                follow_symlinks: false,
+                include_hidden: false,
+                workers: default_workers(),
+                watch_debounce_ms: default_watch_debounce_ms(),
+                semantic: SemanticChunkingConfig::default(),
+                parent_size: default_parent_size(),
+                chunking: vec![],
+                entities: EntityConfig::default(),
             },
         }
     }
@@ -224,8 +656,26 @@ pub struct Chunk {
     pub embedding: Option<Vec<f32>>,
     pub metadata: HashMap<String, Value>,
     pub created_at: DateTime<Utc>,
+    /// The parent section the chunk was cut from
+    pub parent_id: Option<String>,
+    /// Order of the chunk within its document
+    pub position: u32,
+}
+
+/// A larger span of a document that chunks are cut from. Parents are stored
+/// but not indexed; a search can return one in place of a matching chunk.
+#[derive(Debug, Clone)]
+pub struct ParentChunk {
+    /// Hash of the document id and text, so unchanged sections keep their id
+    pub id: String,
+    pub doc_id: String,
+    pub text: String,
 }
 
+/// Separates a file path from a document stored inside that file, as in
+/// `inbox.mbox!/<message-id>`.
+pub const PART_SEPARATOR: &str = "!/";
+
 #[derive(Debug, Clone)]
 pub struct Document {
     pub id: String,
@@ -234,6 +684,12 @@ pub struct Document {
     pub modified_at: DateTime<Utc>,
     pub source: String,
     pub mime: String,
+    /// Size in bytes of the source file when it was ingested
+    pub size: u64,
+    /// Blake3 hash of the source file contents
+    pub content_hash: String,
+    /// Metadata from the file itself, e.g. author or front matter
+    pub metadata: HashMap<String, Value>,
 }
 
 impl Chunk {
@@ -245,6 +701,8 @@ impl Chunk {
             embedding: None,
             metadata: HashMap::new(),
             created_at: Utc::now(),
+            parent_id: None,
+            position: 0,
         }
     }
 }
@@ -258,6 +716,14 @@ impl Document {
             modified_at: Utc::now(),
             source,
             mime,
+            size: 0,
+            content_hash: String::new(),
+            metadata: HashMap::new(),
         }
     }
+    
+    /// Path of a document stored inside the file at `path`.
+    pub fn part_path(path: &str, part: &str) -> String {
+        format!("{}{}{}", path, PART_SEPARATOR, part)
+    }
 }
@@ -2,6 +2,7 @@ use anyhow::Result;
 use clap::{Parser, Subcommand};
 use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::signal;
 use tokio::sync::broadcast;
 use tracing::{error, info, warn};
@@ -30,10 +31,62 @@ enum Commands {
     /// Ingest a file or directory
     Ingest {
         path: PathBuf,
+        /// Only ingest files matching this glob (repeatable)
+        #[arg(long)]
+        include: Vec<String>,
+        /// Skip files and directories matching this glob (repeatable)
+        #[arg(long)]
+        exclude: Vec<String>,
+        /// Follow symbolic links while walking directories
+        #[arg(long)]
+        follow_symlinks: bool,
+        /// Include hidden files and directories
+        #[arg(long)]
+        hidden: bool,
+        /// Number of files to process concurrently
+        #[arg(short, long)]
+        jobs: Option<usize>,
     },
     /// Query the index
     Query {
         text: String,
+        /// Only notes with this tag (repeatable)
+        #[arg(long)]
+        tag: Vec<String>,
+        /// Only notes linking to this note, by path or name
+        #[arg(long)]
+        links_to: Option<String>,
+        /// Only text mentioning this person, by name, address or handle (repeatable)
+        #[arg(long)]
+        person: Vec<String>,
+        /// Only text mentioning this organization (repeatable)
+        #[arg(long)]
+        org: Vec<String>,
+        /// Only text mentioning this place (repeatable)
+        #[arg(long)]
+        place: Vec<String>,
+        /// Text to print with each hit: chunk, parent or window
+        #[arg(long, default_value = "chunk")]
+        context: types::ContextMode,
+        /// Chunks on either side of a hit printed with `--context window`
+        #[arg(long, default_value_t = 1)]
+        window: u32,
+        /// Language of the query as an ISO 639-1 code, e.g. `de`
+        #[arg(long)]
+        language: Option<String>,
+    },
+    /// List the notes that link to a note
+    Backlinks {
+        /// Path or name of the note
+        note: String,
+    },
+    /// List the documents that mention a person, organization, place or date
+    Mentions {
+        /// Name, address or `YYYY-MM-DD` date
+        name: String,
+        /// Only entities of this kind: person, organization, place or date
+        #[arg(long)]
+        kind: Option<types::EntityKind>,
     },
 }
 
@@ -51,8 +104,41 @@ async fn main() -> Result<()> {
     
     match cli.command {
         Some(Commands::Run) => run_server(config).await?,
-        Some(Commands::Ingest { path }) => ingest_path(config, &path).await?,
-        Some(Commands::Query { text }) => query_text(config, &text).await?,
+        Some(Commands::Ingest {
+            path,
+            include,
+            exclude,
+            follow_symlinks,
+            hidden,
+            jobs,
+        }) => {
+            let mut options = ingest::WalkOptions::from_config(&config.ingest);
+            options.include.extend(include);
+            options.exclude.extend(exclude);
+            options.follow_symlinks |= follow_symlinks;
+            options.include_hidden |= hidden;
+            let workers = jobs.unwrap_or(config.ingest.workers);
+            ingest_path(config, &path, &options, workers).await?
+        }
+        Some(Commands::Query { text, tag, links_to, person, org, place, context, window, language }) => {
+            let filtered = !tag.is_empty()
+                || links_to.is_some()
+                || !person.is_empty()
+                || !org.is_empty()
+                || !place.is_empty();
+            let given = |list: Vec<String>| Some(list).filter(|list| !list.is_empty());
+            let filters = filtered.then(|| types::QueryFilters {
+                tags: given(tag),
+                links_to,
+                people: given(person),
+                organizations: given(org),
+                places: given(place),
+                ..Default::default()
+            });
+            query_text(config, &text, filters, context, window, language).await?
+        }
+        Some(Commands::Backlinks { note }) => list_backlinks(config, &note).await?,
+        Some(Commands::Mentions { name, kind }) => list_mentions(config, &name, kind).await?,
         None => run_server(config).await?,
     }
     
@@ -86,15 +172,22 @@ async fn run_server(config: AppConfig) -> Result<()> {
     let models = Arc::new(ModelManager::new(&config).await?);
     let storage = Arc::new(StorageManager::new(&config.paths.data_dir).await?);
     let index = Arc::new(HybridIndex::new(storage.clone(), models.clone(), config.clone()).await?);
+    let pipeline = Arc::new(ingest::IngestPipeline::new(config.clone(), index.clone())?);
     
     // Create progress channel
     let (progress_tx, _) = broadcast::channel(100);
     
+    // Keep watched folders in sync with the index
+    if !config.paths.watch_paths.is_empty() {
+        spawn_watcher(&config, pipeline.clone(), progress_tx.clone())?;
+    }
+    
     // Create app state
     let state = AppState {
         index,
         models,
         storage,
+        pipeline,
         progress_tx,
     };
     
@@ -115,7 +208,42 @@ async fn run_server(config: AppConfig) -> Result<()> {
     Ok(())
 }
 
-async fn ingest_path(config: AppConfig, path: &PathBuf) -> Result<()> {
+fn spawn_watcher(
+    config: &AppConfig,
+    pipeline: Arc<ingest::IngestPipeline>,
+    progress_tx: broadcast::Sender<String>,
+) -> Result<()> {
+    let paths = config
+        .paths
+        .watch_paths
+        .iter()
+        .map(|path| expand_path(path))
+        .collect::<Result<Vec<_>>>()?;
+    
+    let watcher = ingest::FileWatcher::new(
+        pipeline,
+        paths,
+        &ingest::WalkOptions::from_config(&config.ingest),
+        config.ingest.workers,
+        Duration::from_millis(config.ingest.watch_debounce_ms),
+        progress_tx,
+    )?;
+    
+    tokio::spawn(async move {
+        if let Err(e) = watcher.run().await {
+            error!("File watcher stopped: {}", e);
+        }
+    });
+    
+    Ok(())
+}
+
+async fn ingest_path(
+    config: AppConfig,
+    path: &PathBuf,
+    options: &ingest::WalkOptions,
+    workers: usize,
+) -> Result<()> {
     info!("Ingesting path: {:?}", path);
     
     // Initialize components
@@ -124,15 +252,44 @@ async fn ingest_path(config: AppConfig, path: &PathBuf) -> Result<()> {
     let index = Arc::new(HybridIndex::new(storage.clone(), models.clone(), config.clone()).await?);
     
     // Create ingest pipeline
-    let pipeline = ingest::IngestPipeline::new(config)?;
+    let pipeline = ingest::IngestPipeline::new(config, index.clone())?;
     
     if path.is_file() {
         let result = pipeline.ingest_path(path).await?;
-        info!("Ingested file: {} chunks, {} skipped, {}ms", 
-              result.chunks, result.skipped, result.took_ms);
+        if result.unchanged {
+            info!("File unchanged since last ingest, skipped");
+        } else {
+            info!("Ingested file: {} chunks, {} skipped, {}ms", 
+                  result.chunks, result.skipped, result.took_ms);
+        }
+        if !result.pii.is_empty() {
+            println!("PII found: {}", describe_pii(&result.pii));
+        }
     } else if path.is_dir() {
-        // TODO: Implement directory ingestion
-        warn!("Directory ingestion not implemented yet");
+        let report = pipeline.ingest_dir(path, options, workers).await?;
+        
+        println!(
+            "Ingested {} files ({} chunks), unchanged {}, skipped {}, failed {} in {}ms",
+            report.ingested,
+            report.chunks,
+            report.unchanged,
+            report.skipped,
+            report.failed.len(),
+            report.took_ms
+        );
+        for (failed_path, reason) in &report.failed {
+            println!("  failed: {} ({})", failed_path.display(), reason);
+        }
+        if !report.pii.is_empty() {
+            let mut total = types::PiiReport::default();
+            for (_, pii) in &report.pii {
+                total.merge(pii);
+            }
+            println!("PII found in {} files: {}", report.pii.len(), describe_pii(&total));
+            for (pii_path, pii) in &report.pii {
+                println!("  {}: {}", pii_path.display(), describe_pii(pii));
+            }
+        }
     } else {
         return Err(anyhow::anyhow!("Path does not exist: {:?}", path));
     }
@@ -140,7 +297,32 @@ async fn ingest_path(config: AppConfig, path: &PathBuf) -> Result<()> {
     Ok(())
 }
 
-async fn query_text(config: AppConfig, text: &str) -> Result<()> {
+/// Counts by kind and what the privacy policy did, e.g.
+/// `2 email, 1 api_key (1 document skipped)`.
+fn describe_pii(pii: &types::PiiReport) -> String {
+    let found: Vec<String> = pii.found.iter().map(|(kind, count)| format!("{} {}", count, kind)).collect();
+    let mut actions = Vec::new();
+    if pii.skipped > 0 {
+        actions.push(format!("{} documents skipped", pii.skipped));
+    }
+    if pii.unembedded > 0 {
+        actions.push(format!("{} chunks not embedded", pii.unembedded));
+    }
+    if actions.is_empty() {
+        found.join(", ")
+    } else {
+        format!("{} ({})", found.join(", "), actions.join(", "))
+    }
+}
+
+async fn query_text(
+    config: AppConfig,
+    text: &str,
+    filters: Option<types::QueryFilters>,
+    context: types::ContextMode,
+    context_window: u32,
+    language: Option<String>,
+) -> Result<()> {
     info!("Querying: {}", text);
     
     // Initialize components
@@ -154,8 +336,11 @@ async fn query_text(config: AppConfig, text: &str) -> Result<()> {
         k: 10,
         date_from: None,
         date_to: None,
-        filters: None,
+        filters,
         stream: false,
+        context,
+        context_window,
+        language,
     };
     
     // Execute search
@@ -166,6 +351,12 @@ async fn query_text(config: AppConfig, text: &str) -> Result<()> {
     for (i, hit) in hits.iter().enumerate() {
         println!("{}. {} (score: {:.3})", i + 1, hit.title, hit.score);
         println!("   {}", hit.snippet);
+        if let Some(context) = &hit.context {
+            println!();
+            for line in context.lines() {
+                println!("   | {}", line);
+            }
+        }
         println!();
     }
     
@@ -177,6 +368,30 @@ async fn query_text(config: AppConfig, text: &str) -> Result<()> {
     Ok(())
 }
 
+async fn list_backlinks(config: AppConfig, note: &str) -> Result<()> {
+    let storage = StorageManager::new(&config.paths.data_dir).await?;
+    let docs = storage.backlinks(note).await?;
+    
+    println!("{} notes link to {}:", docs.len(), note);
+    for doc in &docs {
+        println!("  {} ({})", doc.title, doc.path);
+    }
+    
+    Ok(())
+}
+
+async fn list_mentions(config: AppConfig, name: &str, kind: Option<types::EntityKind>) -> Result<()> {
+    let storage = StorageManager::new(&config.paths.data_dir).await?;
+    let docs = storage.mentions(name, kind).await?;
+    
+    println!("{} documents mention {}:", docs.len(), name);
+    for (doc, chunks) in &docs {
+        println!("  {} ({}), {} chunks", doc.title, doc.path, chunks);
+    }
+    
+    Ok(())
+}
+
 async fn ensure_directories(config: &AppConfig) -> Result<()> {
     let data_dir = expand_path(&config.paths.data_dir)?;
     let model_dir = expand_path(&config.paths.model_dir)?;